@@ -2,6 +2,14 @@ const loginLink = document.getElementById("login-link");
 const logoutLink = document.getElementById("logout-link");
 const protectImg = document.getElementById("protected-img");
 
+// Set by the auth service at login. Cookies aren't scoped by port, so this page can read it.
+function csrfToken() {
+    const cookie = document.cookie
+        .split("; ")
+        .find(cookie => cookie.startsWith("csrf_token=") || cookie.startsWith("__Host-csrf_token="));
+    return cookie ? cookie.split("=")[1] : "";
+}
+
 logoutLink.addEventListener("click", (e) => {
     e.preventDefault();
 
@@ -10,6 +18,10 @@ logoutLink.addEventListener("click", (e) => {
     fetch(url, {
         method: 'POST',
         credentials: 'include', // This will include cookies in the request
+        headers: {
+            // Proves the request comes from a page that can read the auth service's cookies
+            'X-CSRF-Token': csrfToken(),
+        },
     }).then(response => {
         if (response.ok) {
             loginLink.style.display = "block";
@@ -1,27 +1,138 @@
-use std::env;
+use std::{
+    env,
+    time::{Duration, Instant},
+};
 
 use askama::Template;
 use axum::{
-    http::StatusCode,
+    extract::State,
+    http::{HeaderMap, HeaderName, StatusCode},
     response::{Html, IntoResponse},
     routing::get,
     Json, Router,
 };
 use axum_extra::extract::CookieJar;
 use serde::Serialize;
-use tower_http::services::ServeDir;
+use tower_http::{
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
+
+use telemetry::{
+    init_tracing, make_request_span, on_response, trace_context_headers, LogFormat,
+    REQUEST_ID_HEADER,
+};
+use tracing::Instrument;
+
+mod metrics;
+mod telemetry;
 
 #[tokio::main]
 async fn main() {
+    let log_format = match env::var("LOG_FORMAT") {
+        Ok(format) if !format.is_empty() => format
+            .parse()
+            .expect("LOG_FORMAT must be either \"pretty\" or \"json\"."),
+        _ => LogFormat::Pretty,
+    };
+    let otlp_endpoint = env::var("OTEL_EXPORTER_OTLP_ENDPOINT")
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty());
+    let tracer_provider = init_tracing(log_format, otlp_endpoint.as_deref());
+
+    let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+
+    let prometheus_handle = metrics::install_recorder();
+
+    let state = AppState::from_env().unwrap_or_else(|e| {
+        tracing::error!(error = %e, "invalid configuration");
+        std::process::exit(1);
+    });
+
     let app = Router::new()
         .nest_service("/assets", ServeDir::new("assets"))
         .route("/", get(root))
-        .route("/protected", get(protected));
+        .route("/protected", get(protected))
+        .route("/health/live", get(health_live))
+        .route("/health/ready", get(health_ready))
+        .route(
+            "/metrics",
+            get(move || async move { metrics::render(&prometheus_handle) }),
+        )
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(make_request_span)
+                .on_request(())
+                .on_response(on_response)
+                .on_failure(()),
+        )
+        .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+        .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid))
+        .with_state(state);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await.unwrap();
 
-    println!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    tracing::info!("listening on {}", listener.local_addr().unwrap());
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+
+    // Send any spans still waiting to be exported
+    let _ = tracer_provider.shutdown();
+}
+
+// How auth-service is reached, read once at startup
+#[derive(Clone)]
+struct AppState {
+    http_client: reqwest::Client,
+    // Base URL browsers are sent to, e.g. "https://auth.example.com"
+    auth_service_url: String,
+    // Base URL auth-service is called on from here, e.g. "http://auth-service:3000" inside Docker
+    auth_service_internal_url: String,
+}
+
+impl AppState {
+    fn from_env() -> Result<Self, String> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        let auth_service_url = var("AUTH_SERVICE_URL")
+            .unwrap_or("http://localhost:3000".to_owned())
+            .trim_end_matches('/')
+            .to_owned();
+        let auth_service_internal_url = var("AUTH_SERVICE_INTERNAL_URL")
+            .map(|url| url.trim_end_matches('/').to_owned())
+            .unwrap_or(auth_service_url.clone());
+        for (name, url) in [
+            ("AUTH_SERVICE_URL", &auth_service_url),
+            ("AUTH_SERVICE_INTERNAL_URL", &auth_service_internal_url),
+        ] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(format!("{} must be an http:// or https:// URL", name));
+            }
+        }
+
+        // A private CA auth-service's certificate is issued by, trusted on top of the public ones
+        let mut http_client = reqwest::Client::builder();
+        if let Some(path) = var("AUTH_SERVICE_CA_CERT_PATH") {
+            let pem = std::fs::read(&path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+            let certificate = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| format!("invalid certificate {}: {}", path, e))?;
+            http_client = http_client.add_root_certificate(certificate);
+        }
+        let http_client = http_client.build().map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            http_client,
+            auth_service_url,
+            auth_service_internal_url,
+        })
+    }
+
+    fn auth_service_internal_url(&self, path: &str) -> String {
+        format!("{}{}", self.auth_service_internal_url, path)
+    }
 }
 
 #[derive(Template)]
@@ -31,13 +142,9 @@ struct IndexTemplate {
     logout_link: String,
 }
 
-async fn root() -> impl IntoResponse {
-    let mut address = env::var("AUTH_SERVICE_IP").unwrap_or("localhost".to_owned());
-    if address.is_empty() {
-        address = "localhost".to_owned();
-    }
-    let login_link = format!("http://{}:3000", address);
-    let logout_link = format!("http://{}:3000/logout", address);
+async fn root(State(state): State<AppState>) -> impl IntoResponse {
+    let login_link = state.auth_service_url.clone();
+    let logout_link = format!("{}/logout", state.auth_service_url);
 
     let template = IndexTemplate {
         login_link,
@@ -46,26 +153,55 @@ async fn root() -> impl IntoResponse {
     Html(template.render().unwrap())
 }
 
-async fn protected(jar: CookieJar) -> impl IntoResponse {
-    let jwt_cookie = match jar.get("jwt") {
+async fn protected(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let jwt_cookie = match jar.get(&auth_cookie_name()) {
         Some(cookie) => cookie,
         None => {
             return StatusCode::UNAUTHORIZED.into_response();
         }
     };
 
-    let api_client = reqwest::Client::builder().build().unwrap();
-
     let verify_token_body = serde_json::json!({
         "token": &jwt_cookie.value(),
     });
 
-    let auth_hostname = env::var("AUTH_SERVICE_HOST_NAME").unwrap_or("0.0.0.0".to_owned());
-    let url = format!("http://{}:3000/verify-token", auth_hostname);
+    let url = state.auth_service_internal_url("/verify-token");
+
+    let mut request = state.http_client.post(&url).json(&verify_token_body);
+    // Lets the auth service's logs for this call be matched up with ours
+    if let Some(request_id) = headers
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        request = request.header(REQUEST_ID_HEADER, request_id);
+    }
+
+    // A child span for the call, whose context auth-service continues from
+    let span = tracing::info_span!("verify_token", url = %url);
+    for (name, value) in trace_context_headers(&span) {
+        request = request.header(name, value);
+    }
+
+    let started = Instant::now();
+    let response = request.send().instrument(span).await;
+    metrics::record_upstream_call(
+        "auth-service",
+        "verify_token",
+        response
+            .as_ref()
+            .ok()
+            .map(|response| response.status().as_u16()),
+        started.elapsed(),
+    );
 
-    let response = match api_client.post(&url).json(&verify_token_body).send().await {
+    let response = match response {
         Ok(response) => response,
-        Err(_) => {
+        Err(e) => {
+            tracing::error!(error = %e, "failed to call auth service");
             return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
     };
@@ -82,7 +218,121 @@ async fn protected(jar: CookieJar) -> impl IntoResponse {
     }
 }
 
+// How long readiness waits for auth-service before reporting it unreachable
+const AUTH_SERVICE_HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Answers as long as the process is serving requests at all
+async fn health_live() -> impl IntoResponse {
+    Json(LivenessResponse {
+        status: HealthStatus::Ok,
+    })
+}
+
+// Protected pages can't be served while auth-service is unreachable
+async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let started = Instant::now();
+    let response = state
+        .http_client
+        .get(state.auth_service_internal_url("/health/live"))
+        .timeout(AUTH_SERVICE_HEALTH_TIMEOUT)
+        .send()
+        .await;
+    metrics::record_upstream_call(
+        "auth-service",
+        "health_check",
+        response
+            .as_ref()
+            .ok()
+            .map(|response| response.status().as_u16()),
+        started.elapsed(),
+    );
+
+    let auth_service = match response {
+        Ok(response) if response.status().is_success() => HealthStatus::Ok,
+        _ => HealthStatus::Unavailable,
+    };
+
+    let status_code = match auth_service {
+        HealthStatus::Ok => StatusCode::OK,
+        HealthStatus::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status: auth_service,
+            components: ComponentsHealth { auth_service },
+        }),
+    )
+}
+
+// The name auth-service gives its auth cookie, from the same variables it reads
+fn auth_cookie_name() -> String {
+    let name = env::var("JWT_COOKIE_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("jwt".to_owned());
+    let host_prefix = env::var("JWT_COOKIE_HOST_PREFIX")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"));
+
+    if host_prefix {
+        format!("__Host-{}", name)
+    } else {
+        name
+    }
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Serialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
+
+#[derive(Serialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub components: ComponentsHealth,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentsHealth {
+    pub auth_service: HealthStatus,
+}
+
 #[derive(Serialize)]
 pub struct ProtectedRouteResponse {
     pub img_url: String,
 }
+
+// Resolves on SIGINT (Ctrl+C) or SIGTERM, after which in-flight requests are allowed to finish
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("received SIGTERM, shutting down"),
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use axum::{http::header, response::IntoResponse};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+// Upper bounds of the latency histogram buckets, from a millisecond to 10 seconds
+const LATENCY_BUCKETS_SECONDS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+// Installs the global Prometheus recorder. Metrics recorded before then are dropped.
+pub fn install_recorder() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("_duration_seconds".to_owned()),
+            LATENCY_BUCKETS_SECONDS,
+        )
+        .expect("Latency buckets must not be empty")
+        .install_recorder()
+        .expect("Failed to install the Prometheus recorder")
+}
+
+// Serves every recorded metric in the Prometheus text exposition format
+pub fn render(handle: &PrometheusHandle) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
+
+// Records a call to another service. `status` is `None` when no response came back.
+pub fn record_upstream_call(
+    upstream: &'static str,
+    operation: &'static str,
+    status: Option<u16>,
+    latency: Duration,
+) {
+    let status = status.map_or_else(|| "error".to_owned(), |status| status.to_string());
+
+    metrics::counter!(
+        "upstream_requests_total",
+        "upstream" => upstream,
+        "operation" => operation,
+        "status" => status.clone()
+    )
+    .increment(1);
+    metrics::histogram!(
+        "upstream_request_duration_seconds",
+        "upstream" => upstream,
+        "operation" => operation,
+        "status" => status
+    )
+    .record(latency.as_secs_f64());
+}
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use axum::{
+    extract::MatchedPath,
+    http::{Request, Response},
+};
+use opentelemetry::{global, trace::TracerProvider};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use tracing::{field::Empty, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+// Generated for each request unless the caller already sent one, and forwarded to the auth service
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// Used when RUST_LOG isn't set
+const DEFAULT_LOG_FILTER: &str = "app_service=info,tower_http=warn";
+
+// How spans are labelled in the collector
+const SERVICE_NAME: &str = "app-service";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    // Human readable, multi-line output
+    Pretty,
+    // One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+// Installs the global subscriber. Levels are filtered with RUST_LOG, e.g. "app_service=debug".
+// Spans are exported over OTLP/HTTP when `otlp_endpoint` is set, e.g. "http://localhost:4318".
+// The returned provider should be shut down before exiting so buffered spans are sent.
+pub fn init_tracing(format: LogFormat, otlp_endpoint: Option<&str>) -> SdkTracerProvider {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer_provider = tracer_provider(otlp_endpoint);
+
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(SERVICE_NAME)));
+
+    match format {
+        LogFormat::Pretty => registry.with(fmt::layer().pretty()).init(),
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            )
+            .init(),
+    }
+
+    tracer_provider
+}
+
+// Without an exporter spans still get W3C trace context, so it can be passed on to auth-service
+fn tracer_provider(otlp_endpoint: Option<&str>) -> SdkTracerProvider {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+
+    match otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build()
+                .expect("Failed to build the OTLP span exporter");
+            builder.with_batch_exporter(exporter).build()
+        }
+        None => builder.build(),
+    }
+}
+
+// The span every request is handled in. `status` and `latency_ms` are filled in by `on_response`.
+// The query string and headers are left out, so the JWT cookie never reaches the logs.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| request.uri().path());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        status = Empty,
+        latency_ms = Empty,
+    );
+
+    // Continue the caller's trace when it sent `traceparent`/`tracestate`
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    span
+}
+
+// The `traceparent`/`tracestate` headers to send so a downstream service continues `span`'s trace
+pub fn trace_context_headers(span: &Span) -> HashMap<String, String> {
+    let mut headers = HashMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&span.context(), &mut headers)
+    });
+    headers
+}
+
+pub fn on_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+
+    if status.is_server_error() {
+        tracing::error!("request failed");
+    } else {
+        tracing::info!("request completed");
+    }
+}
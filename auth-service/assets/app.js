@@ -1,10 +1,12 @@
 const loginSection = document.getElementById("login-section");
 const twoFASection = document.getElementById("2fa-section");
 const signupSection = document.getElementById("signup-section");
+const changePasswordSection = document.getElementById("change-password-section");
 
 const signupLink = document.getElementById("signup-link");
 const twoFALoginLink = document.getElementById("2fa-login-link");
 const signupLoginLink = document.getElementById("signup-login-link");
+const changePasswordLoginLink = document.getElementById("change-password-login-link");
 
 signupLink.addEventListener("click", (e) => {
     e.preventDefault();
@@ -30,6 +32,13 @@ signupLoginLink.addEventListener("click", (e) => {
     signupSection.style.display = "none";
 });
 
+changePasswordLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "block";
+    changePasswordSection.style.display = "none";
+});
+
 // -----------------------------------------------------
 
 const loginForm = document.getElementById("login-form");
@@ -62,6 +71,12 @@ loginButton.addEventListener("click", (e) => {
             twoFASection.style.display = "block";
             signupSection.style.display = "none";
             loginErrAlter.style.display = "none";
+        } else if (response.status === 202) {
+            // Logged in with a token that can only change the password
+            loginForm.email.value = "";
+            loginForm.password.value = "";
+            loginErrAlter.style.display = "none";
+            response.json().then(showChangePassword);
         } else if (response.status === 200) {
             loginForm.email.value = "";
             loginForm.password.value = "";
@@ -69,7 +84,7 @@ loginButton.addEventListener("click", (e) => {
             alert("You have successfully logged in.");
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = fieldErrors(data) || data.detail;
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     loginErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     loginErrAlter.style.display = "block";
@@ -81,6 +96,18 @@ loginButton.addEventListener("click", (e) => {
     });
 });
 
+// Lists what's wrong with each field, e.g. "Password must be at least 8 characters"
+function fieldErrors(data) {
+    if (!Array.isArray(data.errors) || data.errors.length === 0) {
+        return null;
+    }
+    // Suggestions, e.g. for a password that is too easy to guess, follow their error
+    return data.errors
+        .flatMap(error => [error.message, ...(error.suggestions || [])])
+        .map(message => message.replace(/\.$/, ""))
+        .join(". ");
+}
+
 const signupForm = document.getElementById("signup-form");
 const signupButton = document.getElementById("signup-form-submit");
 const signupErrAlter = document.getElementById("signup-err-alert");
@@ -110,7 +137,7 @@ signupButton.addEventListener("click", (e) => {
             signupSection.style.display = "none";
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = fieldErrors(data) || data.detail;
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     signupErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     signupErrAlter.style.display = "block";
@@ -140,7 +167,14 @@ TwoFAButton.addEventListener("click", (e) => {
         },
         body: JSON.stringify({ email, loginAttemptId, "2FACode": TwoFACode }),
     }).then(response => {
-        if (response.ok) {
+        if (response.status === 202) {
+            // Logged in with a token that can only change the password
+            TwoFAForm.email.value = "";
+            TwoFAForm.email_code.value = "";
+            TwoFAForm.login_attempt_id.value = "";
+            TwoFAErrAlter.style.display = "none";
+            response.json().then(showChangePassword);
+        } else if (response.ok) {
             TwoFAForm.email.value = "";
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
@@ -151,7 +185,7 @@ TwoFAButton.addEventListener("click", (e) => {
             signupSection.style.display = "none";
         } else {
             response.json().then(data => {
-                let error_msg = data.error;
+                let error_msg = data.detail;
                 if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
                     TwoFAErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
                     TwoFAErrAlter.style.display = "block";
@@ -161,4 +195,147 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+// -----------------------------------------------------
+
+const changePasswordForm = document.getElementById("change-password-form");
+const changePasswordButton = document.getElementById("change-password-form-submit");
+const changePasswordInfoAlert = document.getElementById("change-password-info-alert");
+const changePasswordErrAlter = document.getElementById("change-password-err-alert");
+
+// Shown after a login answered with 202, whose token can only be used to change the password
+function showChangePassword(data) {
+    changePasswordInfoAlert.textContent = data.reason === "expired"
+        ? "Your password has expired. Choose a new one to log in."
+        : "You must change your password before you can log in.";
+    changePasswordInfoAlert.style.display = "block";
+    changePasswordErrAlter.style.display = "none";
+
+    loginSection.style.display = "none";
+    twoFASection.style.display = "none";
+    signupSection.style.display = "none";
+    changePasswordSection.style.display = "block";
+}
+
+// The CSRF cookie is named "__Host-csrf_token" when the auth cookies use the __Host- prefix
+function csrfToken() {
+    const cookie = document.cookie
+        .split("; ")
+        .find(cookie => cookie.startsWith("csrf_token=") || cookie.startsWith("__Host-csrf_token="));
+    return cookie ? decodeURIComponent(cookie.split("=")[1]) : "";
+}
+
+changePasswordButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const currentPassword = changePasswordForm.current_password.value;
+    const newPassword = changePasswordForm.new_password.value;
+
+    fetch('/change-password', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+            'X-CSRF-Token': csrfToken(),
+        },
+        body: JSON.stringify({ currentPassword, newPassword }),
+    }).then(response => {
+        if (response.ok) {
+            changePasswordForm.current_password.value = "";
+            changePasswordForm.new_password.value = "";
+            changePasswordInfoAlert.style.display = "none";
+            changePasswordErrAlter.style.display = "none";
+            // Changing the password logs every session out, so the new password is used to log in
+            alert("Your password has been changed. You can log in with the new password.");
+            loginSection.style.display = "block";
+            changePasswordSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = fieldErrors(data) || data.detail;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    changePasswordErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    changePasswordErrAlter.style.display = "block";
+                } else {
+                    changePasswordErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+// -----------------------------------------------------
+
+const forgotPasswordLink = document.getElementById("forgot-password-link");
+
+forgotPasswordLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value || prompt("Email address of your account:");
+    if (!email) {
+        return;
+    }
+
+    fetch('/forgot-password', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        response.json().then(data => {
+            alert(response.ok ? data.message : `Error: ${fieldErrors(data) || data.detail}`);
+        });
+    });
+});
+
+// Links in emails point back to this page with a token in the query string
+const urlParams = new URLSearchParams(window.location.search);
+
+function submitEmailedToken(route, token, successMessage, fields = {}) {
+    fetch(route, {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token, ...fields }),
+    }).then(response => {
+        window.history.replaceState({}, document.title, window.location.pathname);
+        if (response.ok) {
+            alert(successMessage);
+        } else {
+            response.json().then(data => {
+                alert(`Error: ${fieldErrors(data) || data.detail}`);
+            });
+        }
+    });
+}
+
+if (urlParams.has("confirmEmailChange")) {
+    submitEmailedToken(
+        '/confirm-email-change',
+        urlParams.get("confirmEmailChange"),
+        "Your email address has been changed. Please log in again."
+    );
+} else if (urlParams.has("cancelEmailChange")) {
+    submitEmailedToken(
+        '/cancel-email-change',
+        urlParams.get("cancelEmailChange"),
+        "The email change has been cancelled."
+    );
+} else if (urlParams.has("unlockAccount")) {
+    submitEmailedToken(
+        '/unlock-account',
+        urlParams.get("unlockAccount"),
+        "Your account has been unlocked. You can log in again."
+    );
+} else if (urlParams.has("resetPassword")) {
+    const newPassword = prompt("Choose a new password:");
+    if (newPassword) {
+        submitEmailedToken(
+            '/reset-password',
+            urlParams.get("resetPassword"),
+            "Your password has been reset. You can log in with the new password.",
+            { newPassword }
+        );
+    }
+}
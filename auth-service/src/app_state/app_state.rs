@@ -1,22 +1,54 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, TwoFACodeStore, UserStore};
+use crate::domain::{
+    AuditLog, BannedTokenStore, EmailClient, RateLimitStore, TwoFACodeStore, UserStore,
+    VerificationTokenStore,
+};
+use crate::utils::{rate_limit::RateLimiter, settings::SettingsHandle};
 
 // Using a type alias to improve readability!
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type VerificationTokenStoreType = Arc<RwLock<dyn VerificationTokenStore + Send + Sync>>;
+pub type RateLimitStoreType = Arc<RwLock<dyn RateLimitStore + Send + Sync>>;
+pub type AuditLogType = Arc<RwLock<dyn AuditLog + Send + Sync>>;
+pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub verification_token_store: VerificationTokenStoreType,
+    pub email_client: EmailClientType,
+    pub rate_limiter: RateLimiter,
+    pub audit_log: AuditLogType,
+    pub settings: SettingsHandle,
 }
 
 impl AppState {
-    pub fn new(user_store: UserStoreType, banned_token_store: BannedTokenStoreType, two_fa_code_store: TwoFACodeStoreType) -> Self {
-        Self { user_store, banned_token_store, two_fa_code_store }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        user_store: UserStoreType,
+        banned_token_store: BannedTokenStoreType,
+        two_fa_code_store: TwoFACodeStoreType,
+        verification_token_store: VerificationTokenStoreType,
+        email_client: EmailClientType,
+        rate_limiter: RateLimiter,
+        audit_log: AuditLogType,
+        settings: SettingsHandle,
+    ) -> Self {
+        Self {
+            user_store,
+            banned_token_store,
+            two_fa_code_store,
+            verification_token_store,
+            email_client,
+            rate_limiter,
+            audit_log,
+            settings,
+        }
     }
 }
\ No newline at end of file
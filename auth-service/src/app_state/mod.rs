@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod app_state;
 pub use app_state::*;
\ No newline at end of file
@@ -0,0 +1,50 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter},
+    process::ExitCode,
+};
+
+use auth_service::domain::breached_passwords::write_index;
+
+// Turns the Have I Been Pwned SHA-1 download, ordered by hash, into the compact index that
+// `password_policy.breached_hashes_path` can point at. The index is less than half the size and
+// looks hashes up by their prefix rather than searching the whole file.
+//
+// Usage: build-breached-password-index <download> <index>
+fn main() -> ExitCode {
+    let (input_path, output_path) = match (std::env::args().nth(1), std::env::args().nth(2)) {
+        (Some(input_path), Some(output_path)) => (input_path, output_path),
+        _ => {
+            eprintln!("Usage: build-breached-password-index <download> <index>");
+            return ExitCode::from(2);
+        }
+    };
+
+    let input = match File::open(&input_path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", input_path, e);
+            return ExitCode::from(2);
+        }
+    };
+    let output = match File::create(&output_path) {
+        Ok(file) => BufWriter::new(file),
+        Err(e) => {
+            eprintln!("Failed to create {}: {}", output_path, e);
+            return ExitCode::from(2);
+        }
+    };
+
+    match write_index(input, output) {
+        Ok(hashes) => {
+            println!("{}: {} hashes", output_path, hashes);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Failed to index {}: {}", input_path, e);
+            // Half an index would be rejected when opened anyway
+            let _ = std::fs::remove_file(&output_path);
+            ExitCode::FAILURE
+        }
+    }
+}
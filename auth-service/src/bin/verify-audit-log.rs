@@ -0,0 +1,53 @@
+use std::process::ExitCode;
+
+use auth_service::{domain::audit_chain::verify_audit_log, utils::settings::Settings};
+
+// Checks that an audit log file written by `JsonLinesAuditLog` hasn't been tampered with.
+// Checkpoint signatures are checked with the JWT secrets from the service's own configuration,
+// including previous ones, since the log may have been signed before the secret was rotated.
+//
+// Usage: verify-audit-log <path>
+fn main() -> ExitCode {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: verify-audit-log <path>");
+            return ExitCode::from(2);
+        }
+    };
+
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Failed to read {}: {}", path, e);
+            return ExitCode::from(2);
+        }
+    };
+
+    let signing_keys: Vec<&[u8]> = settings.jwt_secrets().map(str::as_bytes).collect();
+
+    match verify_audit_log(contents.lines(), &signing_keys) {
+        Ok(summary) => {
+            println!(
+                "{}: OK ({} events, {} checkpoints)",
+                path, summary.events, summary.checkpoints
+            );
+            ExitCode::SUCCESS
+        }
+        Err(broken) => {
+            println!(
+                "{}: broken at line {}: {}",
+                path, broken.line, broken.reason
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::AuditEvent;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// The `prev_hash` of the first event in a chain
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// One line of a hash-chained audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditLogEntry {
+    Event(ChainedAuditEvent),
+    Checkpoint(AuditCheckpoint),
+}
+
+// An event that commits to every event before it through `prev_hash`,
+// so editing, removing or reordering a record breaks every link after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainedAuditEvent {
+    pub seq: u64,
+    pub prev_hash: String,
+    pub hash: String,
+    pub event: AuditEvent,
+}
+
+// Vouches for the chain up to and including event `seq`. Without the signing key a
+// rewritten chain can't be given valid checkpoints, so it can't pass for the original.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditCheckpoint {
+    pub seq: u64,
+    pub hash: String,
+    pub at: i64,
+    pub signature: String,
+}
+
+// Where the next event goes in a chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditChain {
+    next_seq: u64,
+    last_hash: String,
+}
+
+impl Default for AuditChain {
+    fn default() -> Self {
+        Self {
+            next_seq: 0,
+            last_hash: GENESIS_HASH.to_owned(),
+        }
+    }
+}
+
+impl AuditChain {
+    // Continues a chain after its most recent event
+    pub fn resume(last: &ChainedAuditEvent) -> Self {
+        Self {
+            next_seq: last.seq + 1,
+            last_hash: last.hash.clone(),
+        }
+    }
+
+    pub fn append(&mut self, event: AuditEvent) -> ChainedAuditEvent {
+        let seq = self.next_seq;
+        let prev_hash = std::mem::take(&mut self.last_hash);
+        let hash = hash_event(seq, &prev_hash, &event);
+
+        self.next_seq += 1;
+        self.last_hash = hash.clone();
+
+        ChainedAuditEvent {
+            seq,
+            prev_hash,
+            hash,
+            event,
+        }
+    }
+
+    // Signs the chain as it is now. Returns `None` while it has no events.
+    pub fn checkpoint(&self, at: i64, signing_key: &[u8]) -> Option<AuditCheckpoint> {
+        let seq = self.next_seq.checked_sub(1)?;
+
+        Some(AuditCheckpoint {
+            seq,
+            hash: self.last_hash.clone(),
+            at,
+            signature: hex::encode(
+                checkpoint_mac(seq, &self.last_hash, at, signing_key)
+                    .finalize()
+                    .into_bytes(),
+            ),
+        })
+    }
+}
+
+// The first entry of a log that doesn't check out
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    // 1-based line number
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditChainSummary {
+    pub events: u64,
+    pub checkpoints: u64,
+}
+
+// Walks a serialized log, one entry per line, checking every hash and checkpoint signature.
+// Checkpoints signed with any of `signing_keys` are accepted, so logs still verify after rotating keys.
+pub fn verify_audit_log<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    signing_keys: &[&[u8]],
+) -> Result<AuditChainSummary, BrokenLink> {
+    let mut chain = AuditChain::default();
+    let mut summary = AuditChainSummary::default();
+
+    let entries = lines
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    for (index, line) in entries {
+        let broken = |reason: String| BrokenLink {
+            line: index + 1,
+            reason,
+        };
+
+        let entry: AuditLogEntry =
+            serde_json::from_str(line).map_err(|e| broken(format!("unreadable entry: {}", e)))?;
+
+        match entry {
+            AuditLogEntry::Event(record) => {
+                if record.seq != chain.next_seq {
+                    return Err(broken(format!(
+                        "expected event {} but found event {}",
+                        chain.next_seq, record.seq
+                    )));
+                }
+                if record.prev_hash != chain.last_hash {
+                    return Err(broken(format!(
+                        "event {} doesn't link to the event before it",
+                        record.seq
+                    )));
+                }
+                if record.hash != hash_event(record.seq, &record.prev_hash, &record.event) {
+                    return Err(broken(format!(
+                        "event {} doesn't match its hash",
+                        record.seq
+                    )));
+                }
+
+                chain = AuditChain::resume(&record);
+                summary.events += 1;
+            }
+            AuditLogEntry::Checkpoint(checkpoint) => {
+                if chain.next_seq.checked_sub(1) != Some(checkpoint.seq)
+                    || checkpoint.hash != chain.last_hash
+                {
+                    return Err(broken(format!(
+                        "checkpoint for event {} doesn't match the chain",
+                        checkpoint.seq
+                    )));
+                }
+
+                let signature = hex::decode(&checkpoint.signature).unwrap_or_default();
+                let signed = signing_keys.iter().any(|signing_key| {
+                    checkpoint_mac(checkpoint.seq, &checkpoint.hash, checkpoint.at, signing_key)
+                        .verify_slice(&signature)
+                        .is_ok()
+                });
+                if !signed {
+                    return Err(broken(format!(
+                        "checkpoint for event {} has an invalid signature",
+                        checkpoint.seq
+                    )));
+                }
+
+                summary.checkpoints += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn hash_event(seq: u64, prev_hash: &str, event: &AuditEvent) -> String {
+    // Serializing a struct always writes its fields in the same order
+    let event = serde_json::to_vec(event).unwrap_or_default();
+
+    let mut hasher = Sha256::new();
+    hasher.update(prev_hash.as_bytes());
+    hasher.update(seq.to_be_bytes());
+    hasher.update(&event);
+    hex::encode(hasher.finalize())
+}
+
+fn checkpoint_mac(seq: u64, hash: &str, at: i64, signing_key: &[u8]) -> HmacSha256 {
+    // HMAC accepts keys of any length
+    let mut mac = HmacSha256::new_from_slice(signing_key).expect("HMAC can take a key of any size");
+    mac.update(&seq.to_be_bytes());
+    mac.update(hash.as_bytes());
+    mac.update(&at.to_be_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AuditOutcome;
+
+    const KEY: &[u8] = b"signing-key";
+
+    fn event(at: i64) -> AuditEvent {
+        AuditEvent {
+            at,
+            action: "POST /login".to_owned(),
+            actor: Some("test@example.com".to_owned()),
+            ip: Some("127.0.0.1".to_owned()),
+            user_agent: None,
+            outcome: AuditOutcome::Success,
+            status: 200,
+        }
+    }
+
+    // Serializes a chain of `events` events with a checkpoint after each of `checkpoints_after`
+    fn serialized_log(events: i64, checkpoints_after: &[i64]) -> Vec<String> {
+        let mut chain = AuditChain::default();
+        let mut lines = Vec::new();
+
+        for at in 0..events {
+            let entry = AuditLogEntry::Event(chain.append(event(at)));
+            lines.push(serde_json::to_string(&entry).unwrap());
+
+            if checkpoints_after.contains(&at) {
+                let entry = AuditLogEntry::Checkpoint(chain.checkpoint(at, KEY).unwrap());
+                lines.push(serde_json::to_string(&entry).unwrap());
+            }
+        }
+
+        lines
+    }
+
+    fn verify(lines: &[String]) -> Result<AuditChainSummary, BrokenLink> {
+        verify_audit_log(lines.iter().map(String::as_str), &[KEY])
+    }
+
+    #[test]
+    fn test_append_links_events() {
+        let mut chain = AuditChain::default();
+        assert_eq!(chain.checkpoint(0, KEY), None);
+
+        let first = chain.append(event(0));
+        let second = chain.append(event(1));
+
+        assert_eq!(first.seq, 0);
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.seq, 1);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_ne!(first.hash, second.hash);
+
+        let checkpoint = chain.checkpoint(2, KEY).unwrap();
+        assert_eq!(checkpoint.seq, 1);
+        assert_eq!(checkpoint.hash, second.hash);
+
+        // Resuming continues exactly where the chain left off
+        assert_eq!(AuditChain::resume(&second), chain);
+    }
+
+    #[test]
+    fn test_verify_intact_log() {
+        let lines = serialized_log(5, &[1, 4]);
+        assert_eq!(
+            verify(&lines),
+            Ok(AuditChainSummary {
+                events: 5,
+                checkpoints: 2
+            })
+        );
+        assert_eq!(verify(&[]), Ok(AuditChainSummary::default()));
+    }
+
+    #[test]
+    fn test_verify_reports_edited_event() {
+        let mut lines = serialized_log(5, &[]);
+        lines[2] = lines[2].replace("test@example.com", "other@example.com");
+
+        let broken = verify(&lines).unwrap_err();
+        assert_eq!(broken.line, 3);
+        assert!(broken.reason.contains("doesn't match its hash"));
+    }
+
+    #[test]
+    fn test_verify_reports_removed_event() {
+        let mut lines = serialized_log(5, &[]);
+        lines.remove(1);
+
+        let broken = verify(&lines).unwrap_err();
+        assert_eq!(broken.line, 2);
+        assert!(broken.reason.contains("expected event 1"));
+    }
+
+    #[test]
+    fn test_verify_reports_rewritten_chain() {
+        // Recomputing every hash after an edit still leaves the checkpoint pointing at the old chain
+        let lines = serialized_log(3, &[2]);
+        let mut chain = AuditChain::default();
+        let mut rewritten: Vec<String> = (0..3)
+            .map(|at| {
+                let mut event = event(at);
+                event.status = 401;
+                serde_json::to_string(&AuditLogEntry::Event(chain.append(event))).unwrap()
+            })
+            .collect();
+        rewritten.push(lines[3].clone());
+
+        let broken = verify(&rewritten).unwrap_err();
+        assert_eq!(broken.line, 4);
+        assert!(broken.reason.contains("doesn't match the chain"));
+
+        // ...and checkpoints can't be forged without the key
+        let forged = chain.checkpoint(3, b"another-key").unwrap();
+        rewritten[3] = serde_json::to_string(&AuditLogEntry::Checkpoint(forged)).unwrap();
+
+        let broken = verify(&rewritten).unwrap_err();
+        assert_eq!(broken.line, 4);
+        assert!(broken.reason.contains("invalid signature"));
+
+        // A checkpoint signed with a previous key still counts
+        let keys: &[&[u8]] = &[KEY, b"another-key"];
+        assert!(verify_audit_log(rewritten.iter().map(String::as_str), keys).is_ok());
+    }
+
+    #[test]
+    fn test_verify_reports_unreadable_entry() {
+        let mut lines = serialized_log(2, &[]);
+        lines.insert(1, "not json".to_owned());
+
+        let broken = verify(&lines).unwrap_err();
+        assert_eq!(broken.line, 2);
+        assert!(broken.reason.starts_with("unreadable entry"));
+    }
+}
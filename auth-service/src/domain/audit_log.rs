@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+// This trait represents the interface all concrete security audit logs should implement.
+// Events are append-only: once recorded they can be queried but never changed.
+#[async_trait::async_trait]
+pub trait AuditLog {
+    async fn record(&mut self, event: AuditEvent) -> Result<(), AuditLogError>;
+    // Returns the matching events, most recent first
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>, AuditLogError>;
+    // Records a signed checkpoint of everything logged so far, if the log keeps them
+    async fn checkpoint(&mut self, at: i64) -> Result<(), AuditLogError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuditLogError {
+    UnexpectedError,
+}
+
+// Something a client did through the API, and how it went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditEvent {
+    // Unix timestamp (seconds)
+    pub at: i64,
+    // The method and route that was called, e.g. "POST /login"
+    pub action: String,
+    // The email of the account the request acted on, if one could be identified
+    pub actor: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub outcome: AuditOutcome,
+    // The HTTP status code of the response
+    pub status: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl AuditOutcome {
+    pub fn from_status(status: u16) -> Self {
+        if (200..400).contains(&status) {
+            AuditOutcome::Success
+        } else {
+            AuditOutcome::Failure
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditQuery {
+    pub actor: Option<String>,
+    // Inclusive bounds on `AuditEvent::at`
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.actor
+            .as_ref()
+            .is_none_or(|actor| event.actor.as_ref() == Some(actor))
+            && self.from.is_none_or(|from| event.at >= from)
+            && self.to.is_none_or(|to| event.at <= to)
+    }
+
+    // Applies the query to events in the order they were recorded
+    pub fn apply<'a>(
+        &self,
+        events: impl DoubleEndedIterator<Item = &'a AuditEvent>,
+    ) -> Vec<AuditEvent> {
+        events
+            .rev()
+            .filter(|event| self.matches(event))
+            .take(self.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(at: i64, actor: Option<&str>) -> AuditEvent {
+        AuditEvent {
+            at,
+            action: "POST /login".to_owned(),
+            actor: actor.map(str::to_owned),
+            ip: None,
+            user_agent: None,
+            outcome: AuditOutcome::Success,
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn test_outcome_from_status() {
+        assert_eq!(AuditOutcome::from_status(200), AuditOutcome::Success);
+        assert_eq!(AuditOutcome::from_status(206), AuditOutcome::Success);
+        assert_eq!(AuditOutcome::from_status(401), AuditOutcome::Failure);
+        assert_eq!(AuditOutcome::from_status(500), AuditOutcome::Failure);
+    }
+
+    #[test]
+    fn test_query_apply() {
+        let events = [
+            event(1, Some("a@example.com")),
+            event(2, Some("b@example.com")),
+            event(3, Some("a@example.com")),
+            event(4, None),
+            event(5, Some("a@example.com")),
+        ];
+
+        let query = AuditQuery {
+            actor: Some("a@example.com".to_owned()),
+            ..AuditQuery::default()
+        };
+        let ats: Vec<i64> = query.apply(events.iter()).iter().map(|e| e.at).collect();
+        assert_eq!(ats, vec![5, 3, 1]);
+
+        let query = AuditQuery {
+            from: Some(2),
+            to: Some(4),
+            ..AuditQuery::default()
+        };
+        let ats: Vec<i64> = query.apply(events.iter()).iter().map(|e| e.at).collect();
+        assert_eq!(ats, vec![4, 3, 2]);
+
+        let query = AuditQuery {
+            limit: Some(2),
+            ..AuditQuery::default()
+        };
+        let ats: Vec<i64> = query.apply(events.iter()).iter().map(|e| e.at).collect();
+        assert_eq!(ats, vec![5, 4]);
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    cmp::Ordering,
+    fmt,
+    fs::File,
+    io::{self, BufRead, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+
+// Start of every file written by `write_index`
+const INDEX_MAGIC: &[u8; 8] = b"HIBPIDX1";
+// Hashes are grouped by their first 20 bits, the 5 hex digit prefixes of the HIBP range API
+const PREFIX_BITS: u32 = 20;
+const BUCKETS: usize = 1 << PREFIX_BITS;
+// After the magic, the number of hashes before each bucket, and then the total
+const INDEX_HEADER_LEN: usize = INDEX_MAGIC.len() + (BUCKETS + 1) * 8;
+// Each hash is stored without its first 2 bytes, as its bucket already gives them away
+const RECORD_LEN: usize = 18;
+const HASH_HEX_LEN: usize = 40;
+
+type Sha1Hash = [u8; 20];
+
+// SHA-1 hashes of passwords seen in data breaches, from a Have I Been Pwned download: either
+// the text file of "HASH:COUNT" lines ordered by hash, or the compact index `write_index` makes
+// of it. The file is memory-mapped and searched in place, so hundreds of millions of hashes
+// neither need to be read at startup nor held in memory.
+#[derive(Clone, Default)]
+pub struct BreachedPasswords(Option<Arc<HashFile>>);
+
+enum HashFile {
+    Text(Mmap),
+    Index(Mmap),
+}
+
+impl BreachedPasswords {
+    // Tells the two formats apart by the magic at the start of an index
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only read. A file truncated while mapped makes reads fault, so it
+        // should be replaced by renaming a new one over it rather than rewritten in place.
+        let map = unsafe { Mmap::map(&file)? };
+
+        let hash_file = if map.starts_with(INDEX_MAGIC) {
+            check_index(&map)?;
+            HashFile::Index(map)
+        } else {
+            check_text(&map)?;
+            HashFile::Text(map)
+        };
+        Ok(Self(Some(Arc::new(hash_file))))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        let Some(hash_file) = &self.0 else {
+            return false;
+        };
+        let hash: Sha1Hash = Sha1::digest(password.as_bytes()).into();
+        match hash_file.as_ref() {
+            HashFile::Text(map) => text_contains(map, &hash),
+            HashFile::Index(map) => index_contains(map, &hash),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+// Two are the same when they share the mapping, as comparing the files could take minutes
+impl PartialEq for BreachedPasswords {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.as_deref() {
+            None => f.write_str("BreachedPasswords(none)"),
+            Some(HashFile::Text(map)) => write!(f, "BreachedPasswords(text, {} bytes)", map.len()),
+            Some(HashFile::Index(map)) => {
+                write!(
+                    f,
+                    "BreachedPasswords(index, {} hashes)",
+                    index_offset(map, BUCKETS)
+                )
+            }
+        }
+    }
+}
+
+// Binary search over the lines of the text file, which are ordered by hash. Hashes are compared
+// case-insensitively, and the counts after them are ignored.
+fn text_contains(data: &[u8], hash: &Sha1Hash) -> bool {
+    let target = hex::encode_upper(hash);
+    // `low` is always the start of a line. Lines before it sort before the hash, and lines
+    // starting at or after `high` sort after it.
+    let (mut low, mut high) = (0, data.len());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let start = data[low..middle]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(low, |newline| low + newline + 1);
+        let end = data[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(data.len(), |newline| start + newline + 1);
+        let key = data[start..end]
+            .split(|&b| b == b':' || b == b'\r' || b == b'\n')
+            .next();
+        let key = key.unwrap_or_default().iter().map(u8::to_ascii_uppercase);
+
+        match key.cmp(target.bytes()) {
+            Ordering::Equal => return true,
+            Ordering::Less => low = end,
+            Ordering::Greater => high = start,
+        }
+    }
+    false
+}
+
+fn index_contains(data: &[u8], hash: &Sha1Hash) -> bool {
+    let bucket = bucket(hash);
+    let (start, end) = (index_offset(data, bucket), index_offset(data, bucket + 1));
+    let records = &data[INDEX_HEADER_LEN + start * RECORD_LEN..INDEX_HEADER_LEN + end * RECORD_LEN];
+    let key = &hash[hash.len() - RECORD_LEN..];
+
+    let (mut low, mut high) = (0, end - start);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        match records[middle * RECORD_LEN..(middle + 1) * RECORD_LEN].cmp(key) {
+            Ordering::Equal => return true,
+            Ordering::Less => low = middle + 1,
+            Ordering::Greater => high = middle,
+        }
+    }
+    false
+}
+
+fn bucket(hash: &Sha1Hash) -> usize {
+    (u32::from_be_bytes([0, hash[0], hash[1], hash[2]]) >> (24 - PREFIX_BITS)) as usize
+}
+
+// Number of hashes before `bucket`, or all of them for `BUCKETS`
+fn index_offset(data: &[u8], bucket: usize) -> usize {
+    let at = INDEX_MAGIC.len() + bucket * 8;
+    u64::from_le_bytes(data[at..at + 8].try_into().unwrap()) as usize
+}
+
+// Checked once when opened, so lookups can't read past the end of the map
+fn check_index(data: &[u8]) -> io::Result<()> {
+    if data.len() < INDEX_HEADER_LEN {
+        return Err(invalid_data("index is truncated".to_owned()));
+    }
+    let offsets: Vec<usize> = (0..=BUCKETS)
+        .map(|bucket| index_offset(data, bucket))
+        .collect();
+    if offsets[0] != 0 || offsets.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Err(invalid_data("index is corrupt".to_owned()));
+    }
+    let expected_len = offsets[BUCKETS]
+        .checked_mul(RECORD_LEN)
+        .and_then(|len| len.checked_add(INDEX_HEADER_LEN));
+    if expected_len != Some(data.len()) {
+        return Err(invalid_data("index is truncated".to_owned()));
+    }
+    Ok(())
+}
+
+// Only the first line, so that opening stays fast. NTLM downloads are rejected here too.
+fn check_text(data: &[u8]) -> io::Result<()> {
+    let first_line = data.split(|&b| b == b'\n').next().unwrap_or_default();
+    match std::str::from_utf8(first_line)
+        .ok()
+        .and_then(parse_hash_line)
+    {
+        Some(_) => Ok(()),
+        None => Err(invalid_data(
+            "expected a Have I Been Pwned SHA-1 file of HASH:COUNT lines".to_owned(),
+        )),
+    }
+}
+
+fn parse_hash_line(line: &str) -> Option<Sha1Hash> {
+    let hex = line.trim().split(':').next()?;
+    let mut hash = [0; 20];
+    if hex.len() != HASH_HEX_LEN || hex::decode_to_slice(hex, &mut hash).is_err() {
+        return None;
+    }
+    Some(hash)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Writes the compact index of a Have I Been Pwned text file of "HASH:COUNT" lines ordered by
+// hash, about 18 bytes per hash instead of about 45. Counts are dropped. Returns the number of
+// hashes written.
+pub fn write_index(input: impl BufRead, mut output: impl Write + Seek) -> io::Result<u64> {
+    // The offsets are only known at the end, so they're written over zeros
+    output.write_all(INDEX_MAGIC)?;
+    output.write_all(&vec![0; INDEX_HEADER_LEN - INDEX_MAGIC.len()])?;
+
+    let mut offsets = vec![0u64; BUCKETS + 1];
+    let mut previous: Option<Sha1Hash> = None;
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let hash = parse_hash_line(&line)
+            .ok_or_else(|| invalid_data(format!("line {}: expected HASH:COUNT", number + 1)))?;
+        if previous.is_some_and(|previous| hash <= previous) {
+            return Err(invalid_data(format!(
+                "line {}: hashes must be in ascending order, without duplicates",
+                number + 1
+            )));
+        }
+        previous = Some(hash);
+
+        offsets[bucket(&hash) + 1] += 1;
+        output.write_all(&hash[hash.len() - RECORD_LEN..])?;
+    }
+
+    // From the number of hashes in each bucket to the number before it
+    for bucket in 1..=BUCKETS {
+        offsets[bucket] += offsets[bucket - 1];
+    }
+    output.seek(SeekFrom::Start(INDEX_MAGIC.len() as u64))?;
+    let offsets_bytes: Vec<u8> = offsets
+        .iter()
+        .flat_map(|offset| offset.to_le_bytes())
+        .collect();
+    output.write_all(&offsets_bytes)?;
+    output.flush()?;
+
+    Ok(offsets[BUCKETS])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufWriter, Cursor};
+    use uuid::Uuid;
+
+    const BREACHED: &[&str] = &["password", "123456", "qwerty", "letmein", "dragon"];
+
+    // The lines of a HIBP download for `passwords`, plus some that aren't passwords at all
+    fn hibp_lines(passwords: &[&str]) -> String {
+        let fillers: Vec<String> = (0..1000).map(|i| format!("filler-{}", i)).collect();
+        let mut hashes: Vec<String> = passwords
+            .iter()
+            .copied()
+            .chain(fillers.iter().map(String::as_str))
+            .map(|password| hex::encode_upper(Sha1::digest(password.as_bytes())))
+            .collect();
+        hashes.sort();
+        hashes
+            .iter()
+            .map(|hash| format!("{}:42\r\n", hash))
+            .collect()
+    }
+
+    fn temp_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("auth-service-{}", Uuid::new_v4()))
+    }
+
+    fn assert_finds_breached_passwords(breached_passwords: &BreachedPasswords) {
+        for password in BREACHED {
+            assert!(
+                breached_passwords.contains(password),
+                "{} not found",
+                password
+            );
+        }
+        for password in ["Password", "correct horse battery staple", "", "dragon2"] {
+            assert!(!breached_passwords.contains(password), "{} found", password);
+        }
+    }
+
+    #[test]
+    fn test_text_file_is_searched() {
+        let path = temp_path();
+        std::fs::write(&path, hibp_lines(BREACHED)).unwrap();
+
+        let breached_passwords = BreachedPasswords::open(&path).unwrap();
+        assert_finds_breached_passwords(&breached_passwords);
+        assert!(format!("{:?}", breached_passwords).starts_with("BreachedPasswords(text"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_text_file_edges_are_searched() {
+        // Only breached passwords, so they're the first and last lines, without a final newline
+        let path = temp_path();
+        let mut hashes: Vec<String> = BREACHED
+            .iter()
+            .map(|password| hex::encode(Sha1::digest(password.as_bytes())))
+            .collect();
+        hashes.sort();
+        std::fs::write(&path, hashes.join("\n")).unwrap();
+
+        assert_finds_breached_passwords(&BreachedPasswords::open(&path).unwrap());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_index_is_searched() {
+        let path = temp_path();
+        let output = BufWriter::new(File::create(&path).unwrap());
+        let written = write_index(Cursor::new(hibp_lines(BREACHED)), output).unwrap();
+        assert_eq!(written, 1005);
+
+        let breached_passwords = BreachedPasswords::open(&path).unwrap();
+        assert_finds_breached_passwords(&breached_passwords);
+        assert_eq!(
+            format!("{:?}", breached_passwords),
+            "BreachedPasswords(index, 1005 hashes)"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_files_are_rejected() {
+        let path = temp_path();
+        for contents in [
+            &b"password\n"[..],
+            b"8846F7EAEE8FB117AD06BDD830B7586C:3\n",
+            b"HIBPIDX1",
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            let error = BreachedPasswords::open(&path).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        let unordered = format!("{}{}", hibp_lines(&["b"]), hibp_lines(&["a"]));
+        let error = write_index(Cursor::new(unordered), Cursor::new(Vec::new())).unwrap_err();
+        assert!(error.to_string().contains("ascending order"));
+    }
+}
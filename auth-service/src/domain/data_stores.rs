@@ -1,12 +1,43 @@
-use super::{User, email::Email, password::Password};
+use super::{
+    ConsentRecord, LoginRecord, PasswordChangeReason, Session, User, email::Email,
+    password::Password, password_hash::PasswordHash,
+};
 use uuid::Uuid;
 use rand::Rng;
+use serde::Deserialize;
 
 #[async_trait::async_trait]
 pub trait UserStore {
     async fn add_user(&mut self, user: User) -> Result<(), UserStoreError>;
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError>;
     async fn validate_user(&self, email: &Email, password: &Password) -> Result<(), UserStoreError>;
+    // Replaces the stored user with the same email. Use `change_email` to move a user to a new email.
+    async fn update_user(&mut self, user: User) -> Result<(), UserStoreError>;
+    async fn change_email(&mut self, email: &Email, new_email: Email) -> Result<(), UserStoreError>;
+    async fn schedule_deletion(&mut self, email: &Email, purge_at: i64) -> Result<(), UserStoreError>;
+    async fn cancel_deletion(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn get_users_due_for_deletion(&self, now: i64) -> Result<Vec<Email>, UserStoreError>;
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn add_session(&mut self, email: &Email, session: Session) -> Result<(), UserStoreError>;
+    // Returns the user's sessions that haven't expired yet
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, UserStoreError>;
+    async fn remove_session(&mut self, email: &Email, session_id: &str) -> Result<(), UserStoreError>;
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), UserStoreError>;
+    async fn record_login(&mut self, email: &Email, record: LoginRecord) -> Result<(), UserStoreError>;
+    async fn get_login_history(&self, email: &Email) -> Result<Vec<LoginRecord>, UserStoreError>;
+    async fn add_consent(&mut self, email: &Email, consent: ConsentRecord) -> Result<(), UserStoreError>;
+    async fn get_consents(&self, email: &Email) -> Result<Vec<ConsentRecord>, UserStoreError>;
+    // Adds the hash of a password the user no longer has, keeping only the `keep` most recent
+    async fn add_password_history(
+        &mut self,
+        email: &Email,
+        hash: PasswordHash,
+        keep: usize,
+    ) -> Result<(), UserStoreError>;
+    // Oldest first
+    async fn get_password_history(&self, email: &Email) -> Result<Vec<PasswordHash>, UserStoreError>;
+    // Succeeds when the store can currently serve requests
+    async fn health_check(&self) -> Result<(), UserStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -21,6 +52,11 @@ pub enum UserStoreError {
 pub trait BannedTokenStore {
     async fn ban_token(&mut self, token: String) -> Result<(), BannedTokenStoreError>;
     async fn is_token_banned(&self, token: String) -> Result<bool, BannedTokenStoreError>;
+    // Bans every token for `sub` that was issued at or before `issued_before`
+    async fn ban_subject(&mut self, sub: String, issued_before: i64) -> Result<(), BannedTokenStoreError>;
+    async fn is_subject_banned(&self, sub: &str, issued_at: i64) -> Result<bool, BannedTokenStoreError>;
+    // Succeeds when the store can currently serve requests
+    async fn health_check(&self) -> Result<(), BannedTokenStoreError>;
 }
 
 #[derive(Debug, PartialEq)]
@@ -31,17 +67,43 @@ pub enum BannedTokenStoreError {
 // This trait represents the interface all concrete 2FA code stores should implement
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
+    // Replaces the user's pending code for `purpose` only. `password_change_reason` is set for
+    // a login whose password has to be changed, so completing it only lets the user do that.
     async fn add_code(
         &mut self,
         email: Email,
+        purpose: TwoFAPurpose,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        password_change_reason: Option<PasswordChangeReason>,
     ) -> Result<(), TwoFACodeStoreError>;
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
+    async fn remove_code(&mut self, email: &Email, purpose: TwoFAPurpose) -> Result<(), TwoFACodeStoreError>;
+    // Removes the user's pending codes for every purpose
+    async fn remove_codes_for(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
     async fn get_code(
         &self,
         email: &Email,
+        purpose: TwoFAPurpose,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+    // Kept with the pending login code
+    async fn get_password_change_reason(
+        &self,
+        email: &Email,
+    ) -> Result<Option<PasswordChangeReason>, TwoFACodeStoreError>;
+    async fn change_email(&mut self, email: &Email, new_email: Email) -> Result<(), TwoFACodeStoreError>;
+    // Whether any 2FA challenge is waiting to be answered, without exposing the code
+    async fn has_code(&self, email: &Email) -> Result<bool, TwoFACodeStoreError>;
+    // Succeeds when the store can currently serve requests
+    async fn health_check(&self) -> Result<(), TwoFACodeStoreError>;
+}
+
+// What a 2FA code was sent for. A code only answers the challenge it was sent for, so a code
+// sent for a login can't turn 2FA off, and starting one challenge doesn't cancel another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TwoFAPurpose {
+    Login,
+    Enable,
+    Disable,
 }
 
 #[derive(Debug, PartialEq)]
@@ -75,13 +137,13 @@ impl AsRef<str> for LoginAttemptId {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct TwoFACode(String);
 
 impl TwoFACode {
     pub fn parse(code: String) -> Result<Self, String> {
         // Ensure `code` is a valid 6-digit code
-        if code.len() == 6 && code.chars().all(|c| c.is_digit(10)) {
+        if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
             Ok(Self(code))
         } else {
             Err("Invalid 2FA code: must be a 6-digit number".to_string())
@@ -100,8 +162,132 @@ impl Default for TwoFACode {
     }
 }
 
+impl std::fmt::Debug for TwoFACode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TwoFACode([REDACTED])")
+    }
+}
+
 impl AsRef<str> for TwoFACode {
     fn as_ref(&self) -> &str {
         &self.0
     }
-}
\ No newline at end of file
+}
+
+// This trait represents the interface all concrete stores of emailed one-time tokens should implement
+#[async_trait::async_trait]
+pub trait VerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        token: VerificationToken,
+        verification: PendingVerification,
+    ) -> Result<(), VerificationTokenStoreError>;
+    async fn get_token(
+        &self,
+        token: &VerificationToken,
+    ) -> Result<PendingVerification, VerificationTokenStoreError>;
+    async fn remove_token(&mut self, token: &VerificationToken) -> Result<(), VerificationTokenStoreError>;
+    async fn remove_tokens_for(&mut self, email: &Email) -> Result<(), VerificationTokenStoreError>;
+    // Returns what is pending for `email`, without the tokens themselves
+    async fn get_pending_for(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingVerification>, VerificationTokenStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum VerificationTokenStoreError {
+    TokenNotFound,
+    TokenExpired,
+    UnexpectedError,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VerificationPurpose {
+    ConfirmEmailChange { new_email: Email },
+    CancelEmailChange { new_email: Email },
+    UnlockAccount,
+    ResetPassword,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingVerification {
+    pub email: Email,
+    pub purpose: VerificationPurpose,
+    // Unix timestamp (seconds) after which the token can no longer be used
+    pub expires_at: i64,
+}
+
+#[derive(Clone, PartialEq, Hash, Eq)]
+pub struct VerificationToken(String);
+
+impl VerificationToken {
+    pub fn parse(token: String) -> Result<Self, String> {
+        Uuid::parse_str(&token)
+            .map(|_| Self(token))
+            .map_err(|e| format!("Invalid verification token: {}", e))
+    }
+}
+
+impl Default for VerificationToken {
+    fn default() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+}
+
+impl std::fmt::Debug for VerificationToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("VerificationToken([REDACTED])")
+    }
+}
+
+impl AsRef<str> for VerificationToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// This trait represents the interface all concrete rate limiter backends should implement.
+// Buckets are shared by every caller using the same key, so a shared backend limits across instances.
+#[async_trait::async_trait]
+pub trait RateLimitStore {
+    // Takes a token from the bucket for `key`, refilling it according to `limit` first.
+    // `now_ms` is a Unix timestamp in milliseconds.
+    async fn try_acquire(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+        now_ms: i64,
+    ) -> Result<(), RateLimitStoreError>;
+    // Removes buckets unused for at least the time they take to refill, since a full bucket
+    // behaves exactly like a missing one. Returns how many were removed.
+    async fn remove_idle(&mut self, now_ms: i64) -> Result<usize, RateLimitStoreError>;
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RateLimitStoreError {
+    LimitExceeded { retry_after_seconds: u64 },
+    UnexpectedError,
+}
+
+// A token bucket holding up to `capacity` requests, refilled at `capacity` per `period_seconds`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_seconds: u64,
+}
+
+impl RateLimit {
+    pub const fn new(capacity: u32, period_seconds: u64) -> Self {
+        Self {
+            capacity,
+            period_seconds,
+        }
+    }
+
+    // How many tokens are added back to the bucket per millisecond
+    pub fn refill_per_ms(&self) -> f64 {
+        self.capacity as f64 / (self.period_seconds as f64 * 1000.0)
+    }
+}
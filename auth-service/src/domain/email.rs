@@ -1,18 +1,54 @@
 use validator::validate_email;
 
+use super::error::FieldError;
+
 #[derive(Debug, Clone, PartialEq, Hash, Eq)]
 pub struct Email(String);
 
 impl Email {
-    pub fn parse(s: String) -> Result<Self, String> {
-        if validate_email(&s) {
+    pub fn parse(s: String) -> Result<Self, EmailError> {
+        if s.trim().is_empty() {
+            Err(EmailError::Missing)
+        } else if validate_email(&s) {
             Ok(Self(s))
         } else {
-            Err(format!("{} is not a valid email", s))
+            Err(EmailError::Invalid)
         }
     }
 }
 
+// Why an email address was rejected. Doesn't echo the address, which may end up in logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailError {
+    Missing,
+    Invalid,
+}
+
+impl EmailError {
+    // Stable and machine readable, for clients to pick their own wording
+    pub fn code(&self) -> &'static str {
+        match self {
+            EmailError::Missing => "required",
+            EmailError::Invalid => "invalid_format",
+        }
+    }
+}
+
+impl std::fmt::Display for EmailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmailError::Missing => f.write_str("Email is required"),
+            EmailError::Invalid => f.write_str("Email is not a valid email address"),
+        }
+    }
+}
+
+impl From<EmailError> for FieldError {
+    fn from(error: EmailError) -> Self {
+        FieldError::new("email", error.code(), error.to_string())
+    }
+}
+
 // AsRef trait allows us to get a &str from an Email
 impl AsRef<str> for Email {
     fn as_ref(&self) -> &str {
@@ -22,20 +58,28 @@ impl AsRef<str> for Email {
 
 #[cfg(test)]
 mod tests {
-    use super::Email;
+    use super::{Email, EmailError, FieldError};
     use fake::faker::internet::en::SafeEmail;
     use fake::Fake;
 
     #[test]
     fn empty_email_is_invalid() {
         let email = "".to_string();
-        assert!(Email::parse(email).is_err());
+        assert_eq!(Email::parse(email), Err(EmailError::Missing));
     }
 
     #[test]
     fn email_missing_at_symbol_is_invalid() {
         let email = "invalidemail.com".to_string();
-        assert!(Email::parse(email).is_err());
+        assert_eq!(Email::parse(email), Err(EmailError::Invalid));
+    }
+
+    #[test]
+    fn field_error_does_not_echo_the_email() {
+        let error = FieldError::from(Email::parse("secret.com".to_owned()).unwrap_err());
+        assert_eq!(error.field, "email");
+        assert_eq!(error.code, "invalid_format");
+        assert!(!error.message.contains("secret"));
     }
 
     #[derive(Debug, Clone)]
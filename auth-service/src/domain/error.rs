@@ -1,8 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug)]
 pub enum AuthAPIError {
     UserAlreadyExists,
+    UserNotFound,
     InvalidCredentials,
+    // Invalid credentials, with what's wrong with each field
+    InvalidFields(Vec<FieldError>),
     IncorrectCredentials,
     UnexpectedError,
     MissingToken,
     InvalidToken,
+    // The CSRF header is missing or doesn't match the CSRF cookie and session
+    InvalidCsrfToken,
+    // The token can only be used to change the password, which has to happen first
+    PasswordChangeRequired,
+    TwoFAUnchanged,
+    TooManyRequests { retry_after_seconds: u64 },
+    AccountLocked { retry_after_seconds: u64 },
+}
+
+// One problem with one field of a request, e.g. `{"field": "email", "code": "required", ...}`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    // Stable and machine readable, e.g. "too_short"
+    pub code: String,
+    pub message: String,
+    // How to fix it, e.g. for a password that is too easy to guess
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: String) -> Self {
+        Self {
+            field: field.to_owned(),
+            code: code.to_owned(),
+            message,
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn with_suggestions(mut self, suggestions: &[&str]) -> Self {
+        self.suggestions = suggestions.iter().map(|s| s.to_string()).collect();
+        self
+    }
 }
@@ -1,11 +1,24 @@
+pub mod audit_chain;
+pub mod audit_log;
+pub mod breached_passwords;
 pub mod data_stores;
 pub mod email;
+pub mod email_client;
 pub mod error;
 pub mod password;
+pub mod password_hash;
+pub mod password_policy;
+pub mod password_strength;
 pub mod user;
 
+pub use audit_log::{AuditEvent, AuditLog, AuditLogError, AuditOutcome, AuditQuery};
 pub use data_stores::*;
-pub use email::Email;
-pub use error::AuthAPIError;
-pub use password::Password;
-pub use user::User;
\ No newline at end of file
+pub use email::{Email, EmailError};
+pub use email_client::EmailClient;
+pub use error::{AuthAPIError, FieldError};
+pub use password::{Password, PasswordError};
+pub use password_hash::PasswordHash;
+pub use password_policy::PasswordPolicy;
+pub use user::{
+    ConsentRecord, LoginLockout, LoginOutcome, LoginRecord, PasswordChangeReason, Session, User,
+};
\ No newline at end of file
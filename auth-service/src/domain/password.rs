@@ -1,18 +1,144 @@
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+use super::{email::Email, error::FieldError, password_policy::PasswordPolicy};
+
+// Every password is at least this long, whatever the policy. See `PasswordPolicy::min_length`.
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Clone, PartialEq, Hash, Eq)]
 pub struct Password(String);
 
 impl Password {
-    pub fn parse(s: String) -> Result<Self, String> {
-        if validate_password(&s) {
+    pub fn parse(s: String) -> Result<Self, PasswordError> {
+        if s.is_empty() {
+            Err(PasswordError::Missing)
+        } else if s.len() < MIN_PASSWORD_LENGTH {
+            Err(PasswordError::TooShort { min_length: MIN_PASSWORD_LENGTH })
+        } else {
+            Ok(Self(s))
+        }
+    }
+
+    // Parses a password being set, rather than one being checked against the stored one,
+    // reporting every rule of `policy` that it breaks. Without an `email`, e.g. when the one
+    // given at signup is invalid, the rules about it are skipped.
+    pub fn parse_with_policy(
+        s: String,
+        email: Option<&Email>,
+        policy: &PasswordPolicy,
+    ) -> Result<Self, Vec<PasswordError>> {
+        let violations = policy.violations(&s, email);
+        if violations.is_empty() {
             Ok(Self(s))
         } else {
-            Err(format!("{} is not a valid password", s))
+            Err(violations)
         }
     }
 }
 
-fn validate_password(s: &str) -> bool {
-    s.len() >= 8
+// Why a password was rejected. Never carries the password itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PasswordError {
+    Missing,
+    TooShort { min_length: usize },
+    TooLong { max_length: usize },
+    MissingLowercase,
+    MissingUppercase,
+    MissingDigit,
+    MissingSymbol,
+    ContainsEmail,
+    Blocklisted,
+    // Found among the breached password hashes of `PasswordPolicy::breached_hashes_path`
+    Breached,
+    // The same as the current password or one of the others in its history
+    Reused { history_size: usize },
+    // Scored below `PasswordPolicy::min_strength_score`, with the estimator's feedback
+    TooWeak {
+        score: u8,
+        min_score: u8,
+        warning: Option<&'static str>,
+        suggestions: Vec<&'static str>,
+    },
+}
+
+impl PasswordError {
+    // Stable and machine readable, for clients to pick their own wording
+    pub fn code(&self) -> &'static str {
+        match self {
+            PasswordError::Missing => "required",
+            PasswordError::TooShort { .. } => "too_short",
+            PasswordError::TooLong { .. } => "too_long",
+            PasswordError::MissingLowercase => "missing_lowercase",
+            PasswordError::MissingUppercase => "missing_uppercase",
+            PasswordError::MissingDigit => "missing_digit",
+            PasswordError::MissingSymbol => "missing_symbol",
+            PasswordError::ContainsEmail => "contains_email",
+            PasswordError::Blocklisted => "blocklisted",
+            PasswordError::Breached => "breached",
+            PasswordError::Reused { .. } => "reused",
+            PasswordError::TooWeak { .. } => "too_weak",
+        }
+    }
+}
+
+impl std::fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordError::Missing => f.write_str("Password is required"),
+            PasswordError::TooShort { min_length } => {
+                write!(f, "Password must be at least {} characters", min_length)
+            }
+            PasswordError::TooLong { max_length } => {
+                write!(f, "Password must be at most {} characters", max_length)
+            }
+            PasswordError::MissingLowercase => {
+                f.write_str("Password must contain a lowercase letter")
+            }
+            PasswordError::MissingUppercase => {
+                f.write_str("Password must contain an uppercase letter")
+            }
+            PasswordError::MissingDigit => f.write_str("Password must contain a digit"),
+            PasswordError::MissingSymbol => f.write_str("Password must contain a symbol"),
+            PasswordError::ContainsEmail => {
+                f.write_str("Password must not contain your email address")
+            }
+            PasswordError::Blocklisted => f.write_str("Password is too common"),
+            PasswordError::Breached => {
+                f.write_str("Password has appeared in a data breach, so it must not be used")
+            }
+            PasswordError::Reused { history_size: 1 } => {
+                f.write_str("Password must be different from your current password")
+            }
+            PasswordError::Reused { history_size } => {
+                write!(f, "Password must not be any of your last {} passwords", history_size)
+            }
+            PasswordError::TooWeak { warning, .. } => {
+                f.write_str(warning.unwrap_or("Password is too easy to guess"))
+            }
+        }
+    }
+}
+
+impl PasswordError {
+    // For requests that name the password field differently, e.g. "newPassword"
+    pub fn field_error(&self, field: &str) -> FieldError {
+        let error = FieldError::new(field, self.code(), self.to_string());
+        match self {
+            PasswordError::TooWeak { suggestions, .. } => error.with_suggestions(suggestions),
+            _ => error,
+        }
+    }
+}
+
+impl From<PasswordError> for FieldError {
+    fn from(error: PasswordError) -> Self {
+        error.field_error("password")
+    }
+}
+
+// Keeps passwords out of logs and panic messages
+impl std::fmt::Debug for Password {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Password([REDACTED])")
+    }
 }
 
 // AsRef trait allows us to get a &str from a Password
@@ -24,7 +150,7 @@ impl AsRef<str> for Password {
 
 #[cfg(test)]
 mod tests {
-    use super::Password;
+    use super::{Password, PasswordError};
     use fake::faker::internet::en::Password as FakePassword;
     use fake::Fake;
 
@@ -37,7 +163,20 @@ mod tests {
     #[test]
     fn short_password_is_invalid() {
         let password = "short".to_string();
-        assert!(Password::parse(password).is_err());
+        assert_eq!(
+            Password::parse(password),
+            Err(PasswordError::TooShort { min_length: 8 })
+        );
+    }
+
+    #[test]
+    fn debug_output_is_redacted() {
+        let password = Password::parse("password123".to_owned()).unwrap();
+        assert_eq!(format!("{:?}", password), "Password([REDACTED])");
+        assert!(!Password::parse("short".to_owned())
+            .unwrap_err()
+            .to_string()
+            .contains("short"));
     }
 
     #[derive(Debug, Clone)]
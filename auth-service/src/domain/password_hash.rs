@@ -0,0 +1,86 @@
+use argon2::{
+    password_hash::{PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+use super::password::Password;
+
+// A salted Argon2id hash of a password, in the PHC string format, e.g. for the password history.
+// Hashing takes tens of milliseconds on purpose, so it's best kept off the async runtime.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PasswordHash(String);
+
+impl PasswordHash {
+    pub fn new(password: &Password) -> Self {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let hash = Argon2::default()
+            .hash_password(password.as_ref().as_bytes(), &salt)
+            .expect("Argon2 can hash any password with the default parameters");
+        Self(hash.to_string())
+    }
+
+    // For stores reading back a hash they saved with `as_ref`
+    pub fn parse(s: String) -> Result<Self, String> {
+        argon2::PasswordHash::new(&s).map_err(|e| e.to_string())?;
+        Ok(Self(s))
+    }
+
+    pub fn matches(&self, password: &Password) -> bool {
+        // Checked when parsed, so this only fails for strings that weren't
+        argon2::PasswordHash::new(&self.0).is_ok_and(|hash| {
+            Argon2::default()
+                .verify_password(password.as_ref().as_bytes(), &hash)
+                .is_ok()
+        })
+    }
+}
+
+// Keeps hashes out of logs, where they could be cracked offline
+impl std::fmt::Debug for PasswordHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PasswordHash([REDACTED])")
+    }
+}
+
+impl AsRef<str> for PasswordHash {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn password(s: &str) -> Password {
+        Password::parse(s.to_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_hash_matches_only_its_password() {
+        let hash = PasswordHash::new(&password("password123"));
+
+        assert!(hash.matches(&password("password123")));
+        assert!(!hash.matches(&password("Password123")));
+        assert!(hash.as_ref().starts_with("$argon2id$"));
+        assert_eq!(format!("{:?}", hash), "PasswordHash([REDACTED])");
+    }
+
+    #[test]
+    fn test_hashes_are_salted() {
+        let first = PasswordHash::new(&password("password123"));
+        let second = PasswordHash::new(&password("password123"));
+
+        assert_ne!(first, second);
+        assert!(second.matches(&password("password123")));
+    }
+
+    #[test]
+    fn test_stored_hashes_are_parsed() {
+        let hash = PasswordHash::new(&password("password123"));
+
+        let parsed = PasswordHash::parse(hash.as_ref().to_owned()).unwrap();
+        assert!(parsed.matches(&password("password123")));
+        assert!(PasswordHash::parse("password123".to_owned()).is_err());
+    }
+}
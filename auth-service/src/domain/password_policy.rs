@@ -0,0 +1,336 @@
+use std::{collections::HashSet, fmt, io, path::PathBuf, sync::Arc};
+
+use serde::Deserialize;
+
+use super::{
+    breached_passwords::BreachedPasswords,
+    email::Email,
+    password::{PasswordError, MIN_PASSWORD_LENGTH},
+    password_strength::{estimate_strength, PasswordStrength},
+};
+
+// The highest score `estimate_strength` gives
+pub const MAX_STRENGTH_SCORE: u8 = 4;
+// Each password in the history takes an Argon2 hash to check
+pub const MAX_HISTORY_SIZE: usize = 24;
+
+const SECONDS_PER_DAY: i64 = 86400;
+
+// Local parts shorter than this, e.g. the "jo" of "jo@example.com", are too likely to show up
+// in a password by chance to be forbidden
+const MIN_FORBIDDEN_LOCAL_PART_LENGTH: usize = 3;
+
+// The rules new passwords are held to, read from the `password_policy` table of the settings.
+// Passwords that are only checked against the stored one, e.g. at login, aren't held to them,
+// so tightening the policy doesn't lock anyone out.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PasswordPolicy {
+    // In characters. Can't be lower than `MIN_PASSWORD_LENGTH`.
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    // Anything that isn't a letter or digit, e.g. "!" or " "
+    pub require_symbol: bool,
+    // Rejects passwords containing the part of the user's email before the "@"
+    pub forbid_email_local_part: bool,
+    // File of passwords to reject, one per line and compared case-insensitively
+    pub blocklist_path: Option<PathBuf>,
+    // A Have I Been Pwned SHA-1 download, ordered by hash, or the index
+    // `build-breached-password-index` makes of it. Memory-mapped rather than read.
+    pub breached_hashes_path: Option<PathBuf>,
+    // Lowest strength score accepted, from 0 (off) to `MAX_STRENGTH_SCORE`.
+    // See `estimate_strength`.
+    pub min_strength_score: u8,
+    // How many of the user's most recent passwords, counting the current one, a new password
+    // can't be. 0 allows any. Up to `MAX_HISTORY_SIZE`.
+    pub history_size: usize,
+    // Days after which a password has to be changed at the next login. 0 never expires them.
+    pub max_age_days: u32,
+    // Read from `blocklist_path` by `load_blocklist`
+    #[serde(skip)]
+    pub blocklist: PasswordBlocklist,
+    // Opened from `breached_hashes_path` by `load_breached_passwords`
+    #[serde(skip)]
+    pub breached_passwords: BreachedPasswords,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: MIN_PASSWORD_LENGTH,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            forbid_email_local_part: true,
+            blocklist_path: None,
+            breached_hashes_path: None,
+            min_strength_score: 0,
+            history_size: 5,
+            max_age_days: 0,
+            blocklist: PasswordBlocklist::default(),
+            breached_passwords: BreachedPasswords::default(),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    // Reads the blocklist from `blocklist_path`, or empties it when there is none
+    pub fn load_blocklist(&mut self) -> io::Result<()> {
+        self.blocklist = match &self.blocklist_path {
+            Some(path) => PasswordBlocklist::parse(&std::fs::read_to_string(path)?),
+            None => PasswordBlocklist::default(),
+        };
+        Ok(())
+    }
+
+    // Maps the file at `breached_hashes_path`, or forgets the breached passwords when there is none
+    pub fn load_breached_passwords(&mut self) -> io::Result<()> {
+        self.breached_passwords = match &self.breached_hashes_path {
+            Some(path) => BreachedPasswords::open(path)?,
+            None => BreachedPasswords::default(),
+        };
+        Ok(())
+    }
+
+    // Whether a password set at `changed_at` has to be changed by `now`
+    pub fn is_expired(&self, changed_at: i64, now: i64) -> bool {
+        self.max_age_days > 0 && now - changed_at >= i64::from(self.max_age_days) * SECONDS_PER_DAY
+    }
+
+    // Every rule `password` breaks, in the order they're listed in the policy
+    pub fn violations(&self, password: &str, email: Option<&Email>) -> Vec<PasswordError> {
+        if password.is_empty() {
+            return vec![PasswordError::Missing];
+        }
+
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(PasswordError::TooShort { min_length: self.min_length });
+        }
+        if length > self.max_length {
+            violations.push(PasswordError::TooLong { max_length: self.max_length });
+        }
+        if self.require_lowercase && !password.chars().any(char::is_lowercase) {
+            violations.push(PasswordError::MissingLowercase);
+        }
+        if self.require_uppercase && !password.chars().any(char::is_uppercase) {
+            violations.push(PasswordError::MissingUppercase);
+        }
+        if self.require_digit && !password.chars().any(|c| c.is_ascii_digit()) {
+            violations.push(PasswordError::MissingDigit);
+        }
+        if self.require_symbol && password.chars().all(char::is_alphanumeric) {
+            violations.push(PasswordError::MissingSymbol);
+        }
+        let contains_email = email.is_some_and(|email| contains_local_part(password, email));
+        if self.forbid_email_local_part && contains_email {
+            violations.push(PasswordError::ContainsEmail);
+        }
+        if self.blocklist.contains(password) {
+            violations.push(PasswordError::Blocklisted);
+        }
+        if self.breached_passwords.contains(password) {
+            violations.push(PasswordError::Breached);
+        }
+        if self.min_strength_score > 0 {
+            let strength = self.strength(password, email);
+            if strength.score < self.min_strength_score {
+                violations.push(PasswordError::TooWeak {
+                    score: strength.score,
+                    min_score: self.min_strength_score,
+                    warning: strength.warning,
+                    suggestions: strength.suggestions,
+                });
+            }
+        }
+        violations
+    }
+
+    // How guessable `password` is, counting the parts of the user's email as easy guesses
+    pub fn strength(&self, password: &str, email: Option<&Email>) -> PasswordStrength {
+        let local_part = email
+            .map(|email| email.as_ref().split('@').next().unwrap_or_default())
+            .unwrap_or_default();
+        let mut user_inputs: Vec<&str> = local_part
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect();
+        if !local_part.is_empty() {
+            user_inputs.push(local_part);
+        }
+        estimate_strength(password, &user_inputs)
+    }
+}
+
+fn contains_local_part(password: &str, email: &Email) -> bool {
+    let local_part = email
+        .as_ref()
+        .split('@')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    local_part.chars().count() >= MIN_FORBIDDEN_LOCAL_PART_LENGTH
+        && password.to_lowercase().contains(&local_part)
+}
+
+// Lowercased, so lookups are case-insensitive. Shared, as it's cloned with the settings.
+#[derive(Clone, Default, PartialEq)]
+pub struct PasswordBlocklist(Arc<HashSet<String>>);
+
+impl PasswordBlocklist {
+    // One password per line. Blank lines are skipped.
+    pub fn parse(contents: &str) -> Self {
+        let passwords = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        Self(Arc::new(passwords))
+    }
+
+    pub fn contains(&self, password: &str) -> bool {
+        self.0.contains(&password.to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Only the size, so the settings can be logged without dumping the whole list
+impl fmt::Debug for PasswordBlocklist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PasswordBlocklist({} passwords)", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn email() -> Email {
+        Email::parse("jordan@example.com".to_owned()).unwrap()
+    }
+
+    #[test]
+    fn test_default_policy_only_checks_length_and_email() {
+        let policy = PasswordPolicy::default();
+
+        assert!(policy.violations("aaaaaaaa", Some(&email())).is_empty());
+        assert_eq!(
+            policy.violations("short", Some(&email())),
+            vec![PasswordError::TooShort { min_length: 8 }]
+        );
+        assert_eq!(
+            policy.violations("Jordan2024!", Some(&email())),
+            vec![PasswordError::ContainsEmail]
+        );
+        assert_eq!(policy.violations("", Some(&email())), vec![PasswordError::Missing]);
+        // Without an email, e.g. an invalid one at signup, there is nothing to compare with
+        assert!(policy.violations("Jordan2024!", None).is_empty());
+    }
+
+    #[test]
+    fn test_every_broken_rule_is_reported() {
+        let policy = PasswordPolicy {
+            min_length: 12,
+            max_length: 16,
+            require_lowercase: true,
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..PasswordPolicy::default()
+        };
+
+        assert_eq!(
+            policy.violations("aaaaaaaa", Some(&email())),
+            vec![
+                PasswordError::TooShort { min_length: 12 },
+                PasswordError::MissingUppercase,
+                PasswordError::MissingDigit,
+                PasswordError::MissingSymbol,
+            ]
+        );
+        assert_eq!(
+            policy.violations("AAAAAAAAAAAAAAAAA", Some(&email())),
+            vec![
+                PasswordError::TooLong { max_length: 16 },
+                PasswordError::MissingLowercase,
+                PasswordError::MissingDigit,
+                PasswordError::MissingSymbol,
+            ]
+        );
+        assert!(policy.violations("Correct-Horse-9", Some(&email())).is_empty());
+    }
+
+    #[test]
+    fn test_length_counts_characters_rather_than_bytes() {
+        let policy = PasswordPolicy {
+            max_length: 8,
+            ..PasswordPolicy::default()
+        };
+
+        assert!(policy.violations("éééééééé", Some(&email())).is_empty());
+    }
+
+    #[test]
+    fn test_short_local_parts_are_not_forbidden() {
+        let policy = PasswordPolicy::default();
+        let email = Email::parse("jo@example.com".to_owned()).unwrap();
+
+        assert!(policy.violations("jo-jo-jo-jo", Some(&email)).is_empty());
+    }
+
+    #[test]
+    fn test_blocklist_is_case_insensitive() {
+        let policy = PasswordPolicy {
+            blocklist: PasswordBlocklist::parse("password123\n\n  Qwertyuiop \n"),
+            ..PasswordPolicy::default()
+        };
+
+        assert_eq!(policy.blocklist.len(), 2);
+        assert_eq!(
+            policy.violations("PASSWORD123", Some(&email())),
+            vec![PasswordError::Blocklisted]
+        );
+        assert_eq!(
+            policy.violations("qwertyuiop", Some(&email())),
+            vec![PasswordError::Blocklisted]
+        );
+        assert!(policy.violations("password1234", Some(&email())).is_empty());
+        assert_eq!(format!("{:?}", policy.blocklist), "PasswordBlocklist(2 passwords)");
+    }
+
+    #[test]
+    fn test_weak_passwords_are_rejected_with_feedback() {
+        let policy = PasswordPolicy {
+            min_strength_score: 3,
+            forbid_email_local_part: false,
+            ..PasswordPolicy::default()
+        };
+
+        assert!(matches!(
+            policy.violations("password1", Some(&email())).as_slice(),
+            [PasswordError::TooWeak { min_score: 3, warning: Some(_), suggestions, .. }]
+                if !suggestions.is_empty()
+        ));
+        // Parts of the email are among the first guesses
+        let email_with_name = Email::parse("jordan.smith@example.com".to_owned()).unwrap();
+        assert!(matches!(
+            policy.violations("smithjordan", Some(&email_with_name)).as_slice(),
+            [PasswordError::TooWeak { .. }]
+        ));
+        assert!(policy.violations("correct horse battery staple", Some(&email())).is_empty());
+    }
+}
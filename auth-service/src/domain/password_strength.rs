@@ -0,0 +1,998 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use chrono::{Datelike, Utc};
+use serde::Serialize;
+
+// A simplified take on zxcvbn (https://github.com/dropbox/zxcvbn): the password is split into
+// the patterns an attacker would try first, such as common passwords, sequences, repeats and
+// keyboard rows, and its strength is the number of guesses the cheapest split takes.
+
+// Longer passwords are only estimated on their start, which is plenty to score them highly
+const MAX_ESTIMATED_LENGTH: usize = 100;
+// Guesses per character for parts that don't match any pattern
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+// Keeps a password made of many short patterns from scoring lower than one long pattern
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10000.0;
+// Years this close to the current one are all about as likely
+const MIN_YEAR_SPACE: f64 = 20.0;
+// Starting keys and neighbours per key of a keyboard row
+const KEYBOARD_STARTING_POSITIONS: f64 = 47.0;
+const KEYBOARD_AVERAGE_DEGREE: f64 = 4.0;
+
+// Most common first, as the rank is the number of guesses
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "password",
+    "12345678",
+    "qwerty",
+    "123456789",
+    "12345",
+    "1234",
+    "111111",
+    "1234567",
+    "dragon",
+    "123123",
+    "baseball",
+    "abc123",
+    "football",
+    "monkey",
+    "letmein",
+    "696969",
+    "shadow",
+    "master",
+    "666666",
+    "qwertyuiop",
+    "123321",
+    "mustang",
+    "1234567890",
+    "michael",
+    "654321",
+    "superman",
+    "1qaz2wsx",
+    "7777777",
+    "121212",
+    "000000",
+    "qazwsx",
+    "123qwe",
+    "killer",
+    "trustno1",
+    "jordan",
+    "jennifer",
+    "zxcvbnm",
+    "asdfgh",
+    "hunter",
+    "buster",
+    "soccer",
+    "harley",
+    "batman",
+    "andrew",
+    "tigger",
+    "sunshine",
+    "iloveyou",
+    "2000",
+    "charlie",
+    "robert",
+    "thomas",
+    "hockey",
+    "ranger",
+    "daniel",
+    "starwars",
+    "klaster",
+    "112233",
+    "george",
+    "computer",
+    "michelle",
+    "jessica",
+    "pepper",
+    "1111",
+    "zxcvbn",
+    "555555",
+    "11111111",
+    "131313",
+    "freedom",
+    "777777",
+    "pass",
+    "maggie",
+    "159753",
+    "aaaaaa",
+    "ginger",
+    "princess",
+    "joshua",
+    "cheese",
+    "amanda",
+    "summer",
+    "love",
+    "ashley",
+    "nicole",
+    "chelsea",
+    "biteme",
+    "matthew",
+    "access",
+    "yankees",
+    "987654321",
+    "dallas",
+    "austin",
+    "thunder",
+    "taylor",
+    "matrix",
+    "welcome",
+    "admin",
+    "login",
+    "passw0rd",
+    "secret",
+    "hello",
+];
+
+// Common English words, most common first
+const COMMON_WORDS: &[&str] = &[
+    "the",
+    "you",
+    "that",
+    "was",
+    "for",
+    "are",
+    "with",
+    "his",
+    "they",
+    "this",
+    "have",
+    "from",
+    "one",
+    "had",
+    "word",
+    "but",
+    "not",
+    "what",
+    "all",
+    "were",
+    "when",
+    "your",
+    "can",
+    "said",
+    "there",
+    "use",
+    "each",
+    "which",
+    "she",
+    "how",
+    "their",
+    "will",
+    "other",
+    "about",
+    "out",
+    "many",
+    "then",
+    "them",
+    "these",
+    "some",
+    "her",
+    "would",
+    "make",
+    "like",
+    "him",
+    "into",
+    "time",
+    "has",
+    "look",
+    "two",
+    "more",
+    "write",
+    "see",
+    "number",
+    "way",
+    "could",
+    "people",
+    "than",
+    "first",
+    "water",
+    "been",
+    "call",
+    "who",
+    "oil",
+    "its",
+    "now",
+    "find",
+    "long",
+    "down",
+    "day",
+    "did",
+    "get",
+    "come",
+    "made",
+    "may",
+    "part",
+    "over",
+    "new",
+    "sound",
+    "take",
+    "only",
+    "little",
+    "work",
+    "know",
+    "place",
+    "year",
+    "live",
+    "back",
+    "give",
+    "most",
+    "very",
+    "after",
+    "thing",
+    "our",
+    "just",
+    "name",
+    "good",
+    "sentence",
+    "man",
+    "think",
+    "say",
+    "great",
+    "where",
+    "help",
+    "through",
+    "much",
+    "before",
+    "line",
+    "right",
+    "too",
+    "mean",
+    "old",
+    "any",
+    "same",
+    "tell",
+    "boy",
+    "follow",
+    "came",
+    "want",
+    "show",
+    "also",
+    "around",
+    "form",
+    "three",
+    "small",
+    "set",
+    "put",
+    "end",
+    "does",
+    "another",
+    "well",
+    "large",
+    "must",
+    "big",
+    "even",
+    "such",
+    "because",
+    "turn",
+    "here",
+    "why",
+    "ask",
+    "went",
+    "men",
+    "read",
+    "need",
+    "land",
+    "different",
+    "home",
+    "move",
+    "try",
+    "kind",
+    "hand",
+    "picture",
+    "again",
+    "change",
+    "off",
+    "play",
+    "spell",
+    "air",
+    "away",
+    "animal",
+    "house",
+    "point",
+    "page",
+    "letter",
+    "mother",
+    "answer",
+    "found",
+    "study",
+    "still",
+    "learn",
+    "should",
+    "america",
+    "world",
+    "monday",
+    "summer",
+    "winter",
+    "spring",
+    "autumn",
+    "apple",
+    "orange",
+    "banana",
+    "secret",
+    "money",
+    "family",
+    "friend",
+    "happy",
+    "cookie",
+    "flower",
+    "garden",
+    "music",
+    "purple",
+    "silver",
+];
+
+const KEYBOARD_ROWS: &[&str] = &[
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+];
+
+// Undone to find dictionary words behind predictable substitutions, e.g. "p@ssw0rd"
+const L33T_SUBSTITUTIONS: &[(char, char)] = &[
+    ('4', 'a'),
+    ('@', 'a'),
+    ('8', 'b'),
+    ('(', 'c'),
+    ('3', 'e'),
+    ('6', 'g'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('|', 'l'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+    ('+', 't'),
+    ('2', 'z'),
+];
+
+// How guessable a password is, with advice on making it less so
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordStrength {
+    // From 0 (guessed almost straight away) to 4 (very unlikely to be guessed)
+    pub score: u8,
+    // Estimated number of guesses to find the password, as a power of 10
+    pub guesses_log10: f64,
+    pub warning: Option<&'static str>,
+    pub suggestions: Vec<&'static str>,
+}
+
+// Estimates the strength of `password`. `user_inputs` are words an attacker targeting this
+// user would try first, such as the parts of their email address.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let chars: Vec<char> = password.chars().take(MAX_ESTIMATED_LENGTH).collect();
+    let matches = find_matches(&chars, user_inputs);
+    let (guesses, sequence) = most_guessable_sequence(&chars, matches);
+    let score = score(guesses);
+    let (warning, suggestions) = feedback(score, &sequence);
+
+    PasswordStrength {
+        score,
+        guesses_log10: guesses.log10(),
+        warning,
+        suggestions,
+    }
+}
+
+// The thresholds zxcvbn uses, from "too guessable" to "very unguessable"
+fn score(guesses: f64) -> u8 {
+    const DELTA: f64 = 5.0;
+    if guesses < 1e3 + DELTA {
+        0
+    } else if guesses < 1e6 + DELTA {
+        1
+    } else if guesses < 1e8 + DELTA {
+        2
+    } else if guesses < 1e10 + DELTA {
+        3
+    } else {
+        4
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    Dictionary {
+        rank: usize,
+        list: WordList,
+        uppercase: Uppercase,
+        l33t: bool,
+        reversed: bool,
+    },
+    Sequence,
+    Repeat {
+        single_char: bool,
+    },
+    Keyboard,
+    Year,
+    Bruteforce,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WordList {
+    Passwords,
+    Words,
+    UserInputs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Uppercase {
+    None,
+    First,
+    All,
+    Mixed,
+}
+
+// The characters `start..=end` of the password follow `pattern`
+#[derive(Debug, Clone, PartialEq)]
+struct Match {
+    start: usize,
+    end: usize,
+    pattern: Pattern,
+    guesses: f64,
+}
+
+impl Match {
+    fn len(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+fn find_matches(chars: &[char], user_inputs: &[&str]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    dictionary_matches(chars, user_inputs, &mut matches);
+    sequence_matches(chars, &mut matches);
+    repeat_matches(chars, user_inputs, &mut matches);
+    keyboard_matches(chars, &mut matches);
+    year_matches(chars, &mut matches);
+    matches
+}
+
+fn ranked(words: &[&str]) -> HashMap<String, usize> {
+    let mut ranked = HashMap::new();
+    for (i, word) in words.iter().enumerate() {
+        ranked.entry(word.to_lowercase()).or_insert(i + 1);
+    }
+    ranked
+}
+
+fn dictionary_matches(chars: &[char], user_inputs: &[&str], matches: &mut Vec<Match>) {
+    static PASSWORDS: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    static WORDS: OnceLock<HashMap<String, usize>> = OnceLock::new();
+    let user_inputs = ranked(user_inputs);
+    let lists = [
+        (
+            WordList::Passwords,
+            PASSWORDS.get_or_init(|| ranked(COMMON_PASSWORDS)),
+        ),
+        (WordList::Words, WORDS.get_or_init(|| ranked(COMMON_WORDS))),
+        (WordList::UserInputs, &user_inputs),
+    ];
+    let lower: Vec<char> = chars.iter().flat_map(|c| c.to_lowercase()).collect();
+    // Lowercasing can change the length of some characters, which would misalign the matches
+    if lower.len() != chars.len() {
+        return;
+    }
+    let unl33ted: Vec<char> = lower.iter().map(|c| unl33t(*c)).collect();
+
+    for start in 0..chars.len() {
+        for end in start + 2..chars.len() {
+            let candidates = [
+                (lower[start..=end].iter().collect::<String>(), false, false),
+                (lower[start..=end].iter().rev().collect(), false, true),
+                (unl33ted[start..=end].iter().collect(), true, false),
+            ];
+            for (word, l33t, reversed) in candidates {
+                if l33t && lower[start..=end] == unl33ted[start..=end] {
+                    continue;
+                }
+                for (list, ranked) in lists.iter() {
+                    if let Some(rank) = ranked.get(&word) {
+                        let original = &chars[start..=end];
+                        let uppercase = uppercase(original);
+                        let pattern = Pattern::Dictionary {
+                            rank: *rank,
+                            list: *list,
+                            uppercase,
+                            l33t,
+                            reversed,
+                        };
+                        let guesses = *rank as f64
+                            * uppercase_variations(original)
+                            * l33t_variations(&lower[start..=end], &unl33ted[start..=end])
+                            * if reversed { 2.0 } else { 1.0 };
+                        matches.push(Match {
+                            start,
+                            end,
+                            pattern,
+                            guesses,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn unl33t(c: char) -> char {
+    L33T_SUBSTITUTIONS
+        .iter()
+        .find(|(l33t, _)| *l33t == c)
+        .map_or(c, |(_, letter)| *letter)
+}
+
+fn uppercase(word: &[char]) -> Uppercase {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    if upper == 0 {
+        Uppercase::None
+    } else if word.iter().all(|c| !c.is_lowercase()) {
+        Uppercase::All
+    } else if upper == 1 && word[0].is_uppercase() {
+        Uppercase::First
+    } else {
+        Uppercase::Mixed
+    }
+}
+
+fn uppercase_variations(word: &[char]) -> f64 {
+    match uppercase(word) {
+        Uppercase::None => 1.0,
+        // Capitalizing the first letter, or all of them, is what everyone does
+        Uppercase::First | Uppercase::All => 2.0,
+        Uppercase::Mixed => {
+            let upper = word.iter().filter(|c| c.is_uppercase()).count();
+            let lower = word.iter().filter(|c| c.is_lowercase()).count();
+            (1..=upper.min(lower))
+                .map(|k| n_choose_k(upper + lower, k))
+                .sum()
+        }
+    }
+}
+
+fn l33t_variations(original: &[char], unl33ted: &[char]) -> f64 {
+    let substituted = original
+        .iter()
+        .zip(unl33ted)
+        .filter(|(a, b)| a != b)
+        .count();
+    2f64.powi(substituted as i32)
+}
+
+fn n_choose_k(n: usize, k: usize) -> f64 {
+    (1..=k).fold(1.0, |result, i| result * (n + 1 - i) as f64 / i as f64)
+}
+
+// Runs of 3 or more characters that go up or down by the same small step, e.g. "abc" or "7531"
+fn sequence_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let delta = chars[start + 1] as i64 - chars[start] as i64;
+        let mut end = start + 1;
+        while end + 1 < chars.len() && chars[end + 1] as i64 - chars[end] as i64 == delta {
+            end += 1;
+        }
+        if end - start >= 2 && delta != 0 && delta.abs() <= 5 {
+            let first = chars[start];
+            let mut base: f64 = if "aAzZ019".contains(first) {
+                4.0
+            } else if first.is_ascii_digit() {
+                10.0
+            } else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+            let pattern = Pattern::Sequence;
+            let guesses = base * (end - start + 1) as f64;
+            matches.push(Match {
+                start,
+                end,
+                pattern,
+                guesses,
+            });
+        }
+        start = end;
+    }
+}
+
+// The same character or string over and over, e.g. "aaaa" or "abcabcabc". Like zxcvbn, each
+// repeat covers as much as it can and matching carries on after it, so only the shortest unit
+// that repeats is estimated.
+fn repeat_matches(chars: &[char], user_inputs: &[&str], matches: &mut Vec<Match>) {
+    let mut start = 0;
+    while start < chars.len() {
+        let mut longest: Option<(usize, usize)> = None;
+        for unit_len in 1..=(chars.len() - start) / 2 {
+            let unit = &chars[start..start + unit_len];
+            let mut repeats = 1;
+            while chars[start + repeats * unit_len..]
+                .get(..unit_len)
+                .is_some_and(|next| next == unit)
+            {
+                repeats += 1;
+            }
+            let long_enough = repeats >= 3 || (repeats == 2 && unit_len > 1);
+            let covers_more = longest.is_none_or(|(len, count)| repeats * unit_len > len * count);
+            if long_enough && covers_more {
+                longest = Some((unit_len, repeats));
+            }
+        }
+
+        let Some((unit_len, repeats)) = longest else {
+            start += 1;
+            continue;
+        };
+        let unit = &chars[start..start + unit_len];
+        let unit_guesses = most_guessable_sequence(unit, find_matches(unit, user_inputs)).0;
+        let end = start + repeats * unit_len - 1;
+        matches.push(Match {
+            start,
+            end,
+            pattern: Pattern::Repeat {
+                single_char: unit_len == 1,
+            },
+            guesses: unit_guesses * repeats as f64,
+        });
+        start = end + 1;
+    }
+}
+
+// Runs of 3 or more neighbouring keys on a row of a QWERTY keyboard, e.g. "asdf" or "poiuy".
+// Each change of direction counts as a turn.
+fn keyboard_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let position = |c: char| {
+        let c = c.to_ascii_lowercase();
+        KEYBOARD_ROWS.iter().enumerate().find_map(|(row, keys)| {
+            keys.chars()
+                .position(|key| key == c)
+                .map(|col| (row, col as i64))
+        })
+    };
+    let step = |a: char, b: char| match (position(a), position(b)) {
+        (Some((row_a, col_a)), Some((row_b, col_b))) if row_a == row_b => {
+            Some(col_b - col_a).filter(|step| step.abs() == 1)
+        }
+        _ => None,
+    };
+
+    let mut start = 0;
+    while start + 2 < chars.len() {
+        let mut end = start;
+        let mut turns = 0;
+        let mut last_step = None;
+        while end + 1 < chars.len() {
+            let Some(step) = step(chars[end], chars[end + 1]) else {
+                break;
+            };
+            if last_step.is_some_and(|last_step| last_step != step) {
+                turns += 1;
+            }
+            last_step = Some(step);
+            end += 1;
+        }
+        if end - start >= 2 {
+            let len = (end - start + 1) as f64;
+            let guesses =
+                KEYBOARD_STARTING_POSITIONS * KEYBOARD_AVERAGE_DEGREE.powi(turns + 1) * len;
+            matches.push(Match {
+                start,
+                end,
+                pattern: Pattern::Keyboard,
+                guesses,
+            });
+        }
+        start = end.max(start + 1);
+    }
+}
+
+// Years from 1900 to 2099
+fn year_matches(chars: &[char], matches: &mut Vec<Match>) {
+    let current_year = Utc::now().year() as f64;
+    for start in 0..chars.len().saturating_sub(3) {
+        let year: String = chars[start..start + 4].iter().collect();
+        if !(year.starts_with("19") || year.starts_with("20")) {
+            continue;
+        }
+        if let Ok(year) = year.parse::<u16>() {
+            let guesses = (year as f64 - current_year).abs().max(MIN_YEAR_SPACE);
+            matches.push(Match {
+                start,
+                end: start + 3,
+                pattern: Pattern::Year,
+                guesses,
+            });
+        }
+    }
+}
+
+fn bruteforce_match(start: usize, end: usize) -> Match {
+    let len = end - start + 1;
+    let min_guesses = match len {
+        1 => MIN_SUBMATCH_GUESSES_SINGLE_CHAR,
+        _ => MIN_SUBMATCH_GUESSES_MULTI_CHAR,
+    };
+    Match {
+        start,
+        end,
+        pattern: Pattern::Bruteforce,
+        guesses: BRUTEFORCE_CARDINALITY
+            .powi(len as i32)
+            .max(min_guesses + 1.0),
+    }
+}
+
+// The split of the password into matches that takes the fewest guesses overall. Guessing a
+// sequence of `l` matches takes `l!` times the product of their guesses, as an attacker doesn't
+// know which patterns come in which order.
+fn most_guessable_sequence(chars: &[char], matches: Vec<Match>) -> (f64, Vec<Match>) {
+    let n = chars.len();
+    if n == 0 {
+        return (1.0, Vec::new());
+    }
+
+    let mut by_end: Vec<Vec<Match>> = vec![Vec::new(); n];
+    for mut m in matches {
+        // A pattern inside a longer password takes at least this many guesses
+        if m.len() < n {
+            let min_guesses = match m.len() {
+                1 => MIN_SUBMATCH_GUESSES_SINGLE_CHAR,
+                _ => MIN_SUBMATCH_GUESSES_MULTI_CHAR,
+            };
+            m.guesses = m.guesses.max(min_guesses);
+        }
+        by_end[m.end].push(m);
+    }
+    for (end, matches) in by_end.iter_mut().enumerate() {
+        for start in 0..=end {
+            matches.push(bruteforce_match(start, end));
+        }
+    }
+
+    // For each end and number of matches, the product of guesses of the best sequence so far,
+    // with the index of the match it ends with to rebuild it
+    let mut best: Vec<HashMap<usize, (f64, usize)>> = vec![HashMap::new(); n];
+    for end in 0..n {
+        for (index, m) in by_end[end].iter().enumerate() {
+            let candidates: Vec<(usize, f64)> = match m.start {
+                0 => vec![(1, m.guesses)],
+                start => best[start - 1]
+                    .iter()
+                    .map(|(len, (product, _))| (len + 1, product * m.guesses))
+                    .collect(),
+            };
+            for (len, product) in candidates {
+                let current = best[end].get(&len).map(|(product, _)| *product);
+                if current.is_none_or(|current| product < current) {
+                    best[end].insert(len, (product, index));
+                }
+            }
+        }
+    }
+
+    let total_guesses = |len: usize, product: f64| {
+        let factorial: f64 = (1..=len).map(|i| i as f64).product();
+        factorial * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(len as i32 - 1)
+    };
+    let (mut len, guesses) = best[n - 1]
+        .iter()
+        .map(|(len, (product, _))| (*len, total_guesses(*len, *product)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((1, 1.0));
+
+    let mut sequence = Vec::with_capacity(len);
+    let mut end = n - 1;
+    loop {
+        let (_, index) = best[end][&len];
+        let m = by_end[end][index].clone();
+        let start = m.start;
+        sequence.push(m);
+        if start == 0 {
+            break;
+        }
+        end = start - 1;
+        len -= 1;
+    }
+    sequence.reverse();
+
+    (guesses, sequence)
+}
+
+fn feedback(score: u8, sequence: &[Match]) -> (Option<&'static str>, Vec<&'static str>) {
+    if sequence.is_empty() {
+        return (
+            None,
+            vec![
+                "Use a few words, avoid common phrases",
+                "No need for symbols, digits, or uppercase letters",
+            ],
+        );
+    }
+    if score > 2 {
+        return (None, Vec::new());
+    }
+
+    let mut suggestions = vec!["Add another word or two. Uncommon words are better."];
+    let longest = sequence
+        .iter()
+        .max_by_key(|m| m.len())
+        .expect("the sequence isn't empty");
+    let warning = match &longest.pattern {
+        Pattern::Dictionary {
+            rank,
+            list,
+            uppercase,
+            l33t,
+            reversed,
+        } => {
+            match uppercase {
+                Uppercase::First => suggestions.push("Capitalization doesn't help very much"),
+                Uppercase::All => {
+                    suggestions.push("All-uppercase is almost as easy to guess as all-lowercase")
+                }
+                Uppercase::None | Uppercase::Mixed => {}
+            }
+            if *reversed && longest.len() >= 4 {
+                suggestions.push("Reversed words aren't much harder to guess");
+            }
+            if *l33t {
+                suggestions
+                    .push("Predictable substitutions like '@' instead of 'a' don't help very much");
+            }
+            let sole_match = sequence.len() == 1;
+            match list {
+                WordList::Passwords if sole_match && !l33t && !reversed && *rank <= 10 => {
+                    Some("This is a top-10 common password")
+                }
+                WordList::Passwords if sole_match && !l33t && !reversed && *rank <= 100 => {
+                    Some("This is a top-100 common password")
+                }
+                WordList::Passwords if sole_match && !l33t && !reversed => {
+                    Some("This is a very common password")
+                }
+                WordList::Passwords => Some("This is similar to a commonly used password"),
+                WordList::Words if sole_match => Some("A word by itself is easy to guess"),
+                WordList::Words => None,
+                WordList::UserInputs => Some("Avoid using parts of your email address"),
+            }
+        }
+        Pattern::Sequence => {
+            suggestions.push("Avoid sequences");
+            Some("Sequences like abc or 6543 are easy to guess")
+        }
+        Pattern::Repeat { single_char } => {
+            suggestions.push("Avoid repeated words and characters");
+            match single_char {
+                true => Some("Repeats like \"aaa\" are easy to guess"),
+                false => Some(
+                    "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"",
+                ),
+            }
+        }
+        Pattern::Keyboard => {
+            suggestions.push("Use a longer keyboard pattern with more turns");
+            Some("Straight rows of keys are easy to guess")
+        }
+        Pattern::Year => {
+            suggestions.push("Avoid recent years");
+            suggestions.push("Avoid years that are associated with you");
+            Some("Recent years are easy to guess")
+        }
+        Pattern::Bruteforce => None,
+    };
+
+    (warning, suggestions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_common_passwords_are_weak() {
+        for password in ["password", "123456", "qwertyuiop", "letmein", "iloveyou"] {
+            let strength = estimate_strength(password, &[]);
+            assert_eq!(strength.score, 0, "Failed for {}", password);
+        }
+
+        let strength = estimate_strength("password", &[]);
+        assert_eq!(strength.warning, Some("This is a top-10 common password"));
+        assert!(!strength.suggestions.is_empty());
+    }
+
+    #[test]
+    fn test_patterns_are_weak() {
+        let test_cases = [
+            ("aaaaaaaaaaaa", "Repeats like \"aaa\" are easy to guess"),
+            (
+                "abcabcabcabc",
+                "Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\"",
+            ),
+            (
+                "abcdefghijkl",
+                "Sequences like abc or 6543 are easy to guess",
+            ),
+            ("asdfghjkl", "Straight rows of keys are easy to guess"),
+            ("P@ssw0rd", "This is similar to a commonly used password"),
+        ];
+
+        for (password, warning) in test_cases {
+            let strength = estimate_strength(password, &[]);
+            assert!(
+                strength.score <= 1,
+                "Failed for {}: {:?}",
+                password,
+                strength
+            );
+            assert_eq!(strength.warning, Some(warning), "Failed for {}", password);
+        }
+
+        let strength = estimate_strength("P@ssw0rd", &[]);
+        assert!(strength
+            .suggestions
+            .contains(&"Predictable substitutions like '@' instead of 'a' don't help very much"));
+    }
+
+    #[test]
+    fn test_user_inputs_are_weak() {
+        let strength = estimate_strength("jordanjordan", &["jordan"]);
+        assert_eq!(strength.score, 0);
+
+        let strength = estimate_strength("kowalski1987", &["kowalski"]);
+        assert_eq!(
+            strength.warning,
+            Some("Avoid using parts of your email address")
+        );
+        assert!(strength.score < estimate_strength("kowalski1987", &[]).score);
+    }
+
+    #[test]
+    fn test_unpredictable_passwords_are_strong() {
+        for password in [
+            "kX9$mQ2!vL7p",
+            "correct horse battery staple",
+            "tz8#Wq1-fbN0yr",
+        ] {
+            let strength = estimate_strength(password, &[]);
+            assert_eq!(strength.score, 4, "Failed for {}: {:?}", password, strength);
+            assert_eq!(strength.warning, None);
+            assert!(strength.suggestions.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_longer_is_stronger() {
+        let short = estimate_strength("mxqzvb", &[]);
+        let long = estimate_strength("mxqzvbwplk", &[]);
+        assert!(long.guesses_log10 > short.guesses_log10);
+        assert!(long.score > short.score);
+    }
+
+    #[test]
+    fn test_empty_password_gets_default_suggestions() {
+        let strength = estimate_strength("", &[]);
+        assert_eq!(strength.score, 0);
+        assert_eq!(strength.suggestions.len(), 2);
+    }
+
+    #[test]
+    fn test_long_passwords_are_estimated_quickly() {
+        let password: String = (0..1000).map(|i| i.to_string()).collect();
+        let strength = estimate_strength(&password, &[]);
+        assert_eq!(strength.score, 4);
+
+        // Only the first 100 characters are estimated, so these are whole repeats
+        for password in ["a".repeat(1000), "ab".repeat(500), "abcd".repeat(250)] {
+            let strength = estimate_strength(&password, &[]);
+            assert!(
+                strength.score <= 1,
+                "Failed for {}: {:?}",
+                password,
+                strength
+            );
+        }
+    }
+}
@@ -1,10 +1,20 @@
-use crate::domain::{email::Email, password::Password};
+use chrono::Utc;
+
+use crate::domain::{email::Email, password::Password, password_policy::PasswordPolicy};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct User {
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
+    // Unix timestamp (seconds) at which a deleted account will be purged.
+    // `None` unless the user has asked for their account to be deleted.
+    pub deletion_scheduled_for: Option<i64>,
+    pub lockout: LoginLockout,
+    // Unix timestamp (seconds) at which the current password was set
+    pub password_changed_at: i64,
+    // Set by an administrator, e.g. after an incident, until the user changes their password
+    pub must_change_password: bool,
 }
 
 impl User {
@@ -13,6 +23,158 @@ impl User {
             email,
             password,
             requires_2fa,
+            deletion_scheduled_for: None,
+            lockout: LoginLockout::default(),
+            password_changed_at: Utc::now().timestamp(),
+            must_change_password: false,
+        }
+    }
+
+    // Why the user has to change their password before they can do anything else, if they do
+    pub fn password_change_reason(
+        &self,
+        policy: &PasswordPolicy,
+        now: i64,
+    ) -> Option<PasswordChangeReason> {
+        if self.must_change_password {
+            Some(PasswordChangeReason::Required)
+        } else if policy.is_expired(self.password_changed_at, now) {
+            Some(PasswordChangeReason::Expired)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PasswordChangeReason {
+    // An administrator asked for it
+    Required,
+    // Older than `password_policy.max_age_days`
+    Expired,
+}
+
+// Failed logins since the last successful one, and the lockout they caused
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoginLockout {
+    pub failed_attempts: u32,
+    // Unix timestamp (seconds) until which logins are refused
+    pub locked_until: Option<i64>,
+}
+
+impl LoginLockout {
+    // Returns how many seconds are left on the lockout, if the account is locked at `now`
+    pub fn remaining_seconds(&self, now: i64) -> Option<u64> {
+        self.locked_until
+            .filter(|locked_until| *locked_until > now)
+            .map(|locked_until| (locked_until - now) as u64)
+    }
+
+    // Counts a failed login. Once `threshold` is reached, every further failure locks
+    // the account for twice as long as the previous one, starting at `base_seconds`.
+    // Returns the new lockout duration if this failure locked the account.
+    pub fn record_failure(
+        &mut self,
+        now: i64,
+        threshold: u32,
+        base_seconds: i64,
+        max_seconds: i64,
+    ) -> Option<i64> {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+
+        if self.failed_attempts < threshold {
+            return None;
         }
+
+        let doublings = (self.failed_attempts - threshold).min(32);
+        let seconds = base_seconds.saturating_mul(1_i64 << doublings).min(max_seconds);
+        self.locked_until = Some(now + seconds);
+        Some(seconds)
+    }
+}
+
+// A logged-in session, identified by the `jti` of the JWT that was issued for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoginOutcome {
+    Success,
+    TwoFactorRequired,
+    PasswordChangeRequired,
+    IncorrectCredentials,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoginRecord {
+    pub at: i64,
+    pub outcome: LoginOutcome,
+}
+
+// Something the user agreed to (e.g. "terms_of_service") and when
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConsentRecord {
+    pub purpose: String,
+    pub granted_at: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_grows_exponentially() {
+        let mut lockout = LoginLockout::default();
+
+        for _ in 0..2 {
+            assert_eq!(lockout.record_failure(0, 3, 60, 3600), None);
+        }
+        assert_eq!(lockout.remaining_seconds(0), None);
+
+        assert_eq!(lockout.record_failure(0, 3, 60, 3600), Some(60));
+        assert_eq!(lockout.remaining_seconds(0), Some(60));
+        assert_eq!(lockout.remaining_seconds(60), None);
+
+        assert_eq!(lockout.record_failure(100, 3, 60, 3600), Some(120));
+        assert_eq!(lockout.record_failure(100, 3, 60, 3600), Some(240));
+        assert_eq!(lockout.remaining_seconds(100), Some(240));
+
+        // The lockout never exceeds the maximum
+        for _ in 0..50 {
+            lockout.record_failure(100, 3, 60, 3600);
+        }
+        assert_eq!(lockout.remaining_seconds(100), Some(3600));
+    }
+
+    #[test]
+    fn test_password_change_reason() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password123".to_owned()).unwrap();
+        let mut user = User::new(email, password, false);
+        user.password_changed_at = 0;
+
+        let day = 86400;
+        let policy = PasswordPolicy {
+            max_age_days: 90,
+            ..PasswordPolicy::default()
+        };
+        assert_eq!(user.password_change_reason(&policy, 90 * day - 1), None);
+        assert_eq!(
+            user.password_change_reason(&policy, 90 * day),
+            Some(PasswordChangeReason::Expired)
+        );
+
+        // Passwords never expire by default
+        assert_eq!(user.password_change_reason(&PasswordPolicy::default(), 1000 * day), None);
+
+        user.must_change_password = true;
+        assert_eq!(
+            user.password_change_reason(&PasswordPolicy::default(), 0),
+            Some(PasswordChangeReason::Required)
+        );
     }
 }
\ No newline at end of file
@@ -1,16 +1,42 @@
 use axum::{
-    http::{Method, StatusCode},
+    http::{header, HeaderName, Method, StatusCode},
+    middleware,
     response::{IntoResponse, Response},
-    routing::post,
-    serve::Serve,
+    routing::{delete, get, post},
     Json, Router,
 };
-use tower_http::{cors::CorsLayer, services::ServeDir};
-use std::error::Error;
-use domain::AuthAPIError;
+use axum_server::Handle;
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
+use chrono::Utc;
+use std::{error::Error, future::Future, io, net::SocketAddr, pin::Pin, time::Duration};
+use tokio::task::JoinHandle;
+use domain::{AuthAPIError, FieldError};
 use serde::{Deserialize, Serialize};
 use app_state::AppState;
-use routes::{login, logout, signup, verify_2fa, verify_token};
+use routes::{
+    admin_require_password_change, admin_unlock_account, cancel_email_change, change_email, change_password, confirm_disable_2fa,
+    confirm_email_change, confirm_enable_2fa, delete_account, disable_2fa, enable_2fa,
+    export_account, forgot_password, get_audit_events, health_live, health_ready, login, logout,
+    metrics, password_strength, reset_password, signup, unlock_account, verify_2fa,
+    verify_token,
+};
+use utils::{
+    account_purge::run_account_purge,
+    audit::{audit, run_audit_checkpoints},
+    config_reload::run_config_reload,
+    constants::{CSRF_TOKEN_HEADER, PROBLEM_JSON_CONTENT_TYPE, REQUEST_ID_HEADER},
+    metrics::{prometheus_handle, track_metrics},
+    problem::problem_details,
+    rate_limit::{rate_limit, run_rate_limit_pruning},
+    shutdown::ShutdownHandle,
+    telemetry::{make_request_span, on_response},
+    tls::{load_tls_config, run_certificate_reload},
+};
 
 pub mod routes;
 pub mod domain;
@@ -20,78 +46,318 @@ pub mod utils;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<Router, Router>,
+    server: Pin<Box<dyn Future<Output = io::Result<()>> + Send>>,
+    // Stops the server from accepting connections and waits for the open ones to finish
+    server_handle: Handle,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
+    // Whether the server speaks HTTPS
+    pub tls: bool,
+    shutdown: ShutdownHandle,
+    // How long in-flight requests get to finish once shutdown is requested
+    drain_timeout: Duration,
+    app_state: AppState,
+    background_tasks: Vec<JoinHandle<()>>,
 }
 
 impl Application {
     pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
-        let allowed_origins = [
-            "http://localhost:8000".parse()?,
-            "http://127.0.0.1:8000".parse()?,
-            "http://[::1]:8000".parse()?,
-            "http://161.35.39.176:8000".parse()?,
-            "http://[2a03:b0c0:1:e0::a406:6001]:8000".parse()?,
-        ];
-        
+        // Checked against the current settings, so reloaded origins apply straight away
+        let allowed_origins = AllowOrigin::predicate({
+            let settings = app_state.settings.clone();
+            move |origin, _| {
+                settings
+                    .current()
+                    .allowed_origins
+                    .iter()
+                    .any(|allowed| allowed.as_bytes() == origin.as_bytes())
+            }
+        });
+
         let cors = CorsLayer::new()
-            // Allow GET and POST requests
-            .allow_methods([Method::GET, Method::POST])
+            // Allow GET, POST and DELETE requests
+            .allow_methods([Method::GET, Method::POST, Method::DELETE])
             // Allow cookies to be included in requests
             .allow_credentials(true)
+            // Pages on allowed origins send the CSRF token they read from the CSRF cookie
+            .allow_headers([HeaderName::from_static(CSRF_TOKEN_HEADER)])
             .allow_origin(allowed_origins);
 
 
+        // Loaded up front, so a missing or broken certificate stops the app from starting
+        let tls_config = match &app_state.settings.current().tls {
+            Some(tls) => Some(load_tls_config(tls).await?),
+            None => None,
+        };
+
+        let shutdown = ShutdownHandle::default();
+        let mut background_tasks = vec![
+            // Purge accounts whose deletion grace period has ended in the background
+            tokio::spawn(run_account_purge(app_state.clone(), shutdown.clone())),
+            // Periodically sign the audit log so later edits can be detected
+            tokio::spawn(run_audit_checkpoints(app_state.clone(), shutdown.clone())),
+            // Drop rate limit buckets of clients that have gone quiet
+            tokio::spawn(run_rate_limit_pruning(
+                app_state.rate_limiter.clone(),
+                shutdown.clone(),
+            )),
+            // Pick up config file changes without a restart
+            tokio::spawn(run_config_reload(app_state.settings.clone(), shutdown.clone())),
+        ];
+        if let Some(tls_config) = &tls_config {
+            // Serve renewed certificates without a restart
+            background_tasks.push(tokio::spawn(run_certificate_reload(
+                tls_config.clone(),
+                app_state.settings.clone(),
+                shutdown.clone(),
+            )));
+        }
+
+        let request_id_header = HeaderName::from_static(REQUEST_ID_HEADER);
+
+        // Metrics are only recorded once the recorder is installed
+        prometheus_handle();
+
         let router = Router::new()
-            .nest_service("/", ServeDir::new("assets"))
             .route("/signup", post(signup))
             .route("/login", post(login))
             .route("/verify-2fa", post(verify_2fa))
             .route("/logout", post(logout))
             .route("/verify-token", post(verify_token))
-            .with_state(app_state)
-            .layer(cors);
+            .route("/change-email", post(change_email))
+            .route("/confirm-email-change", post(confirm_email_change))
+            .route("/cancel-email-change", post(cancel_email_change))
+            .route("/change-password", post(change_password))
+            .route("/forgot-password", post(forgot_password))
+            .route("/reset-password", post(reset_password))
+            .route("/password-strength", post(password_strength))
+            .route("/account", delete(delete_account))
+            .route("/account/export", get(export_account))
+            .route("/enable-2fa", post(enable_2fa))
+            .route("/confirm-enable-2fa", post(confirm_enable_2fa))
+            .route("/disable-2fa", post(disable_2fa))
+            .route("/confirm-disable-2fa", post(confirm_disable_2fa))
+            .route("/unlock-account", post(unlock_account))
+            .route("/admin/unlock-account", post(admin_unlock_account))
+            .route("/admin/require-password-change", post(admin_require_password_change))
+            .route("/admin/audit-events", get(get_audit_events))
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), rate_limit))
+            // Outermost, so requests turned away by the rate limiter are audited too
+            .route_layer(middleware::from_fn_with_state(app_state.clone(), audit))
+            // Added after the layers above, so probes and scrapes are neither audited nor rate limited
+            .route("/health/live", get(health_live))
+            .route("/health/ready", get(health_ready))
+            .route("/metrics", get(metrics))
+            .nest_service("/", ServeDir::new("assets"))
+            .with_state(app_state.clone())
+            // Inside the CORS layer, so browsers on allowed origins can read the errors too
+            .layer(middleware::from_fn(problem_details))
+            .layer(cors)
+            .layer(middleware::from_fn(track_metrics))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(make_request_span)
+                    .on_request(())
+                    .on_response(on_response)
+                    .on_failure(()),
+            )
+            .layer(PropagateRequestIdLayer::new(request_id_header.clone()))
+            // Outermost, so the request ID is set before anything else sees the request
+            .layer(SetRequestIdLayer::new(request_id_header, MakeRequestUuid));
 
-        let listener = tokio::net::TcpListener::bind(address).await?;
+        let listener = std::net::TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        let tls = tls_config.is_some();
+
+        // The peer address is needed to rate limit by IP
+        let make_service = router.into_make_service_with_connect_info::<SocketAddr>();
+        let server_handle = Handle::new();
+        let server: Pin<Box<dyn Future<Output = io::Result<()>> + Send>> = match tls_config {
+            Some(tls_config) => Box::pin(
+                axum_server::from_tcp_rustls(listener, tls_config)
+                    .handle(server_handle.clone())
+                    .serve(make_service),
+            ),
+            None => Box::pin(
+                axum_server::from_tcp(listener)
+                    .handle(server_handle.clone())
+                    .serve(make_service),
+            ),
+        };
 
         // Create a new Application instance and return it
         Ok(Application {
             server,
+            server_handle,
             address,
+            tls,
+            shutdown,
+            drain_timeout: Duration::from_secs(app_state.settings.current().shutdown_drain_timeout_seconds),
+            app_state,
+            background_tasks,
         })
     }
 
+    pub fn with_drain_timeout(mut self, drain_timeout: Duration) -> Self {
+        self.drain_timeout = drain_timeout;
+        self
+    }
+
+    // Stops the application when triggered. See `utils::shutdown::shutdown_on_signal` for OS signals.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown.clone()
+    }
+
+    // Serves requests until shutdown is requested, then stops accepting connections,
+    // lets in-flight requests finish within the drain timeout and flushes the audit log.
     pub async fn run(self) -> Result<(), std::io::Error> {
-        println!("listening on {}", &self.address);
-        self.server.await
+        let Application {
+            server,
+            server_handle,
+            address,
+            tls,
+            shutdown,
+            drain_timeout,
+            app_state,
+            background_tasks,
+        } = self;
+        tracing::info!(tls, "listening on {}", &address);
+
+        tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                shutdown.requested().await;
+                // The drain timeout is enforced below, so wait for connections as long as it takes
+                server_handle.graceful_shutdown(None);
+            }
+        });
+        let drained = async {
+            let result = server.await;
+            // The server may also have stopped on an error, so make sure the background tasks stop too
+            shutdown.shutdown();
+            for task in background_tasks {
+                let _ = task.await;
+            }
+            result
+        };
+        let drain_deadline = async {
+            shutdown.requested().await;
+            tokio::time::sleep(drain_timeout).await;
+        };
+
+        let result = tokio::select! {
+            result = drained => result,
+            _ = drain_deadline => {
+                tracing::warn!("drain timeout elapsed, dropping in-flight requests");
+                Ok(())
+            }
+        };
+
+        // Sign everything logged up to now so the final entries are covered by a checkpoint
+        if app_state
+            .audit_log
+            .write()
+            .await
+            .checkpoint(Utc::now().timestamp())
+            .await
+            .is_err()
+        {
+            tracing::error!("failed to write the final audit log checkpoint");
+        }
+
+        tracing::info!("shutdown complete");
+        result
     }
 
 }
 
-#[derive(Serialize, Deserialize)]
+// An RFC 7807 problem details body, returned as `application/problem+json` for every error
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ErrorResponse {
-    pub error: String,
+    // Always "about:blank": `code` identifies the problem instead
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    // The status' reason phrase, e.g. "Unauthorized"
+    pub title: String,
+    pub status: u16,
+    // Stable and machine readable, e.g. "incorrect_credentials"
+    pub code: String,
+    // Human readable explanation, which may change
+    pub detail: String,
+    // The X-Request-ID of the request, to find it in the logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    // One entry per invalid field, for forms to show next to the field
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
 }
 
 impl IntoResponse for AuthAPIError {
     fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
-            AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
-            AuthAPIError::IncorrectCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
-            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing token"),
-            AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+        // Tell the client when it's worth trying again
+        let retry_after = match self {
+            AuthAPIError::TooManyRequests { retry_after_seconds }
+            | AuthAPIError::AccountLocked { retry_after_seconds } => Some(retry_after_seconds),
+            _ => None,
+        };
+        let errors = match &self {
+            AuthAPIError::InvalidFields(errors) => errors.clone(),
+            _ => Vec::new(),
+        };
+
+        let (status, code, detail) = match self {
+            AuthAPIError::UserAlreadyExists => {
+                (StatusCode::CONFLICT, "user_already_exists", "User already exists")
+            }
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "user_not_found", "User not found"),
+            AuthAPIError::InvalidCredentials | AuthAPIError::InvalidFields(_) => {
+                (StatusCode::BAD_REQUEST, "invalid_credentials", "Invalid credentials")
+            }
+            AuthAPIError::IncorrectCredentials => {
+                (StatusCode::UNAUTHORIZED, "incorrect_credentials", "Invalid credentials")
+            }
+            AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "missing_token", "Missing token"),
+            AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "invalid_token", "Invalid token"),
+            AuthAPIError::InvalidCsrfToken => {
+                (StatusCode::FORBIDDEN, "invalid_csrf_token", "Invalid CSRF token")
+            }
+            AuthAPIError::PasswordChangeRequired => (
+                StatusCode::FORBIDDEN,
+                "password_change_required",
+                "Password change required",
+            ),
+            AuthAPIError::TwoFAUnchanged => (
+                StatusCode::CONFLICT,
+                "two_fa_unchanged",
+                "2FA is already in the requested state",
+            ),
+            AuthAPIError::TooManyRequests { .. } => {
+                (StatusCode::TOO_MANY_REQUESTS, "too_many_requests", "Too many requests")
+            }
+            AuthAPIError::AccountLocked { .. } => {
+                (StatusCode::LOCKED, "account_locked", "Account temporarily locked")
+            }
             AuthAPIError::UnexpectedError => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
+                (StatusCode::INTERNAL_SERVER_ERROR, "unexpected_error", "Unexpected error")
             }
         };
-        let body = Json(ErrorResponse {
-            error: error_message.to_string(),
-        });
-        (status, body).into_response()
+        let mut problem = ErrorResponse::new(status, code, detail);
+        problem.errors = errors;
+
+        // `problem_details` adds the request ID once the response is on its way out
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, PROBLEM_JSON_CONTENT_TYPE)],
+            Json(problem.clone()),
+        )
+            .into_response();
+        response.extensions_mut().insert(problem);
+        if let Some(seconds) = retry_after {
+            response.headers_mut().insert(header::RETRY_AFTER, seconds.into());
+        }
+        response
     }
-}
\ No newline at end of file
+}
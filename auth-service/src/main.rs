@@ -1,27 +1,73 @@
 use auth_service::{
-    app_state::AppState, services::{
+    app_state::{AppState, AuditLogType, EmailClientType}, services::{
         hashmap_user_store::HashmapUserStore, 
         hashset_banned_token_store::HashsetBannedTokenStore,
-        hashmap_two_fa_code_store::HashmapTwoFACodeStore
+        hashmap_two_fa_code_store::HashmapTwoFACodeStore,
+        hashmap_verification_token_store::HashmapVerificationTokenStore,
+        hashmap_rate_limit_store::HashmapRateLimitStore,
+        in_memory_audit_log::InMemoryAuditLog,
+        instrumented_store::InstrumentedStore,
+        json_lines_audit_log::JsonLinesAuditLog,
+        log_email_client::LogEmailClient,
+        postmark_email_client::PostmarkEmailClient,
     }, 
-    utils::constants::prod,
+    utils::{rate_limit::RateLimiter, settings::SettingsHandle, shutdown::shutdown_on_signal, telemetry::init_tracing},
     Application,
 };
 
 #[tokio::main]
 async fn main() {
-    let user_store = HashmapUserStore::default();
-    let banned_token_store = HashsetBannedTokenStore::default();
-    let two_fa_code_store = HashmapTwoFACodeStore::default();
+    // Tracing is configured by the settings, so problems can only be reported on stderr
+    let settings_handle = SettingsHandle::load().unwrap_or_else(|e| {
+        eprintln!("Failed to load configuration: {}", e);
+        std::process::exit(1);
+    });
+    let settings = settings_handle.current();
+
+    let tracer_provider = init_tracing(settings.log_format, settings.otlp_endpoint.as_deref());
+
+    let user_store = InstrumentedStore::new(HashmapUserStore::default());
+    let banned_token_store = InstrumentedStore::new(HashsetBannedTokenStore::default());
+    let two_fa_code_store = InstrumentedStore::new(HashmapTwoFACodeStore::default());
+    let verification_token_store = InstrumentedStore::new(HashmapVerificationTokenStore::default());
+    let email_client: EmailClientType = match settings.email.as_ref() {
+        Some(email) => std::sync::Arc::new(
+            PostmarkEmailClient::new(email).expect("Failed to build email client"),
+        ),
+        None => {
+            tracing::warn!("No email provider is configured, so emails are only logged");
+            std::sync::Arc::new(LogEmailClient)
+        }
+    };
+    let rate_limiter = RateLimiter::new(
+        std::sync::Arc::new(tokio::sync::RwLock::new(InstrumentedStore::new(HashmapRateLimitStore::default()))),
+    );
+    let audit_log: AuditLogType = match settings.audit_log_path.as_ref() {
+        Some(path) => std::sync::Arc::new(tokio::sync::RwLock::new(
+            JsonLinesAuditLog::open(path, settings_handle.clone())
+                .await
+                .expect("Failed to open audit log"),
+        )),
+        None => std::sync::Arc::new(tokio::sync::RwLock::new(InMemoryAuditLog::default())),
+    };
     let app_state = AppState::new(
         std::sync::Arc::new(tokio::sync::RwLock::new(user_store)),
         std::sync::Arc::new(tokio::sync::RwLock::new(banned_token_store)),
         std::sync::Arc::new(tokio::sync::RwLock::new(two_fa_code_store)),
+        std::sync::Arc::new(tokio::sync::RwLock::new(verification_token_store)),
+        email_client,
+        rate_limiter,
+        audit_log,
+        settings_handle,
     );
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+    let app = Application::build(app_state, &settings.app_address)
         .await
         .expect("Failed to build app");
 
+    tokio::spawn(shutdown_on_signal(app.shutdown_handle()));
     app.run().await.expect("Failed to run app");
-}
\ No newline at end of file
+
+    // Send any spans still waiting to be exported
+    let _ = tracer_provider.shutdown();
+}
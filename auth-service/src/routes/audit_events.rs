@@ -0,0 +1,71 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditQuery, AuthAPIError, Email},
+    utils::auth::authenticate_admin,
+};
+
+const DEFAULT_AUDIT_EVENTS_LIMIT: usize = 100;
+const MAX_AUDIT_EVENTS_LIMIT: usize = 1000;
+
+// Lists audit events, most recent first, optionally filtered by user and time range
+pub async fn get_audit_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<AuditEventsParams>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers, state.settings.current().admin_api_key.as_deref())?;
+
+    let actor = match params.email {
+        Some(email) => Some(
+            Email::parse(email)
+                .map_err(|_| AuthAPIError::InvalidCredentials)?
+                .as_ref()
+                .to_owned(),
+        ),
+        None => None,
+    };
+
+    let query = AuditQuery {
+        actor,
+        from: params.from,
+        to: params.to,
+        limit: Some(
+            params
+                .limit
+                .unwrap_or(DEFAULT_AUDIT_EVENTS_LIMIT)
+                .min(MAX_AUDIT_EVENTS_LIMIT),
+        ),
+    };
+
+    let events = state
+        .audit_log
+        .read()
+        .await
+        .query(&query)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, Json(AuditEventsResponse { events })))
+}
+
+#[derive(Deserialize)]
+pub struct AuditEventsParams {
+    pub email: Option<String>,
+    // Unix timestamps (seconds), inclusive
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditEventsResponse {
+    pub events: Vec<AuditEvent>,
+}
@@ -0,0 +1,253 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, PendingVerification, UserStoreError, VerificationPurpose,
+        VerificationToken,
+    },
+    utils::{
+        audit::AuditActor,
+        auth::authenticate_with_csrf,
+        constants::EMAIL_CHANGE_TOKEN_TTL_SECONDS,
+    },
+};
+
+// Starts an email change for the logged-in user. Nothing changes until the
+// new address confirms; the old address gets a notice with a cancel link.
+pub async fn change_email(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<ChangeEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let settings = state.settings.current();
+
+    let (_, claims) =
+        authenticate_with_csrf(&jar, &headers, state.banned_token_store.clone(), &settings).await?;
+
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    actor.set(&email);
+    let new_email =
+        Email::parse(request.new_email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    if new_email == email {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    {
+        let user_store = state.user_store.read().await;
+
+        if user_store.get_user(&email).await.is_err() {
+            return Err(AuthAPIError::InvalidToken);
+        }
+
+        if user_store.get_user(&new_email).await.is_ok() {
+            return Err(AuthAPIError::UserAlreadyExists);
+        }
+    }
+
+    let expires_at = Utc::now().timestamp() + EMAIL_CHANGE_TOKEN_TTL_SECONDS;
+    let confirm_token = VerificationToken::default();
+    let cancel_token = VerificationToken::default();
+
+    {
+        let mut verification_token_store = state.verification_token_store.write().await;
+
+        // Only the most recent request can be confirmed or cancelled
+        verification_token_store
+            .remove_tokens_for(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        verification_token_store
+            .add_token(
+                confirm_token.clone(),
+                PendingVerification {
+                    email: email.clone(),
+                    purpose: VerificationPurpose::ConfirmEmailChange {
+                        new_email: new_email.clone(),
+                    },
+                    expires_at,
+                },
+            )
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        verification_token_store
+            .add_token(
+                cancel_token.clone(),
+                PendingVerification {
+                    email: email.clone(),
+                    purpose: VerificationPurpose::CancelEmailChange {
+                        new_email: new_email.clone(),
+                    },
+                    expires_at,
+                },
+            )
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    state
+        .email_client
+        .send_email(
+            &new_email,
+            "Confirm your new email address",
+            &format!(
+                "Follow this link to confirm {} as the new email address for your account: {}/?confirmEmailChange={}",
+                new_email.as_ref(),
+                settings.auth_service_url,
+                confirm_token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_client
+        .send_email(
+            &email,
+            "Your email address is being changed",
+            &format!(
+                "A request was made to change the email address of your account to {}. If this wasn't you, follow this link to cancel it: {}/?cancelEmailChange={}",
+                new_email.as_ref(),
+                settings.auth_service_url,
+                cancel_token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EmailChangeResponse {
+        message: "Confirmation email sent".to_owned(),
+    });
+
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+// Moves the user to the new address in every store and invalidates
+// all tokens that were issued for the old address.
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    Json(request): Json<EmailChangeTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = VerificationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // The token store stays locked until every store has been re-keyed,
+    // so the same link can't be confirmed twice.
+    let mut verification_token_store = state.verification_token_store.write().await;
+
+    let verification = verification_token_store
+        .get_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let new_email = match verification.purpose {
+        VerificationPurpose::ConfirmEmailChange { new_email } => new_email,
+        _ => return Err(AuthAPIError::InvalidToken),
+    };
+    let email = verification.email;
+    actor.set(&email);
+
+    {
+        let mut user_store = state.user_store.write().await;
+        let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+        user_store
+            .change_email(&email, new_email.clone())
+            .await
+            .map_err(|e| match e {
+                UserStoreError::UserAlreadyExists => AuthAPIError::UserAlreadyExists,
+                UserStoreError::UserNotFound => AuthAPIError::InvalidToken,
+                _ => AuthAPIError::UnexpectedError,
+            })?;
+
+        if two_fa_code_store
+            .change_email(&email, new_email.clone())
+            .await
+            .is_err()
+        {
+            // Roll back so the user isn't left split across stores
+            let _ = user_store.change_email(&new_email, email.clone()).await;
+            return Err(AuthAPIError::UnexpectedError);
+        }
+    }
+
+    // This also drops the cancel token sent to the old address
+    verification_token_store
+        .remove_tokens_for(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_subject(email.as_ref().to_owned(), Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EmailChangeResponse {
+        message: "Email changed successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+pub async fn cancel_email_change(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    Json(request): Json<EmailChangeTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = VerificationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut verification_token_store = state.verification_token_store.write().await;
+
+    let verification = verification_token_store
+        .get_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if !matches!(verification.purpose, VerificationPurpose::CancelEmailChange { .. }) {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    actor.set(&verification.email);
+
+    verification_token_store
+        .remove_tokens_for(&verification.email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(EmailChangeResponse {
+        message: "Email change cancelled".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ChangeEmailRequest {
+    #[serde(rename = "newEmail")]
+    pub new_email: String,
+}
+
+#[derive(Deserialize)]
+pub struct EmailChangeTokenRequest {
+    pub token: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct EmailChangeResponse {
+    pub message: String,
+}
\ No newline at end of file
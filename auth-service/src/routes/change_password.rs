@@ -0,0 +1,279 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::login::check_password;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, FieldError, LoginLockout, Password, PasswordError, PasswordHash,
+        UserStoreError,
+    },
+    utils::{
+        audit::AuditActor,
+        auth::{authenticate_admin, authenticate_for_password_change, remove_auth_cookies},
+    },
+};
+
+// Changes the logged-in user's password. Every session ends, including this one, so the
+// user logs in again with the new password. Users who have to change their password before
+// they can log in do it with the token they were given at login instead.
+pub async fn change_password(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<ChangePasswordRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let settings = state.settings.current();
+
+    let authenticated = authenticate_for_password_change(
+        &jar,
+        &headers,
+        state.banned_token_store.clone(),
+        &settings,
+    )
+    .await;
+    let (token, claims) = match authenticated {
+        Ok(authenticated) => authenticated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let email = match Email::parse(claims.sub) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+    actor.set(&email);
+
+    let current_password = Password::parse(request.current_password);
+    let new_password = Password::parse_with_policy(
+        request.new_password,
+        Some(&email),
+        &settings.password_policy,
+    );
+    let (current_password, new_password) = match (current_password, new_password) {
+        (Ok(current_password), Ok(new_password)) => (current_password, new_password),
+        (current_password, new_password) => {
+            let mut errors: Vec<FieldError> = current_password
+                .err()
+                .map(|e| e.field_error("currentPassword"))
+                .into_iter()
+                .collect();
+            errors.extend(
+                new_password
+                    .err()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|e| e.field_error("newPassword")),
+            );
+            return (jar, Err(AuthAPIError::InvalidFields(errors)));
+        }
+    };
+
+    if let Err(e) = check_password(&state, &email, &current_password).await {
+        return (jar, Err(e));
+    }
+
+    if let Err(e) = replace_password(&state, &email, new_password).await {
+        return (jar, Err(e));
+    }
+
+    // The session this request came from is covered by `replace_password` too, but is banned
+    // explicitly in case it was issued in the same second
+    if state.banned_token_store.write().await.ban_token(token).await.is_err() {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let jar = remove_auth_cookies(jar, &settings);
+
+    let response = Json(PasswordChangeResponse {
+        message: "Password changed. Please log in again.".to_owned(),
+    });
+
+    (jar, Ok((StatusCode::OK, response)))
+}
+
+// Makes the user change their password at their next login, e.g. after an incident. Their
+// sessions end, so that is straight away.
+pub async fn admin_require_password_change(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    headers: HeaderMap,
+    Json(request): Json<AdminRequirePasswordChangeRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers, state.settings.current().admin_api_key.as_deref())?;
+
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    actor.set(&email);
+
+    {
+        let mut user_store = state.user_store.write().await;
+
+        let mut user = user_store.get_user(&email).await.map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+        user.must_change_password = true;
+
+        user_store
+            .update_user(user)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        user_store
+            .remove_sessions(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_subject(email.as_ref().to_owned(), Utc::now().timestamp())
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordChangeResponse {
+        message: "The user must change their password at their next login.".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+// How often `replace_password` checks the history again when another change got there first
+const REPLACE_PASSWORD_ATTEMPTS: usize = 3;
+
+// Stores the user's new password and ends all of their sessions. Whoever can set the password
+// has proven they own the account, so a login lockout is lifted too, and a password change
+// that was required is done. A password still in the user's history is rejected as "newPassword".
+pub(crate) async fn replace_password(
+    state: &AppState,
+    email: &Email,
+    password: Password,
+) -> Result<(), AuthAPIError> {
+    let history_size = state.settings.current().password_policy.history_size;
+
+    // The history is checked without holding the lock. If the password changed meanwhile, it's
+    // checked again, so the password that was replaced in between still joins the history.
+    for _ in 0..REPLACE_PASSWORD_ATTEMPTS {
+        if try_replace_password(state, email, &password, history_size).await? {
+            return state
+                .banned_token_store
+                .write()
+                .await
+                .ban_subject(email.as_ref().to_owned(), Utc::now().timestamp())
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError);
+        }
+    }
+
+    Err(AuthAPIError::UnexpectedError)
+}
+
+// Returns whether the password was replaced, which it isn't when it changed during the check
+async fn try_replace_password(
+    state: &AppState,
+    email: &Email,
+    password: &Password,
+    history_size: usize,
+) -> Result<bool, AuthAPIError> {
+    let (current_password, password_history) = {
+        let user_store = state.user_store.read().await;
+        let user = user_store.get_user(email).await.map_err(|e| match e {
+            UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+            _ => AuthAPIError::UnexpectedError,
+        })?;
+        let password_history = user_store
+            .get_password_history(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        (user.password, password_history)
+    };
+    let replaced_hash = check_password_history(
+        current_password.clone(),
+        password.clone(),
+        password_history,
+        history_size,
+    )
+    .await?;
+
+    let mut user_store = state.user_store.write().await;
+
+    let mut user = user_store.get_user(email).await.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        _ => AuthAPIError::UnexpectedError,
+    })?;
+    if user.password != current_password {
+        return Ok(false);
+    }
+    user.password = password.clone();
+    user.password_changed_at = Utc::now().timestamp();
+    user.must_change_password = false;
+    user.lockout = LoginLockout::default();
+
+    user_store
+        .update_user(user)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    if let Some(replaced_hash) = replaced_hash {
+        user_store
+            .add_password_history(email, replaced_hash, history_size - 1)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+    user_store
+        .remove_sessions(email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok(true)
+}
+
+// Rejects `password` if it's the current one or among the `history_size - 1` before it. Returns
+// the hash of the current password for the history, unless the history is just the current one.
+// Hashes are slow to check on purpose, so that's done away from the async runtime and the store.
+async fn check_password_history(
+    current_password: Password,
+    password: Password,
+    password_history: Vec<PasswordHash>,
+    history_size: usize,
+) -> Result<Option<PasswordHash>, AuthAPIError> {
+    if history_size == 0 {
+        return Ok(None);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let previous = &password_history[password_history.len().saturating_sub(history_size - 1)..];
+        if current_password == password || previous.iter().any(|hash| hash.matches(&password)) {
+            let error = PasswordError::Reused { history_size }.field_error("newPassword");
+            return Err(AuthAPIError::InvalidFields(vec![error]));
+        }
+        Ok((history_size > 1).then(|| PasswordHash::new(&current_password)))
+    })
+    .await
+    .map_err(|_| AuthAPIError::UnexpectedError)?
+}
+
+#[derive(Deserialize)]
+pub struct ChangePasswordRequest {
+    #[serde(rename = "currentPassword")]
+    pub current_password: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminRequirePasswordChangeRequest {
+    pub email: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PasswordChangeResponse {
+    pub message: String,
+}
@@ -0,0 +1,98 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::login::check_password;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password},
+    utils::{audit::AuditActor, auth::{authenticate_with_csrf, remove_auth_cookies}},
+};
+
+// Schedules the logged-in user's account for deletion and logs them out everywhere.
+// The account is only purged once the grace period ends; logging in before then restores it.
+pub async fn delete_account(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<DeleteAccountRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let settings = state.settings.current();
+
+    let authenticated =
+        authenticate_with_csrf(&jar, &headers, state.banned_token_store.clone(), &settings).await;
+    let (token, claims) = match authenticated {
+        Ok(authenticated) => authenticated,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let email = match Email::parse(claims.sub) {
+        Ok(email) => email,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+    actor.set(&email);
+
+    let password = match Password::parse(request.password) {
+        Ok(password) => password,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+    };
+
+    let now = Utc::now().timestamp();
+    let purge_at = now + settings.account_deletion_grace_period_seconds;
+
+    if let Err(e) = check_password(&state, &email, &password).await {
+        return (jar, Err(e));
+    }
+
+    {
+        let mut user_store = state.user_store.write().await;
+
+        if user_store.schedule_deletion(&email, purge_at).await.is_err()
+            || user_store.remove_sessions(&email).await.is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    {
+        let mut banned_token_store = state.banned_token_store.write().await;
+
+        // End every session, including this one
+        if banned_token_store.ban_token(token).await.is_err()
+            || banned_token_store
+                .ban_subject(email.as_ref().to_owned(), now)
+                .await
+                .is_err()
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+    }
+
+    let jar = remove_auth_cookies(jar, &settings);
+
+    let response = Json(DeleteAccountResponse {
+        message: "Account scheduled for deletion".to_owned(),
+        purge_at,
+    });
+
+    (jar, Ok((StatusCode::ACCEPTED, response)))
+}
+
+#[derive(Deserialize)]
+pub struct DeleteAccountRequest {
+    pub password: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeleteAccountResponse {
+    pub message: String,
+    #[serde(rename = "purgeAt")]
+    pub purge_at: i64,
+}
\ No newline at end of file
@@ -0,0 +1,201 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginOutcome, VerificationPurpose},
+    utils::{audit::AuditActor, auth::authenticate},
+};
+
+// Returns everything auth-service holds about the logged-in user, for data-subject access requests.
+// Secrets (password, pending 2FA code, verification tokens) are never included.
+pub async fn export_account(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, claims) = authenticate(&jar, state.banned_token_store.clone(), &state.settings.current()).await?;
+    let email = Email::parse(claims.sub.clone()).map_err(|_| AuthAPIError::InvalidToken)?;
+    actor.set(&email);
+
+    let (user, sessions, login_history, consents) = {
+        let user_store = state.user_store.read().await;
+
+        let user = user_store
+            .get_user(&email)
+            .await
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+        let sessions = user_store
+            .get_sessions(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        let login_history = user_store
+            .get_login_history(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+        let consents = user_store
+            .get_consents(&email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        (user, sessions, login_history, consents)
+    };
+
+    let pending_challenge = state
+        .two_fa_code_store
+        .read()
+        .await
+        .has_code(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let pending_verifications = state
+        .verification_token_store
+        .read()
+        .await
+        .get_pending_for(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let export = AccountExport {
+        exported_at: Utc::now().timestamp(),
+        profile: ProfileExport {
+            email: user.email.as_ref().to_owned(),
+            deletion_scheduled_for: user.deletion_scheduled_for,
+            failed_login_attempts: user.lockout.failed_attempts,
+            locked_until: user.lockout.locked_until,
+            password_changed_at: user.password_changed_at,
+            must_change_password: user.must_change_password,
+        },
+        two_factor_auth: TwoFactorAuthExport {
+            enabled: user.requires_2fa,
+            pending_challenge,
+        },
+        sessions: sessions
+            .into_iter()
+            .map(|session| SessionExport {
+                current: session.id == claims.jti,
+                id: session.id,
+                created_at: session.created_at,
+                expires_at: session.expires_at,
+            })
+            .collect(),
+        login_history: login_history
+            .into_iter()
+            .map(|record| LoginRecordExport {
+                at: record.at,
+                outcome: match record.outcome {
+                    LoginOutcome::Success => "success",
+                    LoginOutcome::TwoFactorRequired => "2fa_required",
+                    LoginOutcome::PasswordChangeRequired => "password_change_required",
+                    LoginOutcome::IncorrectCredentials => "incorrect_credentials",
+                }
+                .to_owned(),
+            })
+            .collect(),
+        consents: consents
+            .into_iter()
+            .map(|consent| ConsentExport {
+                purpose: consent.purpose,
+                granted_at: consent.granted_at,
+            })
+            .collect(),
+        pending_verifications: pending_verifications
+            .into_iter()
+            .map(|verification| {
+                let (purpose, new_email) = match verification.purpose {
+                    VerificationPurpose::ConfirmEmailChange { new_email } => {
+                        ("confirm_email_change", Some(new_email))
+                    }
+                    VerificationPurpose::CancelEmailChange { new_email } => {
+                        ("cancel_email_change", Some(new_email))
+                    }
+                    VerificationPurpose::UnlockAccount => ("unlock_account", None),
+                    VerificationPurpose::ResetPassword => ("reset_password", None),
+                };
+                PendingVerificationExport {
+                    purpose: purpose.to_owned(),
+                    new_email: new_email.map(|email| email.as_ref().to_owned()),
+                    expires_at: verification.expires_at,
+                }
+            })
+            .collect(),
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"account-export.json\"",
+        )],
+        Json(export),
+    ))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountExport {
+    pub exported_at: i64,
+    pub profile: ProfileExport,
+    pub two_factor_auth: TwoFactorAuthExport,
+    pub sessions: Vec<SessionExport>,
+    pub login_history: Vec<LoginRecordExport>,
+    pub consents: Vec<ConsentExport>,
+    pub pending_verifications: Vec<PendingVerificationExport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileExport {
+    pub email: String,
+    pub deletion_scheduled_for: Option<i64>,
+    pub failed_login_attempts: u32,
+    pub locked_until: Option<i64>,
+    pub password_changed_at: i64,
+    pub must_change_password: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TwoFactorAuthExport {
+    pub enabled: bool,
+    pub pending_challenge: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionExport {
+    pub id: String,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRecordExport {
+    pub at: i64,
+    pub outcome: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsentExport {
+    pub purpose: String,
+    pub granted_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingVerificationExport {
+    pub purpose: String,
+    pub new_email: Option<String>,
+    pub expires_at: i64,
+}
\ No newline at end of file
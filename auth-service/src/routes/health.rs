@@ -0,0 +1,95 @@
+use std::{future::Future, time::Duration};
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{app_state::AppState, utils::constants::HEALTH_CHECK_TIMEOUT_MILLISECONDS};
+
+// Answers as long as the process is serving requests at all
+pub async fn health_live() -> impl IntoResponse {
+    Json(LivenessResponse {
+        status: HealthStatus::Ok,
+    })
+}
+
+// Checks every component a request may need, answering 503 unless all of them are healthy
+pub async fn health_ready(State(state): State<AppState>) -> impl IntoResponse {
+    let components = ComponentsHealth {
+        user_store: check(async { state.user_store.read().await.health_check().await.is_ok() })
+            .await,
+        banned_token_store: check(async {
+            state
+                .banned_token_store
+                .read()
+                .await
+                .health_check()
+                .await
+                .is_ok()
+        })
+        .await,
+        two_fa_code_store: check(async {
+            state
+                .two_fa_code_store
+                .read()
+                .await
+                .health_check()
+                .await
+                .is_ok()
+        })
+        .await,
+        email_client: check(async { state.email_client.health_check().await.is_ok() }).await,
+    };
+
+    let healthy = [
+        components.user_store,
+        components.banned_token_store,
+        components.two_fa_code_store,
+        components.email_client,
+    ]
+    .iter()
+    .all(|status| *status == HealthStatus::Ok);
+
+    let (status_code, status) = match healthy {
+        true => (StatusCode::OK, HealthStatus::Ok),
+        false => (StatusCode::SERVICE_UNAVAILABLE, HealthStatus::Unavailable),
+    };
+
+    (status_code, Json(ReadinessResponse { status, components }))
+}
+
+// A component that doesn't answer in time, e.g. because its lock is held, counts as unavailable
+async fn check(healthy: impl Future<Output = bool>) -> HealthStatus {
+    let timeout = Duration::from_millis(HEALTH_CHECK_TIMEOUT_MILLISECONDS);
+
+    match tokio::time::timeout(timeout, healthy).await {
+        Ok(true) => HealthStatus::Ok,
+        _ => HealthStatus::Unavailable,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Ok,
+    Unavailable,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LivenessResponse {
+    pub status: HealthStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    pub status: HealthStatus,
+    pub components: ComponentsHealth,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentsHealth {
+    pub user_store: HealthStatus,
+    pub banned_token_store: HealthStatus,
+    pub two_fa_code_store: HealthStatus,
+    pub email_client: HealthStatus,
+}
@@ -1,46 +1,273 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, Email, FieldError, LoginAttemptId, LoginLockout, LoginOutcome, LoginRecord, Password,
+        PasswordChangeReason, PendingVerification, TwoFACode, TwoFAPurpose, UserStoreError, VerificationPurpose,
+        VerificationToken,
+    },
+    utils::{
+        audit::AuditActor,
+        auth::{generate_auth_cookie, generate_csrf_cookie, generate_password_change_cookie},
+        metrics::record_login,
+        constants::{
+            ACCOUNT_UNLOCK_TOKEN_TTL_SECONDS, LOGIN_LOCKOUT_BASE_SECONDS,
+            LOGIN_LOCKOUT_MAX_SECONDS, LOGIN_LOCKOUT_THRESHOLD,
+        },
+    },
 };
 
 pub async fn login(
     State(state): State<AppState>,
+    actor: AuditActor,
     jar: CookieJar,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
-    let password = match Password::parse(request.password) {
-        Ok(password) => password,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
-    };
+    let (jar, result) = attempt_login(&state, &actor, jar, request).await;
+    record_login(&result);
+    (jar, result)
+}
 
-    let email = match Email::parse(request.email) {
-        Ok(email) => email,
-        Err(_) => return (jar, Err(AuthAPIError::InvalidCredentials)),
+async fn attempt_login(
+    state: &AppState,
+    actor: &AuditActor,
+    jar: CookieJar,
+    request: LoginRequest,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
+    // Field errors only describe what was sent, before any account is looked up, so they can't
+    // tell whether one exists
+    let (email, password) = match (Email::parse(request.email), Password::parse(request.password)) {
+        (Ok(email), Ok(password)) => (email, password),
+        (email, password) => {
+            let errors = [email.err().map(FieldError::from), password.err().map(FieldError::from)];
+            let errors = errors.into_iter().flatten().collect();
+            return (jar, Err(AuthAPIError::InvalidFields(errors)));
+        }
     };
+    actor.set(&email);
 
-    let user_store = &state.user_store.read().await;
+    let now = Utc::now().timestamp();
 
-    if user_store.validate_user(&email, &password).await.is_err() {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    // Locked accounts are refused before the password is even checked
+    if let Ok(user) = state.user_store.read().await.get_user(&email).await {
+        if let Some(retry_after_seconds) = user.lockout.remaining_seconds(now) {
+            return (jar, Err(AuthAPIError::AccountLocked { retry_after_seconds }));
+        }
     }
 
-    let user = match user_store.get_user(&email).await {
-        Ok(user) => user,
+    let validation = state.user_store.read().await.validate_user(&email, &password).await;
+
+    match validation {
+        Ok(()) => {}
+        Err(UserStoreError::InvalidCredentials) => {
+            let e = handle_incorrect_credentials(&email, state, now).await;
+            return (jar, Err(e));
+        }
         Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    }
+
+    let settings = state.settings.current();
+    let (user, password_change_reason) = {
+        let mut user_store = state.user_store.write().await;
+
+        let user = match user_store.get_user(&email).await {
+            Ok(user) => user,
+            Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        };
+
+        let password_change_reason = user.password_change_reason(&settings.password_policy, now);
+
+        let record = LoginRecord {
+            at: now,
+            outcome: match (user.requires_2fa, password_change_reason) {
+                (true, _) => LoginOutcome::TwoFactorRequired,
+                (false, Some(_)) => LoginOutcome::PasswordChangeRequired,
+                (false, None) => LoginOutcome::Success,
+            },
+        };
+        if user_store.record_login(&user.email, record).await.is_err() {
+            return (jar, Err(AuthAPIError::UnexpectedError));
+        }
+
+        (user, password_change_reason)
     };
 
+    // 2FA comes first, so the password alone can't be used to change it. The reason is kept
+    // with the 2FA code, for `verify_2fa` to complete the login with.
     match user.requires_2fa {
-        true => handle_2fa(&user.email, &state, jar).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(&user.email, password_change_reason, state, jar).await,
+        false => complete_login(&user.email, password_change_reason, state, jar).await,
+    }
+}
+
+// Starts a session, or if the password has to be changed first, only lets the user do that
+pub(crate) async fn complete_login(
+    email: &Email,
+    password_change_reason: Option<PasswordChangeReason>,
+    state: &AppState,
+    jar: CookieJar,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
+    if let Err(e) = restore_account(email, state).await {
+        return (jar, Err(e));
+    }
+
+    match password_change_reason {
+        Some(reason) => handle_password_change(email, reason, state, jar),
+        None => start_session(email, state, jar).await,
+    }
+}
+
+// A successful login resets the count of failed ones, and logging in during the deletion grace
+// period restores the account. With 2FA, the password alone does neither.
+async fn restore_account(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    let mut user = user_store
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if user.lockout != LoginLockout::default() {
+        user.lockout = LoginLockout::default();
+        user_store
+            .update_user(user.clone())
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    if user.deletion_scheduled_for.is_some() {
+        user_store
+            .cancel_deletion(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(())
+}
+
+// Checks a password re-entered to confirm a change to the account. Wrong ones count towards the
+// same lockout as failed logins, so these checks can't be used to guess the password either.
+pub(crate) async fn check_password(
+    state: &AppState,
+    email: &Email,
+    password: &Password,
+) -> Result<(), AuthAPIError> {
+    let now = Utc::now().timestamp();
+
+    let validation = {
+        let user_store = state.user_store.read().await;
+
+        if let Ok(user) = user_store.get_user(email).await {
+            if let Some(retry_after_seconds) = user.lockout.remaining_seconds(now) {
+                return Err(AuthAPIError::AccountLocked { retry_after_seconds });
+            }
+        }
+
+        user_store.validate_user(email, password).await
+    };
+
+    match validation {
+        Ok(()) => Ok(()),
+        Err(UserStoreError::InvalidCredentials) => Err(record_password_failure(email, state, now).await),
+        Err(_) => Err(AuthAPIError::IncorrectCredentials),
     }
 }
 
+// Records the failed login and locks the account once there have been too many in a row.
+// Only accounts that exist have a login history and lockout to record the failure in.
+async fn handle_incorrect_credentials(email: &Email, state: &AppState, now: i64) -> AuthAPIError {
+    let record = LoginRecord {
+        at: now,
+        outcome: LoginOutcome::IncorrectCredentials,
+    };
+    let _ = state.user_store.write().await.record_login(email, record).await;
+
+    record_password_failure(email, state, now).await
+}
+
+async fn record_password_failure(email: &Email, state: &AppState, now: i64) -> AuthAPIError {
+    let (locked_for, first_lockout) = {
+        let mut user_store = state.user_store.write().await;
+
+        let mut user = match user_store.get_user(email).await {
+            Ok(user) => user,
+            Err(_) => return AuthAPIError::IncorrectCredentials,
+        };
+        let locked_for = user.lockout.record_failure(
+            now,
+            LOGIN_LOCKOUT_THRESHOLD,
+            LOGIN_LOCKOUT_BASE_SECONDS,
+            LOGIN_LOCKOUT_MAX_SECONDS,
+        );
+        let first_lockout = user.lockout.failed_attempts == LOGIN_LOCKOUT_THRESHOLD;
+        if user_store.update_user(user).await.is_err() {
+            return AuthAPIError::UnexpectedError;
+        }
+
+        (locked_for, first_lockout)
+    };
+
+    match locked_for {
+        Some(seconds) => {
+            // Only the first lockout in a row sends a link. It stays valid for as long as the
+            // longest lockout, so further failures neither flood the inbox nor pile up tokens.
+            // The account is locked either way, so a failure to send the link isn't reported.
+            if first_lockout {
+                let _ = send_unlock_link(email, state, now).await;
+            }
+            AuthAPIError::AccountLocked {
+                retry_after_seconds: seconds as u64,
+            }
+        }
+        None => AuthAPIError::IncorrectCredentials,
+    }
+}
+
+async fn send_unlock_link(email: &Email, state: &AppState, now: i64) -> Result<(), AuthAPIError> {
+    let token = VerificationToken::default();
+
+    state
+        .verification_token_store
+        .write()
+        .await
+        .add_token(
+            token.clone(),
+            PendingVerification {
+                email: email.clone(),
+                purpose: VerificationPurpose::UnlockAccount,
+                expires_at: now + ACCOUNT_UNLOCK_TOKEN_TTL_SECONDS,
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Your account has been locked",
+            &format!(
+                "Your account was locked after too many failed login attempts. If this was you, follow this link to unlock it: {}/?unlockAccount={}",
+                state.settings.current().auth_service_url,
+                token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
 async fn handle_2fa(
     email: &Email,
+    password_change_reason: Option<PasswordChangeReason>,
     state: &AppState,
     jar: CookieJar,
 ) -> (
@@ -56,15 +283,34 @@ async fn handle_2fa(
         .two_fa_code_store
         .write()
         .await
-        .add_code(email.clone(), login_attempt_id.clone(), two_fa_code.clone())
+        .add_code(
+            email.clone(),
+            TwoFAPurpose::Login,
+            login_attempt_id.clone(),
+            two_fa_code.clone(),
+            password_change_reason,
+        )
         .await
         .is_err()
     {
         return (jar, Err(AuthAPIError::UnexpectedError));
     }
 
-    // Return a TwoFactorAuthResponse. The message should be "2FA required".
-    // The login attempt ID should be "123456". We will replace this hard-coded login attempt ID soon!
+    // The login is completed by sending the code back to `verify_2fa`
+    if state
+        .email_client
+        .send_email(
+            email,
+            "Your 2FA code",
+            &format!("Your 2FA code is {}", two_fa_code.as_ref()),
+        )
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    // The login attempt ID is sent back with the code, which only went out by email
     let response = LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_string(),
         login_attempt_id: login_attempt_id.as_ref().to_owned(),
@@ -73,8 +319,9 @@ async fn handle_2fa(
     (jar, Ok((StatusCode::PARTIAL_CONTENT, Json(response))))
 }
 
-async fn handle_no_2fa(
+async fn start_session(
     email: &Email,
+    state: &AppState,
     jar: CookieJar,
 ) -> (
     CookieJar,
@@ -82,18 +329,63 @@ async fn handle_no_2fa(
 ) {
     // Call the generate_auth_cookie function defined in the auth module.
     // If the function call fails return AuthAPIError::UnexpectedError.
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
+    let (auth_cookie, claims) = match generate_auth_cookie(email, &state.settings.current()) {
+        Ok(generated) => generated,
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    if state
+        .user_store
+        .write()
+        .await
+        .add_session(email, claims.session())
+        .await
+        .is_err()
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError));
+    }
+
+    let csrf_cookie = generate_csrf_cookie(&claims, &state.settings.current());
+    let updated_jar = jar.add(auth_cookie).add(csrf_cookie);
 
     let response = LoginResponse::RegularAuth;
 
     (updated_jar, Ok((StatusCode::OK, Json(response))))
 }
 
+// Issues a short-lived token that can only change the password, rather than starting a session
+fn handle_password_change(
+    email: &Email,
+    reason: PasswordChangeReason,
+    state: &AppState,
+    jar: CookieJar,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
+    let settings = state.settings.current();
+
+    let (auth_cookie, claims) = match generate_password_change_cookie(email, &settings) {
+        Ok(generated) => generated,
+        Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
+    };
+
+    // Changing the password is a state-changing request, so it needs the CSRF cookie as well
+    let csrf_cookie = generate_csrf_cookie(&claims, &settings);
+    let updated_jar = jar.add(auth_cookie).add(csrf_cookie);
+
+    let response = LoginResponse::PasswordChangeRequired(PasswordChangeRequiredResponse {
+        message: "Password change required".to_owned(),
+        reason: match reason {
+            PasswordChangeReason::Required => "required",
+            PasswordChangeReason::Expired => "expired",
+        }
+        .to_owned(),
+    });
+
+    (updated_jar, Ok((StatusCode::ACCEPTED, Json(response))))
+}
+
 #[derive(Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -105,6 +397,7 @@ pub struct LoginRequest {
 pub enum LoginResponse {
     RegularAuth,
     TwoFactorAuth(TwoFactorAuthResponse),
+    PasswordChangeRequired(PasswordChangeRequiredResponse),
 }
 
 // If a user requires 2FA, this JSON body should be returned!
@@ -113,4 +406,12 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
-}
\ No newline at end of file
+}
+
+// Returned with 202 Accepted when the password has to be changed before the user can log in
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PasswordChangeRequiredResponse {
+    pub message: String,
+    // "required" by an administrator, or "expired" under `password_policy.max_age_days`
+    pub reason: String,
+}
@@ -1,17 +1,27 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use axum_extra::extract::{cookie, CookieJar};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use axum_extra::extract::CookieJar;
 
 use crate::{
-    app_state::AppState, domain::AuthAPIError, utils::{auth::validate_token, constants::JWT_COOKIE_NAME}
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+    utils::{audit::AuditActor, auth::{remove_auth_cookies, validate_token_of_any_scope, verify_csrf_token}},
 };
 
 pub async fn logout(
     State(state): State<AppState>,
-    jar: CookieJar
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let settings = state.settings.current();
+
     // Retrieve JWT cookie from the `CookieJar`
     // Return AuthAPIError::MissingToken is the cookie is not found
-    let cookie = match jar.get(JWT_COOKIE_NAME) {
+    let cookie = match jar.get(&settings.auth_cookie_name()) {
         Some(cookie) => cookie,
         None => return (jar, Err(AuthAPIError::MissingToken)),
     };
@@ -21,17 +31,32 @@ pub async fn logout(
     // Validate JWT token by calling `validate_token` from the auth service.
     // If the token is valid you can ignore the returned claims for now.
     // Return AuthAPIError::InvalidToken is validation fails.
-    match validate_token(&token, state.banned_token_store.clone()).await {
-        Ok(_claims) => {},
+    // Including the token a user who has to change their password gets, which they may not want to
+    let claims = match validate_token_of_any_scope(&token, state.banned_token_store.clone(), &settings).await {
+        Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
 
+    if let Ok(email) = Email::parse(claims.sub.clone()) {
+        actor.set(&email);
+    }
+
+    // Otherwise any site could log the user out
+    if let Err(e) = verify_csrf_token(&jar, &headers, &claims, &settings) {
+        return (jar, Err(e));
+    }
+
     match state.banned_token_store.write().await.ban_token(token.to_owned()).await {
         Ok(_) => {},
         Err(_) => return (jar, Err(AuthAPIError::UnexpectedError)),
     };
 
-    let jar = jar.remove(cookie::Cookie::from(JWT_COOKIE_NAME));
+    // The session may already be gone if the account was deleted in the meantime
+    if let Ok(email) = Email::parse(claims.sub.clone()) {
+        let _ = state.user_store.write().await.remove_session(&email, &claims.jti).await;
+    }
+
+    let jar = remove_auth_cookies(jar, &settings);
 
     (jar, Ok(StatusCode::OK))
 }
\ No newline at end of file
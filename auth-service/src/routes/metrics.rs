@@ -0,0 +1,11 @@
+use axum::{http::header, response::IntoResponse};
+
+use crate::utils::metrics::prometheus_handle;
+
+// Serves every recorded metric in the Prometheus text exposition format
+pub async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        prometheus_handle().render(),
+    )
+}
@@ -1,12 +1,34 @@
+mod audit_events;
+mod change_email;
+mod change_password;
+mod delete_account;
+mod export_account;
+mod health;
 mod login;
 mod logout;
+mod metrics;
+mod password_strength;
+mod reset_password;
 mod signup;
+mod two_fa_settings;
+mod unlock_account;
 mod verify_2fa;
 mod verify_token;
 
 // re-export items from sub-modules
+pub use audit_events::*;
+pub use change_email::*;
+pub use change_password::*;
+pub use delete_account::*;
+pub use export_account::*;
+pub use health::*;
 pub use login::*;
 pub use logout::*;
+pub use metrics::*;
+pub use password_strength::*;
+pub use reset_password::*;
 pub use signup::*;
+pub use two_fa_settings::*;
+pub use unlock_account::*;
 pub use verify_2fa::*;
 pub use verify_token::*;
\ No newline at end of file
@@ -0,0 +1,52 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, FieldError},
+};
+
+// Scores a password without setting it, e.g. for a strength meter next to a password field.
+// `acceptable` tells whether the policy's `min_strength_score` would let it through.
+pub async fn password_strength(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordStrengthRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = request
+        .email
+        .map(Email::parse)
+        .transpose()
+        .map_err(|e| AuthAPIError::InvalidFields(vec![FieldError::from(e)]))?;
+
+    let policy = &state.settings.current().password_policy;
+    let strength = policy.strength(&request.password, email.as_ref());
+
+    let response = Json(PasswordStrengthResponse {
+        acceptable: strength.score >= policy.min_strength_score,
+        score: strength.score,
+        min_score: policy.min_strength_score,
+        guesses_log10: strength.guesses_log10,
+        warning: strength.warning.map(str::to_owned),
+        suggestions: strength.suggestions.iter().map(|s| s.to_string()).collect(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordStrengthRequest {
+    pub password: String,
+    // Parts of it count as easy guesses
+    pub email: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordStrengthResponse {
+    pub score: u8,
+    pub min_score: u8,
+    pub acceptable: bool,
+    pub guesses_log10: f64,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
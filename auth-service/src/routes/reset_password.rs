@@ -0,0 +1,151 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::change_password::replace_password;
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, FieldError, Password, PendingVerification, VerificationPurpose,
+        VerificationToken,
+    },
+    utils::{audit::AuditActor, constants::PASSWORD_RESET_TOKEN_TTL_SECONDS},
+};
+
+// Emails a link to reset the password with. The response is the same whether or not there is
+// an account for the email, so it can't be used to find out which emails have one.
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    Json(request): Json<ForgotPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email)
+        .map_err(|e| AuthAPIError::InvalidFields(vec![FieldError::from(e)]))?;
+    actor.set(&email);
+
+    let exists = state.user_store.read().await.get_user(&email).await.is_ok();
+    if exists {
+        // Failing to send the link isn't reported either, as that would only happen for
+        // emails with an account
+        let _ = send_reset_link(&email, &state).await;
+    }
+
+    let response = Json(PasswordResetResponse {
+        message: "If there is an account for this email, a password reset link has been sent"
+            .to_owned(),
+    });
+
+    Ok((StatusCode::ACCEPTED, response))
+}
+
+async fn send_reset_link(email: &Email, state: &AppState) -> Result<(), AuthAPIError> {
+    let token = VerificationToken::default();
+
+    state
+        .verification_token_store
+        .write()
+        .await
+        .add_token(
+            token.clone(),
+            PendingVerification {
+                email: email.clone(),
+                purpose: VerificationPurpose::ResetPassword,
+                expires_at: Utc::now().timestamp() + PASSWORD_RESET_TOKEN_TTL_SECONDS,
+            },
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Reset your password",
+            &format!(
+                "A password reset was requested for your account. If this was you, follow this link to choose a new password: {}/?resetPassword={}",
+                state.settings.current().auth_service_url,
+                token.as_ref()
+            ),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+// Sets a new password using the link emailed by `forgot_password`. Every session ends.
+pub async fn reset_password(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = VerificationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let verification = state
+        .verification_token_store
+        .read()
+        .await
+        .get_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if verification.purpose != VerificationPurpose::ResetPassword {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    actor.set(&verification.email);
+
+    let password = Password::parse_with_policy(
+        request.new_password,
+        Some(&verification.email),
+        &state.settings.current().password_policy,
+    )
+    .map_err(|violations| {
+        let errors = violations.iter().map(|e| e.field_error("newPassword")).collect();
+        AuthAPIError::InvalidFields(errors)
+    })?;
+
+    // The link is used up before the password history is checked, which is slow, so the store
+    // isn't locked meanwhile and the same link can't reset the password twice at once
+    state
+        .verification_token_store
+        .write()
+        .await
+        .remove_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if let Err(e) = replace_password(&state, &verification.email, password).await {
+        if matches!(e, AuthAPIError::UserNotFound) {
+            return Err(AuthAPIError::InvalidToken);
+        }
+        // The link keeps working, e.g. to try a password that wasn't used before
+        let _ = state
+            .verification_token_store
+            .write()
+            .await
+            .add_token(token, verification)
+            .await;
+        return Err(e);
+    }
+
+    let response = Json(PasswordResetResponse {
+        message: "Password reset. You can log in with the new password.".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordRequest {
+    pub email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[serde(rename = "newPassword")]
+    pub new_password: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
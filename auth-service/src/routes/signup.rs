@@ -1,18 +1,36 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, User, email::Email, password::Password},
+    domain::{AuthAPIError, ConsentRecord, FieldError, User, email::Email, password::Password},
+    utils::audit::AuditActor,
 };
 
 pub async fn signup(
     state: State<AppState>,
+    actor: AuditActor,
     Json(request): Json<SignupRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    let email = 
-        Email::parse(request.email.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
-    let password = 
-        Password::parse(request.password.clone()).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let email = Email::parse(request.email.clone());
+    if let Ok(email) = &email {
+        actor.set(email);
+    }
+    let password = Password::parse_with_policy(
+        request.password.clone(),
+        email.as_ref().ok(),
+        &state.settings.current().password_policy,
+    );
+
+    // Every invalid field, and every password rule broken, is reported at once
+    let (email, password) = match (email, password) {
+        (Ok(email), Ok(password)) => (email, password),
+        (email, password) => {
+            let mut errors: Vec<FieldError> = email.err().map(FieldError::from).into_iter().collect();
+            errors.extend(password.err().unwrap_or_default().into_iter().map(FieldError::from));
+            return Err(AuthAPIError::InvalidFields(errors));
+        }
+    };
 
     let user = User::new(email, password, request.requires_2fa);
 
@@ -23,9 +41,19 @@ pub async fn signup(
         return Err(AuthAPIError::UserAlreadyExists);
     }
 
+    let email = user.email.clone();
+
     // Add the user to the user store and handle any unexpected errors.
     user_store.add_user(user).await.map_err(|_| AuthAPIError::UnexpectedError)?;
 
+    let granted_at = Utc::now().timestamp();
+    for purpose in request.consents {
+        user_store
+            .add_consent(&email, ConsentRecord { purpose, granted_at })
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
     });
@@ -39,6 +67,9 @@ pub struct SignupRequest {
     pub password: String,
     #[serde(rename = "requires2FA")]
     pub requires_2fa: bool,
+    // What the user agreed to when signing up, e.g. "terms_of_service"
+    #[serde(default)]
+    pub consents: Vec<String>,
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,216 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use serde::{Deserialize, Serialize};
+
+use super::login::check_password;
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode, TwoFAPurpose},
+    routes::TwoFactorAuthResponse,
+    utils::{audit::AuditActor, auth::authenticate_with_csrf, metrics::record_2fa_verification},
+};
+
+// Sends a 2FA code to the logged-in user. 2FA is only turned on once
+// the code comes back through `confirm_enable_2fa`.
+pub async fn enable_2fa(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&state, &actor, &jar, &headers).await?;
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if user.requires_2fa {
+        return Err(AuthAPIError::TwoFAUnchanged);
+    }
+
+    send_2fa_challenge(&state, &email, TwoFAPurpose::Enable).await
+}
+
+pub async fn confirm_enable_2fa(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<Confirm2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&state, &actor, &jar, &headers).await?;
+    let verification = verify_2fa_challenge(&state, &email, TwoFAPurpose::Enable, request).await;
+    record_2fa_verification("enable", &verification);
+    verification?;
+    set_requires_2fa(&state, &email, true).await?;
+
+    Ok(StatusCode::OK)
+}
+
+// Sends a 2FA code to the logged-in user once they have re-entered their password.
+// 2FA is only turned off once the code comes back through `confirm_disable_2fa`.
+pub async fn disable_2fa(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<Disable2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&state, &actor, &jar, &headers).await?;
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_password(&state, &email, &password).await?;
+
+    let user = state
+        .user_store
+        .read()
+        .await
+        .get_user(&email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if !user.requires_2fa {
+        return Err(AuthAPIError::TwoFAUnchanged);
+    }
+
+    send_2fa_challenge(&state, &email, TwoFAPurpose::Disable).await
+}
+
+pub async fn confirm_disable_2fa(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    headers: HeaderMap,
+    Json(request): Json<Confirm2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = authenticated_email(&state, &actor, &jar, &headers).await?;
+    let verification = verify_2fa_challenge(&state, &email, TwoFAPurpose::Disable, request).await;
+    record_2fa_verification("disable", &verification);
+    verification?;
+    set_requires_2fa(&state, &email, false).await?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn authenticated_email(
+    state: &AppState,
+    actor: &AuditActor,
+    jar: &CookieJar,
+    headers: &HeaderMap,
+) -> Result<Email, AuthAPIError> {
+    let (_, claims) = authenticate_with_csrf(
+        jar,
+        headers,
+        state.banned_token_store.clone(),
+        &state.settings.current(),
+    )
+    .await?;
+    let email = Email::parse(claims.sub).map_err(|_| AuthAPIError::InvalidToken)?;
+    actor.set(&email);
+    Ok(email)
+}
+
+async fn send_2fa_challenge(
+    state: &AppState,
+    email: &Email,
+    purpose: TwoFAPurpose,
+) -> Result<(StatusCode, Json<TwoFactorAuthResponse>), AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::default();
+    let two_fa_code = TwoFACode::default();
+
+    state
+        .two_fa_code_store
+        .write()
+        .await
+        .add_code(email.clone(), purpose, login_attempt_id.clone(), two_fa_code.clone(), None)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Your 2FA code",
+            &format!("Your 2FA code is {}", two_fa_code.as_ref()),
+        )
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    let response = TwoFactorAuthResponse {
+        message: "2FA code sent".to_owned(),
+        login_attempt_id: login_attempt_id.as_ref().to_owned(),
+    };
+
+    Ok((StatusCode::ACCEPTED, Json(response)))
+}
+
+// The login attempt ID is only ever handed to whoever started the challenge,
+// so a matching ID and code proves both that and access to the mailbox.
+async fn verify_2fa_challenge(
+    state: &AppState,
+    email: &Email,
+    purpose: TwoFAPurpose,
+    request: Confirm2FARequest,
+) -> Result<(), AuthAPIError> {
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    let (expected_id, expected_code) = two_fa_code_store
+        .get_code(email, purpose)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if expected_id != login_attempt_id || expected_code != two_fa_code {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    two_fa_code_store
+        .remove_code(email, purpose)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+async fn set_requires_2fa(
+    state: &AppState,
+    email: &Email,
+    requires_2fa: bool,
+) -> Result<(), AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    let mut user = user_store
+        .get_user(email)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+    user.requires_2fa = requires_2fa;
+
+    user_store
+        .update_user(user)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct Disable2FARequest {
+    pub password: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct Confirm2FARequest {
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
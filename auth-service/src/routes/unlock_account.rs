@@ -0,0 +1,104 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, Email, LoginLockout, UserStoreError, VerificationPurpose, VerificationToken,
+    },
+    utils::{audit::AuditActor, auth::authenticate_admin},
+};
+
+// Lifts a login lockout using the link emailed to the user when their account was locked
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = VerificationToken::parse(request.token).map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let mut verification_token_store = state.verification_token_store.write().await;
+
+    let verification = verification_token_store
+        .get_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    if verification.purpose != VerificationPurpose::UnlockAccount {
+        return Err(AuthAPIError::InvalidToken);
+    }
+    actor.set(&verification.email);
+
+    clear_lockout(&state, &verification.email)
+        .await
+        .map_err(|e| match e {
+            AuthAPIError::UserNotFound => AuthAPIError::InvalidToken,
+            e => e,
+        })?;
+
+    verification_token_store
+        .remove_token(&token)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, Json(UnlockAccountResponse::unlocked())))
+}
+
+// Lifts a login lockout on behalf of a user, e.g. after they got in touch with support
+pub async fn admin_unlock_account(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    headers: HeaderMap,
+    Json(request): Json<AdminUnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    authenticate_admin(&headers, state.settings.current().admin_api_key.as_deref())?;
+
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    actor.set(&email);
+    clear_lockout(&state, &email).await?;
+
+    Ok((StatusCode::OK, Json(UnlockAccountResponse::unlocked())))
+}
+
+async fn clear_lockout(state: &AppState, email: &Email) -> Result<(), AuthAPIError> {
+    let mut user_store = state.user_store.write().await;
+
+    let mut user = user_store.get_user(email).await.map_err(|e| match e {
+        UserStoreError::UserNotFound => AuthAPIError::UserNotFound,
+        _ => AuthAPIError::UnexpectedError,
+    })?;
+    user.lockout = LoginLockout::default();
+
+    user_store
+        .update_user(user)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)
+}
+
+#[derive(Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUnlockAccountRequest {
+    pub email: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UnlockAccountResponse {
+    pub message: String,
+}
+
+impl UnlockAccountResponse {
+    fn unlocked() -> Self {
+        Self {
+            message: "Account unlocked".to_owned(),
+        }
+    }
+}
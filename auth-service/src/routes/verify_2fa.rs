@@ -1,5 +1,78 @@
-use axum::{response::IntoResponse, http::StatusCode};
+use axum::{extract::State, http::StatusCode, Json};
+use axum_extra::extract::CookieJar;
+use serde::Deserialize;
 
-pub async fn verify_2fa() -> impl IntoResponse {
-    StatusCode::OK.into_response()
-}
\ No newline at end of file
+use super::login::{complete_login, LoginResponse};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LoginAttemptId, PasswordChangeReason, TwoFACode, TwoFAPurpose},
+    utils::{audit::AuditActor, metrics::record_2fa_verification},
+};
+
+// Completes a login that `login` answered with a 2FA challenge, the same way a login without
+// 2FA is completed
+pub async fn verify_2fa(
+    State(state): State<AppState>,
+    actor: AuditActor,
+    jar: CookieJar,
+    Json(request): Json<Verify2FARequest>,
+) -> (
+    CookieJar,
+    Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
+) {
+    let verification = verify_login_code(&state, &actor, request).await;
+    record_2fa_verification("login", &verification);
+
+    match verification {
+        Ok((email, password_change_reason)) => {
+            complete_login(&email, password_change_reason, &state, jar).await
+        }
+        Err(e) => (jar, Err(e)),
+    }
+}
+
+// Checks the code sent at login, returning who it was for and whether they have to change
+// their password before they get a session. A code can only be used once.
+async fn verify_login_code(
+    state: &AppState,
+    actor: &AuditActor,
+    request: Verify2FARequest,
+) -> Result<(Email, Option<PasswordChangeReason>), AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    actor.set(&email);
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let two_fa_code =
+        TwoFACode::parse(request.two_fa_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+    let (expected_id, expected_code) = two_fa_code_store
+        .get_code(&email, TwoFAPurpose::Login)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if expected_id != login_attempt_id || expected_code != two_fa_code {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    let password_change_reason = two_fa_code_store
+        .get_password_change_reason(&email)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+    two_fa_code_store
+        .remove_code(&email, TwoFAPurpose::Login)
+        .await
+        .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+    Ok((email, password_change_reason))
+}
+
+#[derive(Deserialize)]
+pub struct Verify2FARequest {
+    pub email: String,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    #[serde(rename = "2FACode")]
+    pub two_fa_code: String,
+}
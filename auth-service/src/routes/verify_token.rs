@@ -1,14 +1,24 @@
 use axum::{extract::State, http::StatusCode, Json};
 use serde::Deserialize;
 
-use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+    utils::{audit::AuditActor, auth::validate_token},
+};
 
 pub async fn verify_token(
     state: State<AppState>,
+    actor: AuditActor,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<StatusCode, AuthAPIError> {
-    match validate_token(&request.token, state.banned_token_store.clone()).await {
-        Ok(_) => Ok(StatusCode::OK),
+    match validate_token(&request.token, state.banned_token_store.clone(), &state.settings.current()).await {
+        Ok(claims) => {
+            if let Ok(email) = Email::parse(claims.sub) {
+                actor.set(&email);
+            }
+            Ok(StatusCode::OK)
+        }
         Err(_) => Err(AuthAPIError::InvalidToken),
     }
 }
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::domain::data_stores::{RateLimit, RateLimitStore, RateLimitStoreError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TokenBucket {
+    tokens: f64,
+    capacity: f64,
+    refill_per_ms: f64,
+    updated_at_ms: i64,
+    // How long the bucket takes to refill from empty
+    refill_window_ms: i64,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, now_ms: i64) {
+        let elapsed_ms = (now_ms - self.updated_at_ms).max(0) as f64;
+        self.tokens = (self.tokens + elapsed_ms * self.refill_per_ms).min(self.capacity);
+        self.updated_at_ms = now_ms.max(self.updated_at_ms);
+    }
+
+    fn is_idle(&self, now_ms: i64) -> bool {
+        now_ms - self.updated_at_ms >= self.refill_window_ms
+    }
+}
+
+#[derive(Default)]
+pub struct HashmapRateLimitStore {
+    buckets: HashMap<String, TokenBucket>,
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for HashmapRateLimitStore {
+    async fn try_acquire(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+        now_ms: i64,
+    ) -> Result<(), RateLimitStoreError> {
+        if limit.capacity == 0 || limit.period_seconds == 0 {
+            return Err(RateLimitStoreError::UnexpectedError);
+        }
+
+        let bucket = self.buckets.entry(key.to_owned()).or_insert(TokenBucket {
+            tokens: limit.capacity as f64,
+            capacity: limit.capacity as f64,
+            refill_per_ms: limit.refill_per_ms(),
+            updated_at_ms: now_ms,
+            refill_window_ms: limit.period_seconds as i64 * 1000,
+        });
+        bucket.refill(now_ms);
+        // The limit may have been reloaded since the bucket was created
+        bucket.capacity = limit.capacity as f64;
+        bucket.refill_per_ms = limit.refill_per_ms();
+        bucket.refill_window_ms = limit.period_seconds as i64 * 1000;
+        bucket.tokens = bucket.tokens.min(bucket.capacity);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let wait_ms = (1.0 - bucket.tokens) / bucket.refill_per_ms;
+        Err(RateLimitStoreError::LimitExceeded {
+            retry_after_seconds: ((wait_ms / 1000.0).ceil() as u64).max(1),
+        })
+    }
+
+    async fn remove_idle(&mut self, now_ms: i64) -> Result<usize, RateLimitStoreError> {
+        let before = self.buckets.len();
+        self.buckets.retain(|_, bucket| !bucket.is_idle(now_ms));
+        Ok(before - self.buckets.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_try_acquire_until_bucket_is_empty() {
+        let mut store = HashmapRateLimitStore::default();
+        let limit = RateLimit::new(3, 60);
+
+        for _ in 0..3 {
+            assert_eq!(store.try_acquire("key", &limit, 0).await, Ok(()));
+        }
+
+        // One token comes back every 20 seconds
+        assert_eq!(
+            store.try_acquire("key", &limit, 0).await,
+            Err(RateLimitStoreError::LimitExceeded {
+                retry_after_seconds: 20
+            })
+        );
+        assert_eq!(
+            store.try_acquire("key", &limit, 15_000).await,
+            Err(RateLimitStoreError::LimitExceeded {
+                retry_after_seconds: 5
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bucket_refills_over_time() {
+        let mut store = HashmapRateLimitStore::default();
+        let limit = RateLimit::new(2, 10);
+
+        assert!(store.try_acquire("key", &limit, 0).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 0).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 0).await.is_err());
+
+        assert!(store.try_acquire("key", &limit, 5_000).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 5_000).await.is_err());
+
+        // The bucket never holds more than its capacity
+        assert!(store.try_acquire("key", &limit, 1_000_000).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 1_000_000).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 1_000_000).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_existing_buckets_follow_a_changed_limit() {
+        let mut store = HashmapRateLimitStore::default();
+
+        assert!(store.try_acquire("key", &RateLimit::new(10, 60), 0).await.is_ok());
+
+        // Tightened: the 9 tokens left are cut down to the new capacity
+        let limit = RateLimit::new(2, 60);
+        assert!(store.try_acquire("key", &limit, 0).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 0).await.is_ok());
+        assert_eq!(
+            store.try_acquire("key", &limit, 0).await,
+            Err(RateLimitStoreError::LimitExceeded {
+                retry_after_seconds: 30
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keys_have_separate_buckets() {
+        let mut store = HashmapRateLimitStore::default();
+        let limit = RateLimit::new(1, 60);
+
+        assert!(store.try_acquire("first", &limit, 0).await.is_ok());
+        assert!(store.try_acquire("first", &limit, 0).await.is_err());
+        assert!(store.try_acquire("second", &limit, 0).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_remove_idle_buckets() {
+        let mut store = HashmapRateLimitStore::default();
+
+        store.try_acquire("short", &RateLimit::new(1, 1), 0).await.unwrap();
+        store.try_acquire("long", &RateLimit::new(1, 60), 0).await.unwrap();
+        store.try_acquire("recent", &RateLimit::new(1, 1), 500).await.unwrap();
+
+        // Only "short" has gone a whole refill window without a request
+        assert_eq!(store.remove_idle(1_000).await, Ok(1));
+        assert!(!store.buckets.contains_key("short"));
+
+        assert_eq!(store.remove_idle(60_000).await, Ok(2));
+        assert!(store.buckets.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refused_requests_keep_bucket_from_being_idle() {
+        let mut store = HashmapRateLimitStore::default();
+        let limit = RateLimit::new(1, 10);
+
+        assert!(store.try_acquire("key", &limit, 0).await.is_ok());
+        assert!(store.try_acquire("key", &limit, 9_000).await.is_err());
+
+        assert_eq!(store.remove_idle(10_000).await, Ok(0));
+        assert_eq!(store.remove_idle(19_000).await, Ok(1));
+    }
+}
@@ -1,13 +1,20 @@
 use std::collections::HashMap;
 
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError, TwoFAPurpose},
     email::Email,
+    PasswordChangeReason,
 };
 
+struct PendingCode {
+    login_attempt_id: LoginAttemptId,
+    code: TwoFACode,
+    password_change_reason: Option<PasswordChangeReason>,
+}
+
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<(Email, TwoFAPurpose), PendingCode>,
 }
 
 // TODO: implement TwoFACodeStore for HashmapTwoFACodeStore
@@ -16,31 +23,79 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
     async fn add_code(
         &mut self,
         email: Email,
+        purpose: TwoFAPurpose,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        password_change_reason: Option<PasswordChangeReason>,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        let pending = PendingCode {
+            login_attempt_id,
+            code,
+            password_change_reason,
+        };
+        self.codes.insert((email, purpose), pending);
         Ok(())
     }
 
-    async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
-        if self.codes.remove(email).is_some() {
+    async fn remove_code(&mut self, email: &Email, purpose: TwoFAPurpose) -> Result<(), TwoFACodeStoreError> {
+        if self.codes.remove(&(email.clone(), purpose)).is_some() {
             Ok(())
         } else {
             Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
         }
     }
 
+    async fn remove_codes_for(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        self.codes.retain(|(code_email, _), _| code_email != email);
+        Ok(())
+    }
+
     async fn get_code(
         &self,
         email: &Email,
+        purpose: TwoFAPurpose,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        if let Some((login_attempt_id, code)) = self.codes.get(email) {
-            Ok((login_attempt_id.clone(), code.clone()))
+        if let Some(pending) = self.codes.get(&(email.clone(), purpose)) {
+            Ok((pending.login_attempt_id.clone(), pending.code.clone()))
         } else {
             Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
         }
     }
+
+    async fn get_password_change_reason(
+        &self,
+        email: &Email,
+    ) -> Result<Option<PasswordChangeReason>, TwoFACodeStoreError> {
+        self.codes
+            .get(&(email.clone(), TwoFAPurpose::Login))
+            .map(|pending| pending.password_change_reason)
+            .ok_or(TwoFACodeStoreError::LoginAttemptIdNotFound)
+    }
+
+    async fn change_email(&mut self, email: &Email, new_email: Email) -> Result<(), TwoFACodeStoreError> {
+        // A user without a pending code has nothing to move
+        let purposes: Vec<TwoFAPurpose> = self
+            .codes
+            .keys()
+            .filter(|(code_email, _)| code_email == email)
+            .map(|(_, purpose)| *purpose)
+            .collect();
+        for purpose in purposes {
+            if let Some(entry) = self.codes.remove(&(email.clone(), purpose)) {
+                self.codes.insert((new_email.clone(), purpose), entry);
+            }
+        }
+        Ok(())
+    }
+
+    async fn has_code(&self, email: &Email) -> Result<bool, TwoFACodeStoreError> {
+        Ok(self.codes.keys().any(|(code_email, _)| code_email == email))
+    }
+
+    async fn health_check(&self) -> Result<(), TwoFACodeStoreError> {
+        // Kept in memory, so it's available whenever the service is
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -55,11 +110,93 @@ mod tests {
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::parse("123456".to_string()).unwrap();
         store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(email.clone(), TwoFAPurpose::Login, login_attempt_id.clone(), code.clone(), None)
             .await
             .unwrap();
-        let (retrieved_id, retrieved_code) = store.get_code(&email).await.unwrap();
+        let (retrieved_id, retrieved_code) =
+            store.get_code(&email, TwoFAPurpose::Login).await.unwrap();
         assert_eq!(retrieved_id, login_attempt_id);
         assert_eq!(retrieved_code, code);
+        assert_eq!(store.get_password_change_reason(&email).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_password_change_reason_is_kept_with_the_code() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let reason = Some(PasswordChangeReason::Expired);
+        store
+            .add_code(email.clone(), TwoFAPurpose::Login, LoginAttemptId::default(), TwoFACode::default(), reason)
+            .await
+            .unwrap();
+        assert_eq!(store.get_password_change_reason(&email).await, Ok(reason));
+
+        // A new code replaces the reason along with the old code
+        store
+            .add_code(email.clone(), TwoFAPurpose::Login, LoginAttemptId::default(), TwoFACode::default(), None)
+            .await
+            .unwrap();
+        assert_eq!(store.get_password_change_reason(&email).await, Ok(None));
+
+        store.remove_code(&email, TwoFAPurpose::Login).await.unwrap();
+        assert_eq!(
+            store.get_password_change_reason(&email).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_codes_are_kept_per_purpose() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let login_code = TwoFACode::default();
+        let login_attempt_id = LoginAttemptId::default();
+        store
+            .add_code(email.clone(), TwoFAPurpose::Login, login_attempt_id.clone(), login_code.clone(), None)
+            .await
+            .unwrap();
+        store
+            .add_code(email.clone(), TwoFAPurpose::Disable, LoginAttemptId::default(), TwoFACode::default(), None)
+            .await
+            .unwrap();
+
+        // Starting another challenge leaves the login code alone
+        assert_eq!(
+            store.get_code(&email, TwoFAPurpose::Login).await,
+            Ok((login_attempt_id, login_code))
+        );
+        assert_eq!(
+            store.get_code(&email, TwoFAPurpose::Enable).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+
+        store.remove_code(&email, TwoFAPurpose::Login).await.unwrap();
+        assert_eq!(store.has_code(&email).await, Ok(true));
+        store.remove_codes_for(&email).await.unwrap();
+        assert_eq!(store.has_code(&email).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut store = HashmapTwoFACodeStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let new_email = Email::parse("new@test.com".to_string()).unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let code = TwoFACode::default();
+        store
+            .add_code(email.clone(), TwoFAPurpose::Enable, login_attempt_id.clone(), code.clone(), None)
+            .await
+            .unwrap();
+        store.change_email(&email, new_email.clone()).await.unwrap();
+        assert_eq!(
+            store.get_code(&email, TwoFAPurpose::Enable).await,
+            Err(TwoFACodeStoreError::LoginAttemptIdNotFound)
+        );
+        assert_eq!(
+            store.get_code(&new_email, TwoFAPurpose::Enable).await,
+            Ok((login_attempt_id, code))
+        );
+        assert_eq!(store.has_code(&email).await, Ok(false));
+        assert_eq!(store.has_code(&new_email).await, Ok(true));
     }
 }
\ No newline at end of file
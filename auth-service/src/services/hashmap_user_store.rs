@@ -1,9 +1,30 @@
 use std::collections::HashMap;
-use crate::domain::{User, UserStore, UserStoreError, email::Email, password::Password};
+use chrono::Utc;
+use crate::domain::{
+    ConsentRecord, LoginRecord, PasswordHash, Session, User, UserStore, UserStoreError,
+    email::Email, password::Password,
+};
+
+// Only the most recent login attempts are kept for each user
+const MAX_LOGIN_HISTORY_ENTRIES: usize = 50;
 
 #[derive(Default)]
 pub struct HashmapUserStore {
     pub users: HashMap<Email, User>,
+    pub sessions: HashMap<Email, Vec<Session>>,
+    pub login_history: HashMap<Email, Vec<LoginRecord>>,
+    pub consents: HashMap<Email, Vec<ConsentRecord>>,
+    pub password_history: HashMap<Email, Vec<PasswordHash>>,
+}
+
+impl HashmapUserStore {
+    fn ensure_user_exists(&self, email: &Email) -> Result<(), UserStoreError> {
+        if self.users.contains_key(email) {
+            Ok(())
+        } else {
+            Err(UserStoreError::UserNotFound)
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -26,7 +47,7 @@ impl UserStore for HashmapUserStore {
     /// Returns `UserStoreError::UserNotFound` if the user can not be found.
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         match self.users.get(email) {
-            Some(user) => Ok(User::new(user.email.clone(), user.password.clone(), user.requires_2fa)),
+            Some(user) => Ok(user.clone()),
             None => Err(UserStoreError::UserNotFound),
         }
     }
@@ -49,6 +70,159 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    /// Replaces the user stored under `user.email`.
+    /// Returns `UserStoreError::UserNotFound` if the user can not be found.
+    async fn update_user(&mut self, user: User) -> Result<(), UserStoreError> {
+        let stored = self.users.get_mut(&user.email).ok_or(UserStoreError::UserNotFound)?;
+        *stored = user;
+        Ok(())
+    }
+
+    /// Re-keys the user stored under `email` to `new_email`.
+    /// Returns `UserStoreError::UserAlreadyExists` if `new_email` is taken.
+    async fn change_email(&mut self, email: &Email, new_email: Email) -> Result<(), UserStoreError> {
+        if self.users.contains_key(&new_email) {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
+        let mut user = self.users.remove(email).ok_or(UserStoreError::UserNotFound)?;
+        user.email = new_email.clone();
+        self.users.insert(new_email.clone(), user);
+
+        // Sessions belong to tokens issued for the old address, which are no longer valid
+        self.sessions.remove(email);
+        if let Some(login_history) = self.login_history.remove(email) {
+            self.login_history.insert(new_email.clone(), login_history);
+        }
+        if let Some(consents) = self.consents.remove(email) {
+            self.consents.insert(new_email.clone(), consents);
+        }
+        if let Some(password_history) = self.password_history.remove(email) {
+            self.password_history.insert(new_email, password_history);
+        }
+        Ok(())
+    }
+
+    async fn schedule_deletion(&mut self, email: &Email, purge_at: i64) -> Result<(), UserStoreError> {
+        let user = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.deletion_scheduled_for = Some(purge_at);
+        Ok(())
+    }
+
+    async fn cancel_deletion(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let user = self.users.get_mut(email).ok_or(UserStoreError::UserNotFound)?;
+        user.deletion_scheduled_for = None;
+        Ok(())
+    }
+
+    async fn get_users_due_for_deletion(&self, now: i64) -> Result<Vec<Email>, UserStoreError> {
+        Ok(self
+            .users
+            .values()
+            .filter(|user| user.deletion_scheduled_for.is_some_and(|purge_at| purge_at <= now))
+            .map(|user| user.email.clone())
+            .collect())
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        self.users.remove(email).ok_or(UserStoreError::UserNotFound)?;
+        self.sessions.remove(email);
+        self.login_history.remove(email);
+        self.consents.remove(email);
+        self.password_history.remove(email);
+        Ok(())
+    }
+
+    async fn add_session(&mut self, email: &Email, session: Session) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        let now = Utc::now().timestamp();
+        let sessions = self.sessions.entry(email.clone()).or_default();
+        // Drop expired sessions so the list doesn't grow forever
+        sessions.retain(|session| session.expires_at > now);
+        sessions.push(session);
+        Ok(())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, UserStoreError> {
+        self.ensure_user_exists(email)?;
+        let now = Utc::now().timestamp();
+        Ok(self
+            .sessions
+            .get(email)
+            .map(|sessions| {
+                sessions
+                    .iter()
+                    .filter(|session| session.expires_at > now)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn remove_session(&mut self, email: &Email, session_id: &str) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        if let Some(sessions) = self.sessions.get_mut(email) {
+            sessions.retain(|session| session.id != session_id);
+        }
+        Ok(())
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        self.sessions.remove(email);
+        Ok(())
+    }
+
+    async fn record_login(&mut self, email: &Email, record: LoginRecord) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        let login_history = self.login_history.entry(email.clone()).or_default();
+        login_history.push(record);
+        if login_history.len() > MAX_LOGIN_HISTORY_ENTRIES {
+            login_history.remove(0);
+        }
+        Ok(())
+    }
+
+    async fn get_login_history(&self, email: &Email) -> Result<Vec<LoginRecord>, UserStoreError> {
+        self.ensure_user_exists(email)?;
+        Ok(self.login_history.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn add_consent(&mut self, email: &Email, consent: ConsentRecord) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        self.consents.entry(email.clone()).or_default().push(consent);
+        Ok(())
+    }
+
+    async fn get_consents(&self, email: &Email) -> Result<Vec<ConsentRecord>, UserStoreError> {
+        self.ensure_user_exists(email)?;
+        Ok(self.consents.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn add_password_history(
+        &mut self,
+        email: &Email,
+        hash: PasswordHash,
+        keep: usize,
+    ) -> Result<(), UserStoreError> {
+        self.ensure_user_exists(email)?;
+        let password_history = self.password_history.entry(email.clone()).or_default();
+        password_history.push(hash);
+        let excess = password_history.len().saturating_sub(keep);
+        password_history.drain(..excess);
+        Ok(())
+    }
+
+    async fn get_password_history(&self, email: &Email) -> Result<Vec<PasswordHash>, UserStoreError> {
+        self.ensure_user_exists(email)?;
+        Ok(self.password_history.get(email).cloned().unwrap_or_default())
+    }
+
+    async fn health_check(&self) -> Result<(), UserStoreError> {
+        // Kept in memory, so it's available whenever the service is
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -78,11 +252,7 @@ mod tests {
         let mut user_store = HashmapUserStore::default();
         let email = Email::parse("test@example.com".to_owned()).unwrap();
 
-        let user = User {
-            email: email.clone(),
-            password: Password::parse("password".to_owned()).unwrap(),
-            requires_2fa: false,
-        };
+        let user = User::new(email.clone(), Password::parse("password".to_owned()).unwrap(), false);
 
         // Test getting a user that exists
         user_store.users.insert(email.clone(), user.clone());
@@ -103,11 +273,7 @@ mod tests {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
         let password = Password::parse("password".to_owned()).unwrap();
 
-        let user = User {
-            email: email.clone(),
-            password: password.clone(),
-            requires_2fa: false,
-        };
+        let user = User::new(email.clone(), password.clone(), false);
 
         // Test validating a user that exists with correct password
         user_store.users.insert(email.clone(), user.clone());
@@ -129,4 +295,189 @@ mod tests {
 
         assert_eq!(result, Err(UserStoreError::UserNotFound));
     }
+
+    #[tokio::test]
+    async fn test_update_user() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        let user = User::new(email.clone(), password.clone(), false);
+
+        // Test updating a user that doesn't exist
+        let result = user_store.update_user(user.clone()).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        user_store.add_user(user).await.unwrap();
+        let updated = User::new(email.clone(), password, true);
+        user_store.update_user(updated.clone()).await.unwrap();
+        assert_eq!(user_store.get_user(&email).await, Ok(updated));
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let new_email = Email::parse("new@example.com".to_owned()).unwrap();
+        let taken_email = Email::parse("taken@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+
+        let user = User::new(email.clone(), password.clone(), false);
+        user_store.add_user(user.clone()).await.unwrap();
+        user_store.add_user(User::new(taken_email.clone(), password, false)).await.unwrap();
+
+        // Test moving to an address that is already taken
+        let result = user_store.change_email(&email, taken_email).await;
+        assert_eq!(result, Err(UserStoreError::UserAlreadyExists));
+
+        // Test moving to a free address
+        user_store.change_email(&email, new_email.clone()).await.unwrap();
+        assert_eq!(user_store.get_user(&email).await, Err(UserStoreError::UserNotFound));
+        assert_eq!(
+            user_store.get_user(&new_email).await,
+            Ok(User {
+                email: new_email.clone(),
+                ..user
+            })
+        );
+
+        // Test moving a user that doesn't exist
+        let result = user_store
+            .change_email(&email, Email::parse("other@example.com".to_owned()).unwrap())
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_schedule_and_cancel_deletion() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        user_store.schedule_deletion(&email, 100).await.unwrap();
+        assert_eq!(user_store.get_user(&email).await.unwrap().deletion_scheduled_for, Some(100));
+        assert_eq!(user_store.get_users_due_for_deletion(99).await, Ok(vec![]));
+        assert_eq!(user_store.get_users_due_for_deletion(100).await, Ok(vec![email.clone()]));
+
+        user_store.cancel_deletion(&email).await.unwrap();
+        assert_eq!(user_store.get_user(&email).await.unwrap().deletion_scheduled_for, None);
+        assert_eq!(user_store.get_users_due_for_deletion(100).await, Ok(vec![]));
+
+        // Test scheduling a user that doesn't exist
+        let result = user_store
+            .schedule_deletion(&Email::parse("nonexistent@example.com".to_owned()).unwrap(), 100)
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        assert_eq!(user_store.delete_user(&email).await, Ok(()));
+        assert_eq!(user_store.get_user(&email).await, Err(UserStoreError::UserNotFound));
+        assert_eq!(user_store.delete_user(&email).await, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_sessions() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        let now = Utc::now().timestamp();
+        let session = Session { id: "current".to_owned(), created_at: now, expires_at: now + 60 };
+        let expired = Session { id: "expired".to_owned(), created_at: now - 120, expires_at: now - 60 };
+        user_store.add_session(&email, session.clone()).await.unwrap();
+        user_store.add_session(&email, expired).await.unwrap();
+
+        // Expired sessions are not returned
+        assert_eq!(user_store.get_sessions(&email).await, Ok(vec![session]));
+
+        user_store.remove_session(&email, "current").await.unwrap();
+        assert_eq!(user_store.get_sessions(&email).await, Ok(vec![]));
+
+        // Test adding a session for a user that doesn't exist
+        let result = user_store
+            .add_session(
+                &Email::parse("nonexistent@example.com".to_owned()).unwrap(),
+                Session { id: "other".to_owned(), created_at: now, expires_at: now + 60 },
+            )
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_login_history_is_bounded() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        for at in 0..(MAX_LOGIN_HISTORY_ENTRIES as i64 + 5) {
+            let record = LoginRecord { at, outcome: crate::domain::LoginOutcome::Success };
+            user_store.record_login(&email, record).await.unwrap();
+        }
+
+        let login_history = user_store.get_login_history(&email).await.unwrap();
+        assert_eq!(login_history.len(), MAX_LOGIN_HISTORY_ENTRIES);
+        assert_eq!(login_history[0].at, 5);
+    }
+
+    #[tokio::test]
+    async fn test_password_history_is_bounded() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        let hashes: Vec<_> = ["password1", "password2", "password3"]
+            .map(|password| PasswordHash::new(&Password::parse(password.to_owned()).unwrap()))
+            .into();
+        for hash in hashes.iter().cloned() {
+            user_store.add_password_history(&email, hash, 2).await.unwrap();
+        }
+        assert_eq!(user_store.get_password_history(&email).await, Ok(hashes[1..].to_vec()));
+
+        // Keeping none forgets the history
+        user_store.add_password_history(&email, hashes[0].clone(), 0).await.unwrap();
+        assert_eq!(user_store.get_password_history(&email).await, Ok(vec![]));
+
+        let result = user_store
+            .get_password_history(&Email::parse("nonexistent@example.com".to_owned()).unwrap())
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+    }
+
+    #[tokio::test]
+    async fn test_activity_follows_email_change_and_deletion() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let new_email = Email::parse("new@example.com".to_owned()).unwrap();
+        let password = Password::parse("password".to_owned()).unwrap();
+        user_store.add_user(User::new(email.clone(), password, false)).await.unwrap();
+
+        let now = Utc::now().timestamp();
+        let consent = ConsentRecord { purpose: "terms_of_service".to_owned(), granted_at: now };
+        let session = Session { id: "current".to_owned(), created_at: now, expires_at: now + 60 };
+        user_store.add_consent(&email, consent.clone()).await.unwrap();
+        user_store.add_session(&email, session).await.unwrap();
+        let hash = PasswordHash::new(&Password::parse("old-password".to_owned()).unwrap());
+        user_store.add_password_history(&email, hash.clone(), 5).await.unwrap();
+
+        // Consents and the password history move with the user, sessions for the old address don't
+        user_store.change_email(&email, new_email.clone()).await.unwrap();
+        assert_eq!(user_store.get_consents(&new_email).await, Ok(vec![consent]));
+        assert_eq!(user_store.get_sessions(&new_email).await, Ok(vec![]));
+        assert_eq!(user_store.get_password_history(&new_email).await, Ok(vec![hash]));
+
+        user_store.delete_user(&new_email).await.unwrap();
+        assert!(user_store.consents.is_empty());
+        assert!(user_store.password_history.is_empty());
+        assert_eq!(user_store.get_consents(&new_email).await, Err(UserStoreError::UserNotFound));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+
+use crate::domain::{
+    data_stores::{
+        PendingVerification, VerificationToken, VerificationTokenStore,
+        VerificationTokenStoreError,
+    },
+    email::Email,
+};
+
+#[derive(Default)]
+pub struct HashmapVerificationTokenStore {
+    tokens: HashMap<VerificationToken, PendingVerification>,
+}
+
+#[async_trait::async_trait]
+impl VerificationTokenStore for HashmapVerificationTokenStore {
+    async fn add_token(
+        &mut self,
+        token: VerificationToken,
+        verification: PendingVerification,
+    ) -> Result<(), VerificationTokenStoreError> {
+        self.tokens.insert(token, verification);
+        Ok(())
+    }
+
+    async fn get_token(
+        &self,
+        token: &VerificationToken,
+    ) -> Result<PendingVerification, VerificationTokenStoreError> {
+        let verification = self
+            .tokens
+            .get(token)
+            .ok_or(VerificationTokenStoreError::TokenNotFound)?;
+
+        if verification.expires_at < Utc::now().timestamp() {
+            return Err(VerificationTokenStoreError::TokenExpired);
+        }
+
+        Ok(verification.clone())
+    }
+
+    async fn remove_token(&mut self, token: &VerificationToken) -> Result<(), VerificationTokenStoreError> {
+        if self.tokens.remove(token).is_some() {
+            Ok(())
+        } else {
+            Err(VerificationTokenStoreError::TokenNotFound)
+        }
+    }
+
+    async fn remove_tokens_for(&mut self, email: &Email) -> Result<(), VerificationTokenStoreError> {
+        self.tokens.retain(|_, verification| &verification.email != email);
+        Ok(())
+    }
+
+    async fn get_pending_for(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingVerification>, VerificationTokenStoreError> {
+        let now = Utc::now().timestamp();
+        Ok(self
+            .tokens
+            .values()
+            .filter(|verification| &verification.email == email && verification.expires_at >= now)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::data_stores::VerificationPurpose;
+
+    fn pending_email_change(email: &Email, expires_at: i64) -> PendingVerification {
+        PendingVerification {
+            email: email.clone(),
+            purpose: VerificationPurpose::ConfirmEmailChange {
+                new_email: Email::parse("new@test.com".to_string()).unwrap(),
+            },
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_get_and_remove_token() {
+        let mut store = HashmapVerificationTokenStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let token = VerificationToken::default();
+        let verification = pending_email_change(&email, Utc::now().timestamp() + 60);
+        store.add_token(token.clone(), verification.clone()).await.unwrap();
+
+        assert_eq!(store.get_token(&token).await, Ok(verification));
+        store.remove_token(&token).await.unwrap();
+        assert_eq!(
+            store.get_token(&token).await,
+            Err(VerificationTokenStoreError::TokenNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_expired_token() {
+        let mut store = HashmapVerificationTokenStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let token = VerificationToken::default();
+        store
+            .add_token(token.clone(), pending_email_change(&email, Utc::now().timestamp() - 1))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get_token(&token).await,
+            Err(VerificationTokenStoreError::TokenExpired)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_tokens_for() {
+        let mut store = HashmapVerificationTokenStore::default();
+        let email = Email::parse("test@test.com".to_string()).unwrap();
+        let other_email = Email::parse("other@test.com".to_string()).unwrap();
+        let expires_at = Utc::now().timestamp() + 60;
+        let token = VerificationToken::default();
+        let other_token = VerificationToken::default();
+        store.add_token(token.clone(), pending_email_change(&email, expires_at)).await.unwrap();
+        store
+            .add_token(other_token.clone(), pending_email_change(&other_email, expires_at))
+            .await
+            .unwrap();
+
+        assert_eq!(store.get_pending_for(&email).await.unwrap().len(), 1);
+        store.remove_tokens_for(&email).await.unwrap();
+        assert_eq!(store.get_pending_for(&email).await, Ok(vec![]));
+        assert_eq!(
+            store.get_token(&token).await,
+            Err(VerificationTokenStoreError::TokenNotFound)
+        );
+        assert!(store.get_token(&other_token).await.is_ok());
+    }
+}
\ No newline at end of file
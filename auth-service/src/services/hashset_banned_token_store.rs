@@ -1,9 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use crate::domain::{BannedTokenStore, BannedTokenStoreError};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
     pub banned_tokens: HashSet<String>,
+    // Maps a token subject to the latest issue time that is banned for it
+    pub banned_subjects: HashMap<String, i64>,
 }
 
 #[async_trait::async_trait]
@@ -16,6 +18,24 @@ impl BannedTokenStore for HashsetBannedTokenStore {
     async fn is_token_banned(&self, token: String) -> Result<bool, BannedTokenStoreError> {
         Ok(self.banned_tokens.contains(token.as_str()))
     }
+
+    async fn ban_subject(&mut self, sub: String, issued_before: i64) -> Result<(), BannedTokenStoreError> {
+        let cutoff = self.banned_subjects.entry(sub).or_insert(issued_before);
+        *cutoff = (*cutoff).max(issued_before);
+        Ok(())
+    }
+
+    async fn is_subject_banned(&self, sub: &str, issued_at: i64) -> Result<bool, BannedTokenStoreError> {
+        Ok(self
+            .banned_subjects
+            .get(sub)
+            .is_some_and(|issued_before| issued_at <= *issued_before))
+    }
+
+    async fn health_check(&self) -> Result<(), BannedTokenStoreError> {
+        // Kept in memory, so it's available whenever the service is
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -25,16 +45,30 @@ mod tests {
     #[tokio::test]
     async fn test_ban_token() {
         let mut store = HashsetBannedTokenStore::default();
-        assert_eq!(store.is_token_banned("token1".to_string()).await.unwrap(), false);
+        assert!(!store.is_token_banned("token1".to_string()).await.unwrap());
         store.ban_token("token1".to_string()).await.unwrap();
-        assert_eq!(store.is_token_banned("token1".to_string()).await.unwrap(), true);
+        assert!(store.is_token_banned("token1".to_string()).await.unwrap());
     }
 
     #[tokio::test]
     async fn test_is_token_banned() {
         let mut store = HashsetBannedTokenStore::default();
         store.ban_token("token2".to_string()).await.unwrap();
-        assert_eq!(store.is_token_banned("token2".to_string()).await.unwrap(), true);
-        assert_eq!(store.is_token_banned("token3".to_string()).await.unwrap(), false);
+        assert!(store.is_token_banned("token2".to_string()).await.unwrap());
+        assert!(!store.is_token_banned("token3".to_string()).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ban_subject() {
+        let mut store = HashsetBannedTokenStore::default();
+        store.ban_subject("test@example.com".to_string(), 100).await.unwrap();
+        assert!(store.is_subject_banned("test@example.com", 99).await.unwrap());
+        assert!(store.is_subject_banned("test@example.com", 100).await.unwrap());
+        assert!(!store.is_subject_banned("test@example.com", 101).await.unwrap());
+        assert!(!store.is_subject_banned("other@example.com", 99).await.unwrap());
+
+        // An earlier cutoff must not shorten an existing ban
+        store.ban_subject("test@example.com".to_string(), 50).await.unwrap();
+        assert!(store.is_subject_banned("test@example.com", 100).await.unwrap());
     }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use crate::domain::{AuditEvent, AuditLog, AuditLogError, AuditQuery};
+
+#[derive(Default)]
+pub struct InMemoryAuditLog {
+    events: Vec<AuditEvent>,
+}
+
+#[async_trait::async_trait]
+impl AuditLog for InMemoryAuditLog {
+    async fn record(&mut self, event: AuditEvent) -> Result<(), AuditLogError> {
+        self.events.push(event);
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>, AuditLogError> {
+        Ok(query.apply(self.events.iter()))
+    }
+
+    // Nothing outside this process can edit the events, so there's nothing to vouch for
+    async fn checkpoint(&mut self, _at: i64) -> Result<(), AuditLogError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::AuditOutcome;
+
+    #[tokio::test]
+    async fn test_record_and_query() {
+        let mut audit_log = InMemoryAuditLog::default();
+
+        for (at, actor) in [
+            (1, "a@example.com"),
+            (2, "b@example.com"),
+            (3, "a@example.com"),
+        ] {
+            audit_log
+                .record(AuditEvent {
+                    at,
+                    action: "POST /login".to_owned(),
+                    actor: Some(actor.to_owned()),
+                    ip: Some("127.0.0.1".to_owned()),
+                    user_agent: None,
+                    outcome: AuditOutcome::Success,
+                    status: 200,
+                })
+                .await
+                .unwrap();
+        }
+
+        let events = audit_log
+            .query(&AuditQuery {
+                actor: Some("a@example.com".to_owned()),
+                ..AuditQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(events.iter().map(|e| e.at).collect::<Vec<_>>(), vec![3, 1]);
+
+        let events = audit_log.query(&AuditQuery::default()).await.unwrap();
+        assert_eq!(events.len(), 3);
+    }
+}
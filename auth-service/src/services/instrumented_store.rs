@@ -0,0 +1,423 @@
+use crate::{
+    domain::{
+        BannedTokenStore, BannedTokenStoreError, ConsentRecord, Email, LoginAttemptId, LoginRecord,
+        Password, PasswordChangeReason, PasswordHash, PendingVerification, RateLimit,
+        RateLimitStore, RateLimitStoreError, Session, TwoFACode, TwoFACodeStore,
+        TwoFACodeStoreError, TwoFAPurpose, User, UserStore, UserStoreError, VerificationToken,
+        VerificationTokenStore, VerificationTokenStoreError,
+    },
+    utils::metrics::{record_banned_token, time_store_operation as timed},
+};
+
+// Wraps any store to record how long each of its operations takes,
+// labelled with the kind of store and the name of the operation.
+pub struct InstrumentedStore<S> {
+    inner: S,
+}
+
+impl<S> InstrumentedStore<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: UserStore + Send + Sync> UserStore for InstrumentedStore<S> {
+    async fn add_user(&mut self, user: User) -> Result<(), UserStoreError> {
+        timed("user", "add_user", self.inner.add_user(user)).await
+    }
+
+    async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
+        timed("user", "get_user", self.inner.get_user(email)).await
+    }
+
+    async fn validate_user(
+        &self,
+        email: &Email,
+        password: &Password,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "validate_user",
+            self.inner.validate_user(email, password),
+        )
+        .await
+    }
+
+    async fn update_user(&mut self, user: User) -> Result<(), UserStoreError> {
+        timed("user", "update_user", self.inner.update_user(user)).await
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: Email,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "change_email",
+            self.inner.change_email(email, new_email),
+        )
+        .await
+    }
+
+    async fn schedule_deletion(
+        &mut self,
+        email: &Email,
+        purge_at: i64,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "schedule_deletion",
+            self.inner.schedule_deletion(email, purge_at),
+        )
+        .await
+    }
+
+    async fn cancel_deletion(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        timed("user", "cancel_deletion", self.inner.cancel_deletion(email)).await
+    }
+
+    async fn get_users_due_for_deletion(&self, now: i64) -> Result<Vec<Email>, UserStoreError> {
+        timed(
+            "user",
+            "get_users_due_for_deletion",
+            self.inner.get_users_due_for_deletion(now),
+        )
+        .await
+    }
+
+    async fn delete_user(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        timed("user", "delete_user", self.inner.delete_user(email)).await
+    }
+
+    async fn add_session(&mut self, email: &Email, session: Session) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "add_session",
+            self.inner.add_session(email, session),
+        )
+        .await
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, UserStoreError> {
+        timed("user", "get_sessions", self.inner.get_sessions(email)).await
+    }
+
+    async fn remove_session(
+        &mut self,
+        email: &Email,
+        session_id: &str,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "remove_session",
+            self.inner.remove_session(email, session_id),
+        )
+        .await
+    }
+
+    async fn remove_sessions(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        timed("user", "remove_sessions", self.inner.remove_sessions(email)).await
+    }
+
+    async fn record_login(
+        &mut self,
+        email: &Email,
+        record: LoginRecord,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "record_login",
+            self.inner.record_login(email, record),
+        )
+        .await
+    }
+
+    async fn get_login_history(&self, email: &Email) -> Result<Vec<LoginRecord>, UserStoreError> {
+        timed(
+            "user",
+            "get_login_history",
+            self.inner.get_login_history(email),
+        )
+        .await
+    }
+
+    async fn add_consent(
+        &mut self,
+        email: &Email,
+        consent: ConsentRecord,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "add_consent",
+            self.inner.add_consent(email, consent),
+        )
+        .await
+    }
+
+    async fn get_consents(&self, email: &Email) -> Result<Vec<ConsentRecord>, UserStoreError> {
+        timed("user", "get_consents", self.inner.get_consents(email)).await
+    }
+
+    async fn add_password_history(
+        &mut self,
+        email: &Email,
+        hash: PasswordHash,
+        keep: usize,
+    ) -> Result<(), UserStoreError> {
+        timed(
+            "user",
+            "add_password_history",
+            self.inner.add_password_history(email, hash, keep),
+        )
+        .await
+    }
+
+    async fn get_password_history(&self, email: &Email) -> Result<Vec<PasswordHash>, UserStoreError> {
+        timed(
+            "user",
+            "get_password_history",
+            self.inner.get_password_history(email),
+        )
+        .await
+    }
+
+    async fn health_check(&self) -> Result<(), UserStoreError> {
+        timed("user", "health_check", self.inner.health_check()).await
+    }
+}
+
+// Also counts bans, since every ban goes through the store
+#[async_trait::async_trait]
+impl<S: BannedTokenStore + Send + Sync> BannedTokenStore for InstrumentedStore<S> {
+    async fn ban_token(&mut self, token: String) -> Result<(), BannedTokenStoreError> {
+        let result = timed("banned_token", "ban_token", self.inner.ban_token(token)).await;
+        if result.is_ok() {
+            record_banned_token("token");
+        }
+        result
+    }
+
+    async fn is_token_banned(&self, token: String) -> Result<bool, BannedTokenStoreError> {
+        timed(
+            "banned_token",
+            "is_token_banned",
+            self.inner.is_token_banned(token),
+        )
+        .await
+    }
+
+    async fn ban_subject(
+        &mut self,
+        sub: String,
+        issued_before: i64,
+    ) -> Result<(), BannedTokenStoreError> {
+        let result = timed(
+            "banned_token",
+            "ban_subject",
+            self.inner.ban_subject(sub, issued_before),
+        )
+        .await;
+        if result.is_ok() {
+            record_banned_token("subject");
+        }
+        result
+    }
+
+    async fn is_subject_banned(
+        &self,
+        sub: &str,
+        issued_at: i64,
+    ) -> Result<bool, BannedTokenStoreError> {
+        timed(
+            "banned_token",
+            "is_subject_banned",
+            self.inner.is_subject_banned(sub, issued_at),
+        )
+        .await
+    }
+
+    async fn health_check(&self) -> Result<(), BannedTokenStoreError> {
+        timed("banned_token", "health_check", self.inner.health_check()).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: TwoFACodeStore + Send + Sync> TwoFACodeStore for InstrumentedStore<S> {
+    async fn add_code(
+        &mut self,
+        email: Email,
+        purpose: TwoFAPurpose,
+        login_attempt_id: LoginAttemptId,
+        code: TwoFACode,
+        password_change_reason: Option<PasswordChangeReason>,
+    ) -> Result<(), TwoFACodeStoreError> {
+        timed(
+            "two_fa_code",
+            "add_code",
+            self.inner
+                .add_code(email, purpose, login_attempt_id, code, password_change_reason),
+        )
+        .await
+    }
+
+    async fn remove_code(&mut self, email: &Email, purpose: TwoFAPurpose) -> Result<(), TwoFACodeStoreError> {
+        timed("two_fa_code", "remove_code", self.inner.remove_code(email, purpose)).await
+    }
+
+    async fn remove_codes_for(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError> {
+        timed("two_fa_code", "remove_codes_for", self.inner.remove_codes_for(email)).await
+    }
+
+    async fn get_code(
+        &self,
+        email: &Email,
+        purpose: TwoFAPurpose,
+    ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
+        timed("two_fa_code", "get_code", self.inner.get_code(email, purpose)).await
+    }
+
+    async fn get_password_change_reason(
+        &self,
+        email: &Email,
+    ) -> Result<Option<PasswordChangeReason>, TwoFACodeStoreError> {
+        timed(
+            "two_fa_code",
+            "get_password_change_reason",
+            self.inner.get_password_change_reason(email),
+        )
+        .await
+    }
+
+    async fn change_email(
+        &mut self,
+        email: &Email,
+        new_email: Email,
+    ) -> Result<(), TwoFACodeStoreError> {
+        timed(
+            "two_fa_code",
+            "change_email",
+            self.inner.change_email(email, new_email),
+        )
+        .await
+    }
+
+    async fn has_code(&self, email: &Email) -> Result<bool, TwoFACodeStoreError> {
+        timed("two_fa_code", "has_code", self.inner.has_code(email)).await
+    }
+
+    async fn health_check(&self) -> Result<(), TwoFACodeStoreError> {
+        timed("two_fa_code", "health_check", self.inner.health_check()).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: VerificationTokenStore + Send + Sync> VerificationTokenStore for InstrumentedStore<S> {
+    async fn add_token(
+        &mut self,
+        token: VerificationToken,
+        verification: PendingVerification,
+    ) -> Result<(), VerificationTokenStoreError> {
+        timed(
+            "verification_token",
+            "add_token",
+            self.inner.add_token(token, verification),
+        )
+        .await
+    }
+
+    async fn get_token(
+        &self,
+        token: &VerificationToken,
+    ) -> Result<PendingVerification, VerificationTokenStoreError> {
+        timed(
+            "verification_token",
+            "get_token",
+            self.inner.get_token(token),
+        )
+        .await
+    }
+
+    async fn remove_token(
+        &mut self,
+        token: &VerificationToken,
+    ) -> Result<(), VerificationTokenStoreError> {
+        timed(
+            "verification_token",
+            "remove_token",
+            self.inner.remove_token(token),
+        )
+        .await
+    }
+
+    async fn remove_tokens_for(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), VerificationTokenStoreError> {
+        timed(
+            "verification_token",
+            "remove_tokens_for",
+            self.inner.remove_tokens_for(email),
+        )
+        .await
+    }
+
+    async fn get_pending_for(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PendingVerification>, VerificationTokenStoreError> {
+        timed(
+            "verification_token",
+            "get_pending_for",
+            self.inner.get_pending_for(email),
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: RateLimitStore + Send + Sync> RateLimitStore for InstrumentedStore<S> {
+    async fn try_acquire(
+        &mut self,
+        key: &str,
+        limit: &RateLimit,
+        now_ms: i64,
+    ) -> Result<(), RateLimitStoreError> {
+        timed(
+            "rate_limit",
+            "try_acquire",
+            self.inner.try_acquire(key, limit, now_ms),
+        )
+        .await
+    }
+
+    async fn remove_idle(&mut self, now_ms: i64) -> Result<usize, RateLimitStoreError> {
+        timed("rate_limit", "remove_idle", self.inner.remove_idle(now_ms)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::HashsetBannedTokenStore;
+
+    #[tokio::test]
+    async fn test_operations_reach_the_inner_store() {
+        let mut store = InstrumentedStore::new(HashsetBannedTokenStore::default());
+
+        store.ban_token("token".to_owned()).await.unwrap();
+        store
+            .ban_subject("test@example.com".to_owned(), 10)
+            .await
+            .unwrap();
+
+        assert!(store.is_token_banned("token".to_owned()).await.unwrap());
+        assert!(!store.is_token_banned("other".to_owned()).await.unwrap());
+        assert!(store
+            .is_subject_banned("test@example.com", 10)
+            .await
+            .unwrap());
+        assert!(store.inner.banned_tokens.contains("token"));
+    }
+}
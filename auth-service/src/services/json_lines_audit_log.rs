@@ -0,0 +1,256 @@
+use std::path::{Path, PathBuf};
+
+use tokio::{
+    fs::{self, File, OpenOptions},
+    io::AsyncWriteExt,
+};
+
+use crate::{
+    domain::{
+        audit_chain::{AuditChain, AuditLogEntry},
+        AuditEvent, AuditLog, AuditLogError, AuditQuery,
+    },
+    utils::settings::SettingsHandle,
+};
+
+// Appends each event to a file as one line of JSON, so the log survives restarts
+// and can be read with standard tools. Events are hash-chained and checkpoints are
+// signed with the current JWT secret, so the file can be checked for tampering with
+// `verify_audit_log`.
+pub struct JsonLinesAuditLog {
+    path: PathBuf,
+    file: File,
+    chain: AuditChain,
+    // Read for each checkpoint, so a rotated secret is picked up on reload
+    settings: SettingsHandle,
+    // Whether events were appended since the last checkpoint
+    unchecked_events: bool,
+}
+
+impl JsonLinesAuditLog {
+    pub async fn open(path: impl AsRef<Path>, settings: SettingsHandle) -> std::io::Result<Self> {
+        let path = path.as_ref().to_owned();
+
+        // Pick the chain back up where the existing file ends
+        let mut chain = AuditChain::default();
+        let mut unchecked_events = false;
+        if let Ok(contents) = fs::read_to_string(&path).await {
+            for entry in contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+            {
+                match entry {
+                    AuditLogEntry::Event(record) => {
+                        chain = AuditChain::resume(&record);
+                        unchecked_events = true;
+                    }
+                    AuditLogEntry::Checkpoint(_) => unchecked_events = false,
+                }
+            }
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+
+        Ok(Self {
+            path,
+            file,
+            chain,
+            settings,
+            unchecked_events,
+        })
+    }
+
+    async fn append(&mut self, entry: &AuditLogEntry) -> Result<(), AuditLogError> {
+        let mut line = serde_json::to_vec(entry).map_err(|_| AuditLogError::UnexpectedError)?;
+        line.push(b'\n');
+
+        self.file
+            .write_all(&line)
+            .await
+            .map_err(|_| AuditLogError::UnexpectedError)?;
+        self.file
+            .flush()
+            .await
+            .map_err(|_| AuditLogError::UnexpectedError)
+    }
+}
+
+#[async_trait::async_trait]
+impl AuditLog for JsonLinesAuditLog {
+    async fn record(&mut self, event: AuditEvent) -> Result<(), AuditLogError> {
+        // Only advance the chain once the event is safely on disk
+        let mut chain = self.chain.clone();
+        let entry = AuditLogEntry::Event(chain.append(event));
+
+        self.append(&entry).await?;
+        self.chain = chain;
+        self.unchecked_events = true;
+        Ok(())
+    }
+
+    async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>, AuditLogError> {
+        let contents = fs::read_to_string(&self.path)
+            .await
+            .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        let entries = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<AuditLogEntry>, _>>()
+            .map_err(|_| AuditLogError::UnexpectedError)?;
+
+        let events: Vec<AuditEvent> = entries
+            .into_iter()
+            .filter_map(|entry| match entry {
+                AuditLogEntry::Event(record) => Some(record.event),
+                AuditLogEntry::Checkpoint(_) => None,
+            })
+            .collect();
+
+        Ok(query.apply(events.iter()))
+    }
+
+    async fn checkpoint(&mut self, at: i64) -> Result<(), AuditLogError> {
+        if !self.unchecked_events {
+            return Ok(());
+        }
+
+        let signing_key = self.settings.current().jwt_secret.clone();
+        let checkpoint = match self.chain.checkpoint(at, signing_key.as_bytes()) {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(()),
+        };
+
+        self.append(&AuditLogEntry::Checkpoint(checkpoint)).await?;
+        self.unchecked_events = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        domain::{audit_chain::verify_audit_log, AuditOutcome},
+        utils::settings::Settings,
+    };
+
+    const KEY: &[u8] = b"signing-key";
+
+    fn settings_handle() -> SettingsHandle {
+        SettingsHandle::new(Settings {
+            jwt_secret: "signing-key".to_owned(),
+            ..Settings::default()
+        })
+    }
+
+    fn event(at: i64, actor: &str) -> AuditEvent {
+        AuditEvent {
+            at,
+            action: "POST /login".to_owned(),
+            actor: Some(actor.to_owned()),
+            ip: Some("127.0.0.1".to_owned()),
+            user_agent: Some("test".to_owned()),
+            outcome: AuditOutcome::Failure,
+            status: 401,
+        }
+    }
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("audit-{}.jsonl", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_record_and_query_across_reopen() {
+        let path = temp_path();
+
+        let mut audit_log = JsonLinesAuditLog::open(&path, settings_handle()).await.unwrap();
+        audit_log.record(event(1, "a@example.com")).await.unwrap();
+        audit_log.record(event(2, "b@example.com")).await.unwrap();
+        drop(audit_log);
+
+        // Reopening appends instead of truncating
+        let mut audit_log = JsonLinesAuditLog::open(&path, settings_handle()).await.unwrap();
+        audit_log.record(event(3, "a@example.com")).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+
+        let events = audit_log
+            .query(&AuditQuery {
+                actor: Some("a@example.com".to_owned()),
+                ..AuditQuery::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            events,
+            vec![event(3, "a@example.com"), event(1, "a@example.com")]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_log_stays_verifiable_across_reopen_and_checkpoints() {
+        let path = temp_path();
+
+        let mut audit_log = JsonLinesAuditLog::open(&path, settings_handle()).await.unwrap();
+        // An empty log has nothing to checkpoint
+        audit_log.checkpoint(0).await.unwrap();
+        audit_log.record(event(1, "a@example.com")).await.unwrap();
+        audit_log.checkpoint(1).await.unwrap();
+        // Nor does a log with no new events since its last checkpoint
+        audit_log.checkpoint(2).await.unwrap();
+        audit_log.record(event(3, "a@example.com")).await.unwrap();
+        drop(audit_log);
+
+        let mut audit_log = JsonLinesAuditLog::open(&path, settings_handle()).await.unwrap();
+        audit_log.record(event(4, "b@example.com")).await.unwrap();
+        audit_log.checkpoint(5).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let summary = verify_audit_log(contents.lines(), &[KEY]).unwrap();
+        assert_eq!(summary.events, 3);
+        assert_eq!(summary.checkpoints, 2);
+
+        assert_eq!(
+            audit_log.query(&AuditQuery::default()).await.unwrap().len(),
+            3
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_are_signed_with_the_current_secret() {
+        let path = temp_path();
+        let settings = settings_handle();
+
+        let mut audit_log = JsonLinesAuditLog::open(&path, settings.clone()).await.unwrap();
+        audit_log.record(event(1, "a@example.com")).await.unwrap();
+        audit_log.checkpoint(1).await.unwrap();
+
+        settings
+            .apply(Settings {
+                jwt_secret: "rotated-key".to_owned(),
+                ..settings.current().as_ref().clone()
+            })
+            .unwrap();
+        audit_log.record(event(2, "a@example.com")).await.unwrap();
+        audit_log.checkpoint(2).await.unwrap();
+
+        // Once the old secret is retired, only checkpoints signed since the rotation verify
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(verify_audit_log(contents.lines(), &[b"rotated-key".as_slice()]).is_err());
+        let summary = verify_audit_log(contents.lines(), &[KEY, b"rotated-key".as_slice()]).unwrap();
+        assert_eq!(summary.checkpoints, 2);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
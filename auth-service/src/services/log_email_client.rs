@@ -0,0 +1,20 @@
+use crate::domain::{Email, EmailClient};
+
+// Logs emails instead of delivering them, for running locally without an email provider.
+// Nothing is kept, so the codes and links they hold don't pile up in memory.
+#[derive(Default)]
+pub struct LogEmailClient;
+
+#[async_trait::async_trait]
+impl EmailClient for LogEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String> {
+        tracing::info!(recipient = recipient.as_ref(), subject, "sending email");
+        // The content holds codes and links, so it's only shown when debugging locally
+        tracing::debug!(content, "email content");
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
@@ -2,4 +2,11 @@ pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
 pub use hashmap_user_store::HashmapUserStore;
 pub use hashset_banned_token_store::HashsetBannedTokenStore;
-pub mod hashmap_two_fa_code_store;
\ No newline at end of file
+pub mod hashmap_two_fa_code_store;
+pub mod hashmap_verification_token_store;
+pub mod log_email_client;
+pub mod postmark_email_client;
+pub mod hashmap_rate_limit_store;
+pub mod in_memory_audit_log;
+pub mod json_lines_audit_log;
+pub mod instrumented_store;
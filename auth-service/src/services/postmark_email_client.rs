@@ -0,0 +1,194 @@
+use std::{fmt, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{Email, EmailClient};
+
+// The Postmark server emails are sent through, and the address they're sent from
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmailSettings {
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    pub sender: String,
+    // The Postmark server API token
+    pub authorization_token: String,
+    #[serde(default = "default_timeout_milliseconds")]
+    pub timeout_milliseconds: u64,
+}
+
+pub fn default_base_url() -> String {
+    "https://api.postmarkapp.com".to_owned()
+}
+
+pub fn default_timeout_milliseconds() -> u64 {
+    10_000
+}
+
+// Keeps the token out of logs and panic messages
+impl fmt::Debug for EmailSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmailSettings")
+            .field("base_url", &self.base_url)
+            .field("sender", &self.sender)
+            .field("authorization_token", &"[REDACTED]")
+            .field("timeout_milliseconds", &self.timeout_milliseconds)
+            .finish()
+    }
+}
+
+// Delivers emails through Postmark's HTTP API
+pub struct PostmarkEmailClient {
+    http_client: reqwest::Client,
+    base_url: String,
+    sender: Email,
+    authorization_token: String,
+}
+
+impl PostmarkEmailClient {
+    pub fn new(settings: &EmailSettings) -> Result<Self, String> {
+        let sender = Email::parse(settings.sender.clone()).map_err(|e| e.to_string())?;
+        let http_client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(settings.timeout_milliseconds))
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            http_client,
+            base_url: settings.base_url.trim_end_matches('/').to_owned(),
+            sender,
+            authorization_token: settings.authorization_token.clone(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for PostmarkEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String> {
+        let request_body = SendEmailRequest {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            text_body: content,
+            message_stream: "outbound",
+        };
+
+        self.http_client
+            .post(format!("{}/email", self.base_url))
+            .header("X-Postmark-Server-Token", &self.authorization_token)
+            .json(&request_body)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| e.to_string())?;
+
+        tracing::info!(recipient = recipient.as_ref(), subject, "sent email");
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        // Postmark is only called to send, so an outage fails those requests rather than
+        // taking every other endpoint out of rotation
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    text_body: &'a str,
+    message_stream: &'a str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::{extract::State, http::{HeaderMap, StatusCode}, routing::post, Json, Router};
+
+    use super::*;
+
+    type Received = Arc<Mutex<Vec<(HeaderMap, serde_json::Value)>>>;
+
+    // Serves a stand-in for Postmark that answers with `status`, returning its base URL
+    async fn spawn_postmark(status: StatusCode, received: Received) -> String {
+        let app = Router::new()
+            .route(
+                "/email",
+                post(
+                    move |State(received): State<Received>,
+                          headers: HeaderMap,
+                          Json(body): Json<serde_json::Value>| async move {
+                        received.lock().unwrap().push((headers, body));
+                        status
+                    },
+                ),
+            )
+            .with_state(received);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", address)
+    }
+
+    fn client(base_url: String) -> PostmarkEmailClient {
+        PostmarkEmailClient::new(&EmailSettings {
+            base_url,
+            sender: "sender@example.com".to_owned(),
+            authorization_token: "server-token".to_owned(),
+            timeout_milliseconds: 1_000,
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_email_posts_to_postmark() {
+        let received = Received::default();
+        let base_url = spawn_postmark(StatusCode::OK, received.clone()).await;
+        let recipient = Email::parse("user@example.com".to_owned()).unwrap();
+
+        client(base_url)
+            .send_email(&recipient, "Subject", "Content")
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        let (headers, body) = &received[0];
+        assert_eq!(headers["X-Postmark-Server-Token"], "server-token");
+        assert_eq!(
+            *body,
+            serde_json::json!({
+                "From": "sender@example.com",
+                "To": "user@example.com",
+                "Subject": "Subject",
+                "TextBody": "Content",
+                "MessageStream": "outbound"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_email_fails_if_postmark_rejects_it() {
+        let base_url = spawn_postmark(StatusCode::UNPROCESSABLE_ENTITY, Received::default()).await;
+        let recipient = Email::parse("user@example.com".to_owned()).unwrap();
+
+        assert!(client(base_url)
+            .send_email(&recipient, "Subject", "Content")
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_debug_output_is_redacted() {
+        let settings = EmailSettings {
+            base_url: default_base_url(),
+            sender: "sender@example.com".to_owned(),
+            authorization_token: "server-token".to_owned(),
+            timeout_milliseconds: default_timeout_milliseconds(),
+        };
+        assert!(!format!("{:?}", settings).contains("server-token"));
+    }
+}
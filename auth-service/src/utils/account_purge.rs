@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email},
+};
+
+use super::{constants::ACCOUNT_PURGE_INTERVAL_SECONDS, shutdown::ShutdownHandle};
+
+// Removes every account whose deletion grace period ended at or before `now`,
+// along with its pending 2FA code, verification tokens and sessions.
+pub async fn purge_deleted_accounts(
+    state: &AppState,
+    now: i64,
+) -> Result<Vec<Email>, AuthAPIError> {
+    let purged = {
+        // Holding the user store lock keeps a concurrent login from restoring
+        // an account between it being selected and being removed.
+        let mut user_store = state.user_store.write().await;
+        let mut two_fa_code_store = state.two_fa_code_store.write().await;
+
+        let due = user_store
+            .get_users_due_for_deletion(now)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        for email in due.iter() {
+            user_store
+                .delete_user(email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+            two_fa_code_store
+                .remove_codes_for(email)
+                .await
+                .map_err(|_| AuthAPIError::UnexpectedError)?;
+        }
+
+        due
+    };
+
+    for email in purged.iter() {
+        state
+            .verification_token_store
+            .write()
+            .await
+            .remove_tokens_for(email)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+
+        state
+            .banned_token_store
+            .write()
+            .await
+            .ban_subject(email.as_ref().to_owned(), now)
+            .await
+            .map_err(|_| AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(purged)
+}
+
+// Runs `purge_deleted_accounts` once every `ACCOUNT_PURGE_INTERVAL_SECONDS` until shutdown
+pub async fn run_account_purge(state: AppState, shutdown: ShutdownHandle) {
+    let mut interval = tokio::time::interval(Duration::from_secs(ACCOUNT_PURGE_INTERVAL_SECONDS));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.requested() => return,
+        }
+
+        match purge_deleted_accounts(&state, Utc::now().timestamp()).await {
+            Ok(purged) if !purged.is_empty() => {
+                tracing::info!(count = purged.len(), "purged deleted accounts")
+            }
+            Ok(_) => {}
+            Err(_) => tracing::error!("failed to purge deleted accounts"),
+        }
+    }
+}
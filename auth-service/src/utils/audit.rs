@@ -0,0 +1,112 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuditEvent, AuditOutcome, Email},
+    utils::{
+        constants::AUDIT_CHECKPOINT_INTERVAL_SECONDS, rate_limit::request_client_ip,
+        shutdown::ShutdownHandle,
+    },
+};
+
+// Lets a route name the account a request acted on. The `audit` middleware
+// records it with the event once the route has responded.
+#[derive(Clone, Default)]
+pub struct AuditActor(Arc<Mutex<Option<String>>>);
+
+impl AuditActor {
+    pub fn set(&self, email: &Email) {
+        if let Ok(mut actor) = self.0.lock() {
+            *actor = Some(email.as_ref().to_owned());
+        }
+    }
+
+    fn get(&self) -> Option<String> {
+        self.0.lock().ok().and_then(|actor| actor.clone())
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuditActor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Outside of the `audit` middleware the actor goes nowhere
+        Ok(parts
+            .extensions
+            .get::<AuditActor>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+// Middleware recording an `AuditEvent` for every request to a route
+pub async fn audit(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| request.uri().path().to_owned());
+    let action = format!("{} {}", request.method(), path);
+    let ip = request_client_ip(&request, &state.settings.current().rate_limits.trusted_proxies)
+        .map(|ip| ip.to_string());
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|user_agent| user_agent.to_str().ok())
+        .map(str::to_owned);
+
+    let actor = AuditActor::default();
+    request.extensions_mut().insert(actor.clone());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16();
+    let event = AuditEvent {
+        at: Utc::now().timestamp(),
+        action,
+        actor: actor.get(),
+        ip,
+        user_agent,
+        outcome: AuditOutcome::from_status(status),
+        status,
+    };
+
+    // Failing to audit shouldn't undo what the request already did
+    if state.audit_log.write().await.record(event).await.is_err() {
+        tracing::error!("failed to record audit event");
+    }
+
+    response
+}
+
+// Writes a signed checkpoint of the audit log once every `AUDIT_CHECKPOINT_INTERVAL_SECONDS`
+// until shutdown. `Application::run` writes a final one once requests have drained.
+pub async fn run_audit_checkpoints(state: AppState, shutdown: ShutdownHandle) {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(AUDIT_CHECKPOINT_INTERVAL_SECONDS));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.requested() => return,
+        }
+
+        let now = Utc::now().timestamp();
+        if state.audit_log.write().await.checkpoint(now).await.is_err() {
+            tracing::error!("failed to write audit log checkpoint");
+        }
+    }
+}
@@ -1,48 +1,167 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::http::HeaderMap;
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
 use chrono::Utc;
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::str::FromStr;
+use uuid::Uuid;
 
 use crate::{
     app_state::BannedTokenStoreType,
-    domain::email::Email
+    domain::{email::Email, AuthAPIError, Session},
+};
+
+use super::{
+    constants::{ADMIN_API_KEY_HEADER, CSRF_TOKEN_HEADER, PASSWORD_CHANGE_TOKEN_TTL_SECONDS},
+    settings::Settings,
 };
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+type HmacSha256 = Hmac<Sha256>;
+
+// Create cookie with a new JWT auth token, returning the token's claims alongside it
+pub fn generate_auth_cookie(
+    email: &Email,
+    settings: &Settings,
+) -> Result<(Cookie<'static>, Claims), GenerateTokenError> {
+    let (token, claims) = generate_auth_token(email, settings)?;
+    Ok((create_auth_cookie(token, claims.exp as i64, settings), claims))
+}
+
+// Like `generate_auth_cookie`, for a user who has to change their password before doing anything
+// else. The token only lasts a few minutes and is only accepted by `authenticate_for_password_change`.
+pub fn generate_password_change_cookie(
+    email: &Email,
+    settings: &Settings,
+) -> Result<(Cookie<'static>, Claims), GenerateTokenError> {
+    let (token, claims) = generate_token(
+        email,
+        TokenScope::ChangePassword,
+        PASSWORD_CHANGE_TOKEN_TTL_SECONDS,
+        settings,
+    )?;
+    Ok((create_auth_cookie(token, claims.exp as i64, settings), claims))
+}
+
+// Create cookie and set the value to the passed-in token string.
+// It expires at `exp` along with the token, rather than lasting the whole browser session.
+fn create_auth_cookie(token: String, exp: i64, settings: &Settings) -> Cookie<'static> {
+    let mut cookie = auth_cookie(settings.auth_cookie_name(), token, settings);
+    cookie.set_max_age(max_age(exp));
+    cookie
+}
+
+// Create the double-submit CSRF cookie for the session `claims` belong to. Unlike the auth
+// cookie, the page's JavaScript can read it, to send it back in the CSRF header.
+pub fn generate_csrf_cookie(claims: &Claims, settings: &Settings) -> Cookie<'static> {
+    let token = csrf_token(&claims.jti, &settings.jwt_secret);
+    let mut cookie = auth_cookie(settings.csrf_cookie_name(), token, settings);
+    cookie.set_http_only(false);
+    cookie.set_max_age(max_age(claims.exp as i64));
+    cookie
+}
 
-// Create cookie with a new JWT auth token
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>, GenerateTokenError> {
-    let token = generate_auth_token(email)?;
-    Ok(create_auth_cookie(token))
+// Logs the browser out. Browsers only remove a cookie when the removal repeats
+// the path and domain it was set with.
+pub fn remove_auth_cookies(jar: CookieJar, settings: &Settings) -> CookieJar {
+    jar.remove(auth_cookie(settings.auth_cookie_name(), String::new(), settings))
+        .remove(auth_cookie(settings.csrf_cookie_name(), String::new(), settings))
 }
 
-// Create cookie and set the value to the passed-in token string 
-fn create_auth_cookie(token: String) -> Cookie<'static> {
-    let cookie = Cookie::build((JWT_COOKIE_NAME, token))
+fn auth_cookie(name: String, value: String, settings: &Settings) -> Cookie<'static> {
+    let mut cookie = Cookie::build((name, value))
         .path("/") // apple cookie to all URLs on the server
         .http_only(true) // prevent JavaScript from accessing the cookie
-        .same_site(SameSite::Lax) // send cookie with "same-site" requests, and with "cross-site" top-level navigations.
+        .same_site(settings.jwt_cookie_same_site.into())
+        .secure(settings.auth_cookie_secure())
         .build();
+    if let Some(domain) = &settings.jwt_cookie_domain {
+        cookie.set_domain(domain.clone());
+    }
 
     cookie
 }
 
+// Until `exp`, or already expired
+fn max_age(exp: i64) -> time::Duration {
+    time::Duration::seconds((exp - Utc::now().timestamp()).max(0))
+}
+
+// Derived from the session ID, so a CSRF cookie planted by another site (e.g. a sibling
+// subdomain) doesn't match the session it is sent with
+fn csrf_token(session_id: &str, secret: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC can take a key of any size");
+    mac.update(b"csrf:");
+    mac.update(session_id.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Which cross-site requests browsers send the auth cookie with
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CookieSameSite {
+    // Only requests from our own site
+    Strict,
+    // Also top-level navigations from other sites, e.g. following a link
+    Lax,
+    // Every request. The cookie must be secure.
+    None,
+}
+
+impl FromStr for CookieSameSite {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "strict" => Ok(CookieSameSite::Strict),
+            "lax" => Ok(CookieSameSite::Lax),
+            "none" => Ok(CookieSameSite::None),
+            _ => Err(format!("Unknown SameSite value: {}", s)),
+        }
+    }
+}
+
+impl From<CookieSameSite> for SameSite {
+    fn from(same_site: CookieSameSite) -> Self {
+        match same_site {
+            CookieSameSite::Strict => SameSite::Strict,
+            CookieSameSite::Lax => SameSite::Lax,
+            CookieSameSite::None => SameSite::None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
     UnexpectedError,
 }
 
-// This value determines how long the JWT auth token is valid for
-pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
+// Create JWT auth token, valid for `token_ttl_seconds`
+fn generate_auth_token(
+    email: &Email,
+    settings: &Settings,
+) -> Result<(String, Claims), GenerateTokenError> {
+    generate_token(email, TokenScope::Full, settings.token_ttl_seconds, settings)
+}
 
-// Create JWT auth token
-fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
+fn generate_token(
+    email: &Email,
+    scope: TokenScope,
+    ttl_seconds: i64,
+    settings: &Settings,
+) -> Result<(String, Claims), GenerateTokenError> {
+    let delta = chrono::Duration::try_seconds(ttl_seconds)
         .ok_or(GenerateTokenError::UnexpectedError)?;
 
+    let now = Utc::now();
+
     // Create JWT expiration time
-    let exp = Utc::now()
+    let exp = now
         .checked_add_signed(delta)
         .ok_or(GenerateTokenError::UnexpectedError)?
         .timestamp();
@@ -52,17 +171,49 @@ fn generate_auth_token(email: &Email) -> Result<String, GenerateTokenError> {
         .try_into()
         .map_err(|_| GenerateTokenError::UnexpectedError)?;
 
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .map_err(|_| GenerateTokenError::UnexpectedError)?;
+
     let sub = email.as_ref().to_owned();
 
-    let claims = Claims { sub, exp };
+    // Every token gets a unique ID so the session it belongs to can be tracked
+    let jti = Uuid::new_v4().to_string();
+
+    let claims = Claims {
+        sub,
+        exp,
+        iat,
+        jti,
+        scope,
+    };
 
-    create_token(&claims).map_err(GenerateTokenError::TokenError)
+    let token = create_token(&claims, settings).map_err(GenerateTokenError::TokenError)?;
+    Ok((token, claims))
 }
 
-// Check if JWT auth token is valid by decoding it using the JWT secret
+// Check if JWT auth token is valid by decoding it using the JWT secrets. Tokens that can only
+// change the password aren't.
 pub async fn validate_token(
     token: &str,
-    banned_token_store: BannedTokenStoreType
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
+) -> Result<Claims, jsonwebtoken::errors::Error> {
+    let claims = validate_token_of_any_scope(token, banned_token_store, settings).await?;
+    match claims.scope {
+        TokenScope::Full => Ok(claims),
+        TokenScope::ChangePassword => Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        )),
+    }
+}
+
+// Like `validate_token`, but also accepts tokens that can only change the password, e.g. to log out
+pub async fn validate_token_of_any_scope(
+    token: &str,
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
 ) -> Result<Claims, jsonwebtoken::errors::Error> {
     match banned_token_store.read().await.is_token_banned(token.to_string()).await {
         Ok(is_banned) => {
@@ -79,20 +230,144 @@ pub async fn validate_token(
         }
     }
 
-    decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_bytes()),
-        &Validation::default(),
-    )
-    .map(|data| data.claims)
+    // Tokens signed with a previous secret are still accepted, so the secret can be rotated
+    let mut decoded = Err(jsonwebtoken::errors::ErrorKind::InvalidSignature.into());
+    for secret in settings.jwt_secrets() {
+        decoded = decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(secret.as_bytes()),
+            &Validation::default(),
+        );
+        if !matches!(&decoded, Err(e) if *e.kind() == jsonwebtoken::errors::ErrorKind::InvalidSignature) {
+            break;
+        }
+    }
+    let claims = decoded.map(|data| data.claims)?;
+
+    // Tokens issued to a subject before it was banned (e.g. an old email address) are no longer valid
+    match banned_token_store
+        .read()
+        .await
+        .is_subject_banned(&claims.sub, claims.iat as i64)
+        .await
+    {
+        Ok(false) => Ok(claims),
+        _ => Err(jsonwebtoken::errors::Error::from(
+            jsonwebtoken::errors::ErrorKind::InvalidToken,
+        )),
+    }
+}
+
+// Validate the JWT from the auth cookie, returning the raw token along with its claims
+pub async fn authenticate(
+    jar: &CookieJar,
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
+) -> Result<(String, Claims), AuthAPIError> {
+    let (token, claims) = authenticate_any_scope(jar, banned_token_store, settings).await?;
+    match claims.scope {
+        TokenScope::Full => Ok((token, claims)),
+        // The user logged in, but has to change their password first
+        TokenScope::ChangePassword => Err(AuthAPIError::PasswordChangeRequired),
+    }
+}
+
+async fn authenticate_any_scope(
+    jar: &CookieJar,
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
+) -> Result<(String, Claims), AuthAPIError> {
+    let token = jar
+        .get(&settings.auth_cookie_name())
+        .ok_or(AuthAPIError::MissingToken)?
+        .value()
+        .to_owned();
+
+    let claims = validate_token_of_any_scope(&token, banned_token_store, settings)
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    Ok((token, claims))
+}
+
+// Like `authenticate`, for requests that change state. These must also send the CSRF
+// cookie's value in the CSRF header, which other sites can't read or set.
+pub async fn authenticate_with_csrf(
+    jar: &CookieJar,
+    headers: &HeaderMap,
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
+) -> Result<(String, Claims), AuthAPIError> {
+    let (token, claims) = authenticate(jar, banned_token_store, settings).await?;
+    verify_csrf_token(jar, headers, &claims, settings)?;
+    Ok((token, claims))
+}
+
+// Like `authenticate_with_csrf`, but also accepts the token issued at login to a user who has to
+// change their password
+pub async fn authenticate_for_password_change(
+    jar: &CookieJar,
+    headers: &HeaderMap,
+    banned_token_store: BannedTokenStoreType,
+    settings: &Settings,
+) -> Result<(String, Claims), AuthAPIError> {
+    let (token, claims) = authenticate_any_scope(jar, banned_token_store, settings).await?;
+    verify_csrf_token(jar, headers, &claims, settings)?;
+    Ok((token, claims))
+}
+
+// Check the CSRF header against the CSRF cookie and the session the auth token belongs to
+pub fn verify_csrf_token(
+    jar: &CookieJar,
+    headers: &HeaderMap,
+    claims: &Claims,
+    settings: &Settings,
+) -> Result<(), AuthAPIError> {
+    let header = headers
+        .get(CSRF_TOKEN_HEADER)
+        .ok_or(AuthAPIError::InvalidCsrfToken)?
+        .as_bytes();
+    let cookie = jar
+        .get(&settings.csrf_cookie_name())
+        .ok_or(AuthAPIError::InvalidCsrfToken)?;
+
+    // Sessions started before the JWT secret was rotated keep working
+    let issued_for_session = settings
+        .jwt_secrets()
+        .any(|secret| constant_time_eq(csrf_token(&claims.jti, secret).as_bytes(), header));
+
+    if issued_for_session && constant_time_eq(cookie.value().as_bytes(), header) {
+        Ok(())
+    } else {
+        Err(AuthAPIError::InvalidCsrfToken)
+    }
+}
+
+// Check the admin API key header against the configured key, comparing in constant time
+pub fn authenticate_admin(headers: &HeaderMap, admin_api_key: Option<&str>) -> Result<(), AuthAPIError> {
+    let provided = headers
+        .get(ADMIN_API_KEY_HEADER)
+        .ok_or(AuthAPIError::MissingToken)?
+        .as_bytes();
+    let expected = admin_api_key.ok_or(AuthAPIError::InvalidToken)?.as_bytes();
+
+    if constant_time_eq(provided, expected) {
+        Ok(())
+    } else {
+        Err(AuthAPIError::InvalidToken)
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
 }
 
 // Create JWT auth token by encoding claims using the JWT secret
-fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+fn create_token(claims: &Claims, settings: &Settings) -> Result<String, jsonwebtoken::errors::Error> {
     encode(
         &jsonwebtoken::Header::default(),
         &claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_bytes()),
+        &EncodingKey::from_secret(settings.jwt_secret.as_bytes()),
     )
 }
 
@@ -100,6 +375,32 @@ fn create_token(claims: &Claims) -> Result<String, jsonwebtoken::errors::Error>
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    pub iat: usize,
+    pub jti: String,
+    // Tokens issued before scopes were introduced can do anything
+    #[serde(default)]
+    pub scope: TokenScope,
+}
+
+// What a token can be used for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    #[default]
+    Full,
+    // Only changing the password, which the user has to do before anything else
+    ChangePassword,
+}
+
+impl Claims {
+    // The session this token was issued for
+    pub fn session(&self) -> Session {
+        Session {
+            id: self.jti.clone(),
+            created_at: self.iat as i64,
+            expires_at: self.exp as i64,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -109,44 +410,163 @@ mod tests {
 
     use crate::services::hashset_banned_token_store::HashsetBannedTokenStore;
     use crate::domain::BannedTokenStore;
+    use crate::utils::{constants::CSRF_COOKIE_NAME, tls::TlsSettings};
+    use axum::{
+        http::header::{COOKIE, SET_COOKIE},
+        response::IntoResponse,
+    };
 
     use super::*;
 
+    fn settings() -> Settings {
+        Settings {
+            jwt_secret: "secret".to_owned(),
+            ..Settings::default()
+        }
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
-        assert_eq!(cookie.name(), JWT_COOKIE_NAME);
+        let (cookie, claims) = generate_auth_cookie(&email, &settings()).unwrap();
+        assert_eq!(claims.sub, "test@example.com");
+        assert_eq!(cookie.name(), settings().jwt_cookie_name);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        // Expires along with the token
+        let max_age = cookie.max_age().unwrap().whole_seconds();
+        assert!((settings().token_ttl_seconds - 1..=settings().token_ttl_seconds).contains(&max_age));
     }
 
     #[tokio::test]
     async fn test_create_auth_cookie() {
         let token = "test_token".to_owned();
-        let cookie = create_auth_cookie(token.clone());
-        assert_eq!(cookie.name(), JWT_COOKIE_NAME);
+        let exp = Utc::now().timestamp() + 300;
+        let cookie = create_auth_cookie(token.clone(), exp, &settings());
+        assert_eq!(cookie.name(), settings().jwt_cookie_name);
         assert_eq!(cookie.value(), token);
         assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.domain(), None);
         assert_eq!(cookie.http_only(), Some(true));
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_eq!(cookie.secure(), Some(false));
+        assert!(cookie.max_age().unwrap().whole_seconds() <= 300);
+
+        // A token that has already expired gets a cookie that does too
+        let cookie = create_auth_cookie(token, exp - 600, &settings());
+        assert_eq!(cookie.max_age().unwrap().whole_seconds(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_auth_cookie_is_secure_when_serving_https() {
+        let settings = Settings {
+            tls: Some(TlsSettings {
+                cert_path: "cert.pem".into(),
+                key_path: "key.pem".into(),
+            }),
+            ..settings()
+        };
+        let cookie = create_auth_cookie("test_token".to_owned(), 0, &settings);
+        assert_eq!(cookie.secure(), Some(true));
+
+        // Unless configured otherwise
+        let settings = Settings {
+            jwt_cookie_secure: Some(false),
+            ..settings
+        };
+        let cookie = create_auth_cookie("test_token".to_owned(), 0, &settings);
+        assert_eq!(cookie.secure(), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_auth_cookie_attributes_are_configurable() {
+        let settings = Settings {
+            jwt_cookie_domain: Some("example.com".to_owned()),
+            jwt_cookie_secure: Some(true),
+            jwt_cookie_same_site: CookieSameSite::Strict,
+            ..settings()
+        };
+        let cookie = create_auth_cookie("test_token".to_owned(), 0, &settings);
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+
+        // Removing the cookies only works with the same domain and path
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(COOKIE, "jwt=token; csrf_token=token".parse().unwrap());
+        let jar = CookieJar::from_headers(&request_headers);
+        let response = remove_auth_cookies(jar, &settings).into_response();
+        let removals: Vec<&str> = response
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| value.to_str().unwrap())
+            .collect();
+        assert_eq!(removals.len(), 2);
+        for removal in removals {
+            assert!(removal.contains("Domain=example.com"));
+            assert!(removal.contains("Path=/"));
+            assert!(removal.contains("Max-Age=0"));
+        }
+
+        let settings = Settings {
+            jwt_cookie_domain: None,
+            jwt_cookie_host_prefix: true,
+            ..settings
+        };
+        let cookie = create_auth_cookie("test_token".to_owned(), 0, &settings);
+        assert_eq!(cookie.name(), "__Host-jwt");
+        assert_eq!(settings.auth_cookie_name(), "__Host-jwt");
+        assert_eq!(settings.csrf_cookie_name(), "__Host-csrf_token");
+    }
+
+    #[tokio::test]
+    async fn test_csrf_cookie_is_readable_and_bound_to_the_session() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (auth_cookie, claims) = generate_auth_cookie(&email, &settings()).unwrap();
+        let csrf_cookie = generate_csrf_cookie(&claims, &settings());
+        assert_eq!(csrf_cookie.name(), CSRF_COOKIE_NAME);
+        assert_eq!(csrf_cookie.http_only(), Some(false));
+        assert_eq!(csrf_cookie.max_age(), auth_cookie.max_age());
+
+        let csrf_header = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(CSRF_TOKEN_HEADER, value.parse().unwrap());
+            headers
+        };
+        let jar = CookieJar::new().add(csrf_cookie.clone());
+
+        let headers = csrf_header(csrf_cookie.value());
+        assert!(verify_csrf_token(&jar, &headers, &claims, &settings()).is_ok());
+
+        // The header must be sent, and match the cookie
+        let result = verify_csrf_token(&jar, &HeaderMap::new(), &claims, &settings());
+        assert!(matches!(result, Err(AuthAPIError::InvalidCsrfToken)));
+        let result = verify_csrf_token(&jar, &csrf_header("forged"), &claims, &settings());
+        assert!(matches!(result, Err(AuthAPIError::InvalidCsrfToken)));
+
+        // A matching cookie and header that were issued for another session are rejected
+        let (_, other_claims) = generate_auth_cookie(&email, &settings()).unwrap();
+        let result = verify_csrf_token(&jar, &headers, &other_claims, &settings());
+        assert!(matches!(result, Err(AuthAPIError::InvalidCsrfToken)));
     }
 
     #[tokio::test]
     async fn test_generate_auth_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let (result, claims) = generate_auth_token(&email, &settings()).unwrap();
         assert_eq!(result.split('.').count(), 3);
+        assert!(Uuid::parse_str(&claims.jti).is_ok());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_valid_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (token, _) = generate_auth_token(&email, &settings()).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let result = validate_token(&token, banned_token_store, &settings()).await.unwrap();
         assert_eq!(result.sub, "test@example.com");
 
         let exp = Utc::now()
@@ -157,21 +577,102 @@ mod tests {
         assert!(result.exp > exp as usize);
     }
 
+    #[tokio::test]
+    async fn test_validate_token_signed_with_previous_secret() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (token, _) = generate_auth_token(&email, &settings()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let mut rotated = Settings {
+            jwt_secret: "new-secret".to_owned(),
+            ..settings()
+        };
+        assert!(validate_token(&token, banned_token_store.clone(), &rotated)
+            .await
+            .is_err());
+
+        rotated.jwt_previous_secrets = vec![settings().jwt_secret];
+        let result = validate_token(&token, banned_token_store, &rotated).await.unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_password_change_token_is_short_lived_and_restricted() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (cookie, claims) = generate_password_change_cookie(&email, &settings()).unwrap();
+        assert_eq!(claims.scope, TokenScope::ChangePassword);
+        assert!(cookie.max_age().unwrap().whole_seconds() <= PASSWORD_CHANGE_TOKEN_TTL_SECONDS);
+
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_token(cookie.value(), banned_token_store.clone(), &settings()).await;
+        assert!(result.is_err());
+        let result =
+            validate_token_of_any_scope(cookie.value(), banned_token_store.clone(), &settings())
+                .await;
+        assert_eq!(result.unwrap().jti, claims.jti);
+
+        let csrf_cookie = generate_csrf_cookie(&claims, &settings());
+        let mut headers = HeaderMap::new();
+        headers.insert(CSRF_TOKEN_HEADER, csrf_cookie.value().parse().unwrap());
+        let jar = CookieJar::new().add(cookie).add(csrf_cookie);
+
+        let result = authenticate_with_csrf(&jar, &headers, banned_token_store.clone(), &settings()).await;
+        assert!(matches!(result, Err(AuthAPIError::PasswordChangeRequired)));
+        let result =
+            authenticate_for_password_change(&jar, &headers, banned_token_store, &settings()).await;
+        assert!(result.is_ok());
+    }
+
     #[tokio::test]
     async fn test_validate_token_with_invalid_token() {
         let token = "invalid_token".to_owned();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(&token, banned_token_store, &settings()).await;
         assert!(result.is_err());
     }
 
     #[tokio::test]
     async fn test_validate_token_with_banned_token() {
         let email = Email::parse("test@example.com".to_owned()).unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (token, _) = generate_auth_token(&email, &settings()).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
         banned_token_store.write().await.ban_token(token.clone()).await.unwrap();
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_token(&token, banned_token_store, &settings()).await;
         assert!(result.is_err());
     }
-}
+
+    #[tokio::test]
+    async fn test_validate_token_with_banned_subject() {
+        let email = Email::parse("test@example.com".to_owned()).unwrap();
+        let (token, _) = generate_auth_token(&email, &settings()).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        banned_token_store
+            .write()
+            .await
+            .ban_subject(email.as_ref().to_owned(), Utc::now().timestamp())
+            .await
+            .unwrap();
+        let result = validate_token(&token, banned_token_store, &settings()).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_authenticate_admin() {
+        let mut headers = HeaderMap::new();
+        assert!(matches!(
+            authenticate_admin(&headers, Some("key")),
+            Err(AuthAPIError::MissingToken)
+        ));
+
+        headers.insert(ADMIN_API_KEY_HEADER, "key".parse().unwrap());
+        assert!(authenticate_admin(&headers, Some("key")).is_ok());
+        assert!(matches!(
+            authenticate_admin(&headers, Some("other-key")),
+            Err(AuthAPIError::InvalidToken)
+        ));
+        assert!(matches!(
+            authenticate_admin(&headers, None),
+            Err(AuthAPIError::InvalidToken)
+        ));
+    }
+}
\ No newline at end of file
@@ -0,0 +1,68 @@
+use std::{path::Path, time::{Duration, SystemTime}};
+
+use super::{
+    constants::CONFIG_POLL_INTERVAL_SECONDS, settings::SettingsHandle, shutdown::ShutdownHandle,
+};
+
+// Reloads the settings on SIGHUP, or once the config file has been modified, until shutdown.
+// Settings that fail to load or would need a restart are logged and the current ones are kept.
+pub async fn run_config_reload(settings: SettingsHandle, shutdown: ShutdownHandle) {
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .expect("Failed to listen for SIGHUP");
+
+    let mut interval = tokio::time::interval(Duration::from_secs(CONFIG_POLL_INTERVAL_SECONDS));
+    let mut last_modified = match settings.config_path() {
+        Some(path) => modified_at(path).await,
+        None => None,
+    };
+
+    loop {
+        #[cfg(unix)]
+        let hangup_received = hangup.recv();
+        #[cfg(not(unix))]
+        let hangup_received = std::future::pending::<Option<()>>();
+
+        let trigger = tokio::select! {
+            _ = hangup_received => "SIGHUP",
+            _ = interval.tick() => {
+                let modified = match settings.config_path() {
+                    Some(path) => modified_at(path).await,
+                    None => continue,
+                };
+                if modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                "config file modified"
+            }
+            _ = shutdown.requested() => return,
+        };
+
+        let path = match settings.config_path() {
+            Some(path) => path.display().to_string(),
+            None => {
+                tracing::warn!(trigger, "no config file to reload the configuration from");
+                continue;
+            }
+        };
+
+        match settings.reload() {
+            Ok(()) => tracing::info!(trigger, path, "reloaded configuration"),
+            Err(e) => tracing::error!(
+                trigger,
+                path,
+                error = %e,
+                "rejected configuration reload, keeping the current configuration"
+            ),
+        }
+    }
+}
+
+// `None` while the file can't be read, so it's reloaded again once it can
+pub async fn modified_at(path: &Path) -> Option<SystemTime> {
+    tokio::fs::metadata(path)
+        .await
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,31 +1,86 @@
-use dotenvy::dotenv;
-use lazy_static::lazy_static;
-use std::env as std_env;
-
-// Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
-lazy_static! {
-    pub static ref JWT_SECRET: String = set_token();
-}
-
-fn set_token() -> String {
-    dotenv().ok(); // Load environment variables
-    let secret = std_env::var(env::JWT_SECRET_ENV_VAR).expect("JWT_SECRET must be set.");
-    if secret.is_empty() {
-        panic!("JWT_SECRET must not be empty.");
-    }
-    secret
-}
-
 pub mod env {
+    pub const CONFIG_PATH_ENV_VAR: &str = "AUTH_SERVICE_CONFIG";
+    pub const APP_ADDRESS_ENV_VAR: &str = "APP_ADDRESS";
+    pub const ALLOWED_ORIGINS_ENV_VAR: &str = "ALLOWED_ORIGINS";
     pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    // Comma-separated
+    pub const JWT_PREVIOUS_SECRETS_ENV_VAR: &str = "JWT_PREVIOUS_SECRETS";
+    pub const JWT_COOKIE_NAME_ENV_VAR: &str = "JWT_COOKIE_NAME";
+    pub const JWT_COOKIE_DOMAIN_ENV_VAR: &str = "JWT_COOKIE_DOMAIN";
+    pub const JWT_COOKIE_SECURE_ENV_VAR: &str = "JWT_COOKIE_SECURE";
+    pub const JWT_COOKIE_SAME_SITE_ENV_VAR: &str = "JWT_COOKIE_SAME_SITE";
+    pub const JWT_COOKIE_HOST_PREFIX_ENV_VAR: &str = "JWT_COOKIE_HOST_PREFIX";
+    pub const TOKEN_TTL_ENV_VAR: &str = "TOKEN_TTL_SECONDS";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
+    pub const ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR: &str = "ACCOUNT_DELETION_GRACE_PERIOD_SECONDS";
+    pub const PASSWORD_MIN_LENGTH_ENV_VAR: &str = "PASSWORD_MIN_LENGTH";
+    pub const PASSWORD_MAX_LENGTH_ENV_VAR: &str = "PASSWORD_MAX_LENGTH";
+    pub const PASSWORD_BLOCKLIST_PATH_ENV_VAR: &str = "PASSWORD_BLOCKLIST_PATH";
+    pub const PASSWORD_BREACHED_HASHES_PATH_ENV_VAR: &str = "PASSWORD_BREACHED_HASHES_PATH";
+    pub const PASSWORD_HISTORY_SIZE_ENV_VAR: &str = "PASSWORD_HISTORY_SIZE";
+    pub const PASSWORD_MIN_STRENGTH_SCORE_ENV_VAR: &str = "PASSWORD_MIN_STRENGTH_SCORE";
+    pub const PASSWORD_MAX_AGE_DAYS_ENV_VAR: &str = "PASSWORD_MAX_AGE_DAYS";
+    pub const TRUSTED_PROXIES_ENV_VAR: &str = "TRUSTED_PROXIES";
+    pub const ADMIN_API_KEY_ENV_VAR: &str = "ADMIN_API_KEY";
+    pub const AUDIT_LOG_PATH_ENV_VAR: &str = "AUDIT_LOG_PATH";
+    pub const LOG_FORMAT_ENV_VAR: &str = "LOG_FORMAT";
+    pub const OTLP_ENDPOINT_ENV_VAR: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+    pub const SHUTDOWN_DRAIN_TIMEOUT_ENV_VAR: &str = "SHUTDOWN_DRAIN_TIMEOUT_SECONDS";
+    pub const TLS_CERT_PATH_ENV_VAR: &str = "TLS_CERT_PATH";
+    pub const TLS_KEY_PATH_ENV_VAR: &str = "TLS_KEY_PATH";
+    pub const EMAIL_BASE_URL_ENV_VAR: &str = "EMAIL_BASE_URL";
+    pub const EMAIL_SENDER_ENV_VAR: &str = "EMAIL_SENDER";
+    pub const EMAIL_AUTHORIZATION_TOKEN_ENV_VAR: &str = "EMAIL_AUTHORIZATION_TOKEN";
 }
 
-pub const JWT_COOKIE_NAME: &str = "jwt";
+// TOML config file read when AUTH_SERVICE_CONFIG isn't set. Unlike that one, it may be missing.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
 
-pub mod prod {
-    pub const APP_ADDRESS: &str = "0.0.0.0:3000";
-}
+pub const ADMIN_API_KEY_HEADER: &str = "x-admin-api-key";
+// Generated for each request unless the caller already sent one, and echoed back in the response
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+// Error responses are RFC 7807 problem details
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+// Requests that change state with the auth cookie must repeat the CSRF cookie in this header
+pub const CSRF_TOKEN_HEADER: &str = "x-csrf-token";
+pub const CSRF_COOKIE_NAME: &str = "csrf_token";
+
+// This value determines how long email change confirmation links are valid for
+pub const EMAIL_CHANGE_TOKEN_TTL_SECONDS: i64 = 86400; // 24 hours
+
+// Failed logins allowed before the account is locked. Each further failure doubles the lockout.
+pub const LOGIN_LOCKOUT_THRESHOLD: u32 = 5;
+pub const LOGIN_LOCKOUT_BASE_SECONDS: i64 = 60; // 1 minute
+pub const LOGIN_LOCKOUT_MAX_SECONDS: i64 = 86400; // 24 hours
+
+// This value determines how long account unlock links are valid for
+pub const ACCOUNT_UNLOCK_TOKEN_TTL_SECONDS: i64 = 86400; // 24 hours
+
+// This value determines how long password reset links are valid for
+pub const PASSWORD_RESET_TOKEN_TTL_SECONDS: i64 = 3600; // 1 hour
+
+// How long the token issued at login to a user who has to change their password is valid for.
+// It can only be used to change the password.
+pub const PASSWORD_CHANGE_TOKEN_TTL_SECONDS: i64 = 300; // 5 minutes
+
+// How long a readiness probe waits for each component before reporting it unavailable
+pub const HEALTH_CHECK_TIMEOUT_MILLISECONDS: u64 = 2000;
+
+// How often a signed checkpoint of the audit log is written
+pub const AUDIT_CHECKPOINT_INTERVAL_SECONDS: u64 = 600; // 10 minutes
+
+// How often the config file and TLS certificate are checked for changes to reload
+pub const CONFIG_POLL_INTERVAL_SECONDS: u64 = 5;
+
+// How often accounts whose deletion grace period has ended are purged
+pub const ACCOUNT_PURGE_INTERVAL_SECONDS: u64 = 3600; // 1 hour
+
+// How often rate limit buckets nobody has used for their whole refill window are removed
+pub const RATE_LIMIT_PRUNE_INTERVAL_SECONDS: u64 = 60;
 
 pub mod test {
     pub const APP_ADDRESS: &str = "127.0.0.1:0";
+    pub const ADMIN_API_KEY: &str = "test-admin-api-key";
+    pub const JWT_SECRET: &str = "test-jwt-secret";
+    pub const USER_AGENT: &str = "auth-service-tests";
 }
\ No newline at end of file
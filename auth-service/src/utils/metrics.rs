@@ -0,0 +1,109 @@
+use std::{future::Future, sync::OnceLock, time::Instant};
+
+use axum::{
+    extract::{MatchedPath, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+
+use crate::domain::AuthAPIError;
+
+// Upper bounds of the latency histogram buckets, from half a millisecond to 10 seconds
+const LATENCY_BUCKETS_SECONDS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static PROMETHEUS_HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+// Installs the global Prometheus recorder the first time it's called. Metrics recorded
+// before then are dropped, so this is called while the application is being built.
+pub fn prometheus_handle() -> &'static PrometheusHandle {
+    PROMETHEUS_HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .set_buckets_for_metric(
+                Matcher::Suffix("_duration_seconds".to_owned()),
+                LATENCY_BUCKETS_SECONDS,
+            )
+            .expect("Latency buckets must not be empty")
+            .install_recorder()
+            .expect("Failed to install the Prometheus recorder")
+    })
+}
+
+// Middleware counting requests and timing them by route and status
+pub async fn track_metrics(request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let method = request.method().to_string();
+    // Unmatched paths are grouped together so clients can't create new series at will
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+
+    let response = next.run(request).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", response.status().as_u16().to_string()),
+    ];
+    metrics::counter!("http_requests_total", &labels).increment(1);
+    metrics::histogram!("http_request_duration_seconds", &labels)
+        .record(started.elapsed().as_secs_f64());
+
+    response
+}
+
+// How a login attempt ended. Accounts that need 2FA aren't logged in until `/verify-2fa`.
+pub fn record_login<T>(result: &Result<(StatusCode, T), AuthAPIError>) {
+    let outcome = match result {
+        Ok((status, _)) if *status == StatusCode::PARTIAL_CONTENT => "2fa_required",
+        Ok((status, _)) if *status == StatusCode::ACCEPTED => "password_change_required",
+        Ok(_) => "success",
+        Err(e) => auth_error_outcome(e),
+    };
+    metrics::counter!("login_attempts_total", "outcome" => outcome).increment(1);
+}
+
+// How checking a 2FA code ended, whichever flow the code was for
+pub fn record_2fa_verification<T>(flow: &'static str, result: &Result<T, AuthAPIError>) {
+    let outcome = match result {
+        Ok(_) => "success",
+        Err(e) => auth_error_outcome(e),
+    };
+    metrics::counter!("two_fa_verifications_total", "flow" => flow, "outcome" => outcome)
+        .increment(1);
+}
+
+pub fn record_banned_token(kind: &'static str) {
+    metrics::counter!("banned_tokens_total", "kind" => kind).increment(1);
+}
+
+// Runs a store operation, recording how long it took
+pub async fn time_store_operation<F: Future>(
+    store: &'static str,
+    operation: &'static str,
+    future: F,
+) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    metrics::histogram!(
+        "store_operation_duration_seconds",
+        "store" => store,
+        "operation" => operation
+    )
+    .record(started.elapsed().as_secs_f64());
+    output
+}
+
+fn auth_error_outcome(error: &AuthAPIError) -> &'static str {
+    match error {
+        AuthAPIError::IncorrectCredentials => "bad_credentials",
+        AuthAPIError::InvalidCredentials | AuthAPIError::InvalidFields(_) => "invalid_input",
+        AuthAPIError::AccountLocked { .. } => "locked",
+        _ => "error",
+    }
+}
@@ -1,5 +1,15 @@
 pub mod constants;
 pub mod auth;
+pub mod account_purge;
+pub mod audit;
+pub mod config_reload;
+pub mod metrics;
+pub mod problem;
+pub mod rate_limit;
+pub mod settings;
+pub mod shutdown;
+pub mod telemetry;
+pub mod tls;
 
 pub use constants::*;
 pub use auth::{generate_auth_cookie, validate_token};
\ No newline at end of file
@@ -0,0 +1,101 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+};
+
+use crate::ErrorResponse;
+
+use super::constants::{PROBLEM_JSON_CONTENT_TYPE, REQUEST_ID_HEADER};
+
+// Plain text error bodies are short, e.g. axum's extractor rejections
+const MAX_PLAIN_TEXT_ERROR_BYTES: usize = 16 * 1024;
+
+// Renders every error response as `application/problem+json` (RFC 7807) carrying the request ID.
+// `AuthAPIError`s keep their code; plain text and empty errors, such as axum's extractor
+// rejections or unknown routes, get a code named after their status. Error responses that are
+// already JSON, like failed readiness probes, are left alone.
+pub async fn problem_details(request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let mut problem = match parts.extensions.remove::<ErrorResponse>() {
+        Some(problem) => problem,
+        None if is_plain_text(parts.headers.get(CONTENT_TYPE)) => {
+            let body = to_bytes(body, MAX_PLAIN_TEXT_ERROR_BYTES)
+                .await
+                .unwrap_or_default();
+            ErrorResponse::from_status(status, String::from_utf8_lossy(&body).trim())
+        }
+        None => return Response::from_parts(parts, body),
+    };
+    problem.request_id = request_id;
+
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE));
+    parts.headers.remove(CONTENT_LENGTH);
+    let body = serde_json::to_vec(&problem).unwrap_or_default();
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn is_plain_text(content_type: Option<&HeaderValue>) -> bool {
+    match content_type.and_then(|value| value.to_str().ok()) {
+        Some(content_type) => content_type.starts_with("text/plain"),
+        None => true,
+    }
+}
+
+impl ErrorResponse {
+    pub fn new(status: StatusCode, code: &str, detail: &str) -> Self {
+        Self {
+            problem_type: "about:blank".to_owned(),
+            title: status.canonical_reason().unwrap_or("Error").to_owned(),
+            status: status.as_u16(),
+            code: code.to_owned(),
+            detail: detail.to_owned(),
+            request_id: None,
+            errors: Vec::new(),
+        }
+    }
+
+    // For errors that only come with a status, e.g. "unprocessable_entity" for 422
+    fn from_status(status: StatusCode, detail: &str) -> Self {
+        let reason = status.canonical_reason().unwrap_or("Error");
+        let code = reason.to_lowercase().replace([' ', '-'], "_");
+        let detail = if detail.is_empty() { reason } else { detail };
+        Self::new(status, &code, detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_is_named_after_status() {
+        let problem = ErrorResponse::from_status(StatusCode::UNPROCESSABLE_ENTITY, "missing field");
+        assert_eq!(problem.code, "unprocessable_entity");
+        assert_eq!(problem.title, "Unprocessable Entity");
+        assert_eq!(problem.detail, "missing field");
+
+        let problem = ErrorResponse::from_status(StatusCode::NOT_FOUND, "");
+        assert_eq!(problem.code, "not_found");
+        assert_eq!(problem.detail, "Not Found");
+    }
+}
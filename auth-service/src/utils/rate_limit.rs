@@ -0,0 +1,355 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use serde::Deserialize;
+
+use crate::{
+    app_state::{AppState, RateLimitStoreType},
+    domain::{AuthAPIError, RateLimit, RateLimitStoreError},
+};
+
+use super::{constants::RATE_LIMIT_PRUNE_INTERVAL_SECONDS, shutdown::ShutdownHandle};
+
+// Bodies of rate limited routes are buffered to find the target email, so they must be small
+const MAX_RATE_LIMITED_BODY_BYTES: usize = 64 * 1024;
+
+// The limits applied to a single route. Each one is a separate token bucket.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RouteRateLimits {
+    // Keyed by client IP
+    pub per_ip: Option<RateLimit>,
+    // Keyed by the `email` field of the JSON body, so spreading requests over many IPs doesn't help
+    pub per_account: Option<RateLimit>,
+}
+
+// Read from the `rate_limits` table of the settings. A `routes` table replaces the default routes entirely.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    // Keyed by request path. Routes without an entry aren't limited.
+    pub routes: HashMap<String, RouteRateLimits>,
+    // Peers allowed to report the client IP through X-Forwarded-For
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl RateLimitConfig {
+    // Limits nothing. Routes can then be added with `with_route`.
+    pub fn empty() -> Self {
+        Self {
+            routes: HashMap::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    pub fn with_route(mut self, path: &str, limits: RouteRateLimits) -> Self {
+        self.routes.insert(path.to_owned(), limits);
+        self
+    }
+
+    pub fn with_trusted_proxies(mut self, trusted_proxies: Vec<IpAddr>) -> Self {
+        self.trusted_proxies = trusted_proxies;
+        self
+    }
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        let per_minute = |capacity| Some(RateLimit::new(capacity, 60));
+
+        Self::empty()
+            .with_route(
+                "/login",
+                RouteRateLimits {
+                    per_ip: per_minute(20),
+                    per_account: Some(RateLimit::new(10, 300)),
+                },
+            )
+            .with_route(
+                "/signup",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: None,
+                },
+            )
+            .with_route(
+                "/verify-2fa",
+                RouteRateLimits {
+                    per_ip: per_minute(20),
+                    per_account: per_minute(5),
+                },
+            )
+            .with_route(
+                "/confirm-enable-2fa",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: None,
+                },
+            )
+            .with_route(
+                "/confirm-disable-2fa",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: None,
+                },
+            )
+            .with_route(
+                "/change-password",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: None,
+                },
+            )
+            // Each request sends an email, so an address can't be flooded with them
+            .with_route(
+                "/forgot-password",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: Some(RateLimit::new(3, 3600)),
+                },
+            )
+            .with_route(
+                "/reset-password",
+                RouteRateLimits {
+                    per_ip: per_minute(10),
+                    per_account: None,
+                },
+            )
+            // Called as the password is typed, so it gets more room than the routes setting it
+            .with_route(
+                "/password-strength",
+                RouteRateLimits {
+                    per_ip: per_minute(60),
+                    per_account: None,
+                },
+            )
+    }
+}
+
+// The limits themselves are read from the current settings on every request, so they can be reloaded
+#[derive(Clone)]
+pub struct RateLimiter {
+    pub store: RateLimitStoreType,
+}
+
+impl RateLimiter {
+    pub fn new(store: RateLimitStoreType) -> Self {
+        Self { store }
+    }
+
+    async fn check(&self, key: &str, limit: &RateLimit) -> Result<(), AuthAPIError> {
+        let now_ms = Utc::now().timestamp_millis();
+
+        match self
+            .store
+            .write()
+            .await
+            .try_acquire(key, limit, now_ms)
+            .await
+        {
+            Ok(()) => Ok(()),
+            Err(RateLimitStoreError::LimitExceeded {
+                retry_after_seconds,
+            }) => Err(AuthAPIError::TooManyRequests {
+                retry_after_seconds,
+            }),
+            Err(RateLimitStoreError::UnexpectedError) => Err(AuthAPIError::UnexpectedError),
+        }
+    }
+}
+
+// Removes idle buckets once every `RATE_LIMIT_PRUNE_INTERVAL_SECONDS` until shutdown,
+// so clients that stop sending requests don't keep their buckets around forever
+pub async fn run_rate_limit_pruning(rate_limiter: RateLimiter, shutdown: ShutdownHandle) {
+    let mut interval =
+        tokio::time::interval(Duration::from_secs(RATE_LIMIT_PRUNE_INTERVAL_SECONDS));
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.requested() => return,
+        }
+
+        let now_ms = Utc::now().timestamp_millis();
+        match rate_limiter.store.write().await.remove_idle(now_ms).await {
+            Ok(removed) => tracing::debug!(removed, "removed idle rate limit buckets"),
+            Err(_) => tracing::error!("failed to remove idle rate limit buckets"),
+        }
+    }
+}
+
+// Middleware applying the per-route limits in `RateLimitConfig`.
+// Needs the router to be served with `ConnectInfo<SocketAddr>` to limit by IP.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let rate_limiter = &state.rate_limiter;
+    let settings = state.settings.current();
+    let config = &settings.rate_limits;
+
+    let limits = match config.routes.get(request.uri().path()) {
+        Some(limits) if request.method() != Method::OPTIONS => *limits,
+        _ => return next.run(request).await,
+    };
+    let path = request.uri().path().to_owned();
+
+    if let Some(limit) = limits.per_ip {
+        if let Some(ip) = request_client_ip(&request, &config.trusted_proxies) {
+            if let Err(e) = rate_limiter
+                .check(&format!("{}:ip:{}", path, ip), &limit)
+                .await
+            {
+                return e.into_response();
+            }
+        }
+    }
+
+    let request = match limits.per_account {
+        Some(limit) => {
+            let (parts, body) = request.into_parts();
+            let bytes = match to_bytes(body, MAX_RATE_LIMITED_BODY_BYTES).await {
+                Ok(bytes) => bytes,
+                Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+            };
+
+            // Bodies without an email are left for the route to reject
+            if let Some(email) = target_email(&bytes) {
+                let key = format!("{}:account:{}", path, email);
+                if let Err(e) = rate_limiter.check(&key, &limit).await {
+                    return e.into_response();
+                }
+            }
+
+            Request::from_parts(parts, Body::from(bytes))
+        }
+        None => request,
+    };
+
+    next.run(request).await
+}
+
+// Returns the IP of the client that sent `request`, if the router is served with `ConnectInfo<SocketAddr>`
+pub fn request_client_ip(request: &Request, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let ConnectInfo(peer) = request.extensions().get::<ConnectInfo<SocketAddr>>()?;
+    Some(client_ip(peer.ip(), request.headers(), trusted_proxies))
+}
+
+// Returns the IP the request came from. X-Forwarded-For is only believed as far
+// back as it was written by trusted proxies, since anyone can send the header.
+fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+
+    let forwarded_for: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    // Walk back from the closest hop until we leave our own proxies
+    for hop in forwarded_for.into_iter().rev() {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+
+    client
+}
+
+fn target_email(body: &[u8]) -> Option<String> {
+    let body: serde_json::Value = serde_json::from_slice(body).ok()?;
+    let email = body.get("email")?.as_str()?.trim().to_lowercase();
+
+    if email.is_empty() {
+        None
+    } else {
+        Some(email)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append("x-forwarded-for", value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn test_client_ip_ignores_forwarded_for_from_untrusted_peer() {
+        let headers = forwarded_for(&["1.1.1.1"]);
+
+        assert_eq!(client_ip(ip("2.2.2.2"), &headers, &[]), ip("2.2.2.2"));
+        assert_eq!(
+            client_ip(ip("2.2.2.2"), &headers, &[ip("10.0.0.1")]),
+            ip("2.2.2.2")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_uses_forwarded_for_from_trusted_peer() {
+        let trusted_proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+
+        // The client can prepend anything, so only the hop added by our own proxy counts
+        let headers = forwarded_for(&["6.6.6.6, 1.1.1.1", "10.0.0.2"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted_proxies),
+            ip("1.1.1.1")
+        );
+
+        let headers = forwarded_for(&["1.1.1.1"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted_proxies),
+            ip("1.1.1.1")
+        );
+    }
+
+    #[test]
+    fn test_client_ip_stops_at_malformed_forwarded_for() {
+        let trusted_proxies = [ip("10.0.0.1")];
+
+        let headers = forwarded_for(&["1.1.1.1, not-an-ip"]);
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted_proxies),
+            ip("10.0.0.1")
+        );
+
+        let headers = HeaderMap::new();
+        assert_eq!(
+            client_ip(ip("10.0.0.1"), &headers, &trusted_proxies),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn test_target_email() {
+        assert_eq!(
+            target_email(br#"{"email": " User@Example.com ", "password": "password123"}"#),
+            Some("user@example.com".to_owned())
+        );
+        assert_eq!(target_email(br#"{"password": "password123"}"#), None);
+        assert_eq!(target_email(br#"{"email": 42}"#), None);
+        assert_eq!(target_email(b"not json"), None);
+    }
+}
@@ -0,0 +1,1012 @@
+use std::{
+    env as std_env,
+    fmt,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, PoisonError, RwLock},
+};
+
+use axum::http::HeaderValue;
+use dotenvy::dotenv;
+use serde::Deserialize;
+
+use crate::{
+    domain::{
+        password::MIN_PASSWORD_LENGTH,
+        password_policy::{MAX_HISTORY_SIZE, MAX_STRENGTH_SCORE},
+        Email, PasswordPolicy,
+    },
+    services::postmark_email_client::{
+        default_base_url, default_timeout_milliseconds, EmailSettings,
+    },
+};
+
+use super::{
+    auth::CookieSameSite,
+    constants::{env, CSRF_COOKIE_NAME, DEFAULT_CONFIG_PATH},
+    rate_limit::RateLimitConfig,
+    telemetry::LogFormat,
+    tls::TlsSettings,
+};
+
+// Everything the service is configured with. Each setting starts at its default, is overridden
+// by the TOML config file and then by its environment variable. See `config.example.toml`.
+// Settings read at startup only are listed in `restart_required`; all others can be reloaded.
+#[derive(Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    // Address the server listens on
+    pub app_address: String,
+    // The public base URL used to build links in emails, e.g. "https://auth.example.com"
+    pub auth_service_url: String,
+    // Origins browsers may call the API from with credentials, e.g. "http://localhost:8000"
+    pub allowed_origins: Vec<String>,
+    // Signs JWTs and audit log checkpoints. There is no default, so it must be set.
+    pub jwt_secret: String,
+    // Retired secrets, still accepted when verifying so the secret can be rotated without
+    // logging everyone out. Remove them once every token they signed has expired.
+    pub jwt_previous_secrets: Vec<String>,
+    pub jwt_cookie_name: String,
+    // Shares the auth cookie with subdomains of this domain, e.g. "example.com" for single sign-on
+    // across them. Only the host that set the cookie gets it back when unset.
+    pub jwt_cookie_domain: Option<String>,
+    // Only send the auth cookie over HTTPS. Defaults to whether `tls` is set, so set it
+    // explicitly behind a proxy that terminates TLS.
+    pub jwt_cookie_secure: Option<bool>,
+    pub jwt_cookie_same_site: CookieSameSite,
+    // Prefixes the auth cookie name with "__Host-", so browsers only accept it from this host
+    // over HTTPS. Can't be combined with a domain.
+    pub jwt_cookie_host_prefix: bool,
+    // How long a JWT auth token is valid for
+    pub token_ttl_seconds: i64,
+    // How long a deleted account can still be restored by logging in
+    pub account_deletion_grace_period_seconds: i64,
+    // Rules for new passwords, at signup and when changing or resetting a password
+    pub password_policy: PasswordPolicy,
+    // Per-route limits, and the reverse proxies allowed to set X-Forwarded-For
+    pub rate_limits: RateLimitConfig,
+    // Admin endpoints are disabled unless a key is set
+    pub admin_api_key: Option<String>,
+    // File the audit log is appended to. The audit log is only kept in memory when unset.
+    pub audit_log_path: Option<String>,
+    pub log_format: LogFormat,
+    // Base URL of an OTLP/HTTP collector, e.g. "http://localhost:4318". Spans aren't exported when unset.
+    pub otlp_endpoint: Option<String>,
+    // How long in-flight requests get to finish after SIGTERM/SIGINT before they are dropped
+    pub shutdown_drain_timeout_seconds: u64,
+    // Serves HTTPS when set, and plain HTTP otherwise. The certificate is reloaded when it changes.
+    pub tls: Option<TlsSettings>,
+    // Sends emails through Postmark when set. They're only logged otherwise, which is no use
+    // outside local development.
+    pub email: Option<EmailSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            app_address: "0.0.0.0:3000".to_owned(),
+            auth_service_url: "http://localhost:3000".to_owned(),
+            allowed_origins: vec![
+                "http://localhost:8000".to_owned(),
+                "http://127.0.0.1:8000".to_owned(),
+                "http://[::1]:8000".to_owned(),
+            ],
+            jwt_secret: String::new(),
+            jwt_previous_secrets: Vec::new(),
+            jwt_cookie_name: "jwt".to_owned(),
+            jwt_cookie_domain: None,
+            jwt_cookie_secure: None,
+            jwt_cookie_same_site: CookieSameSite::Lax,
+            jwt_cookie_host_prefix: false,
+            token_ttl_seconds: 600, // 10 minutes
+            account_deletion_grace_period_seconds: 30 * 24 * 60 * 60, // 30 days
+            password_policy: PasswordPolicy::default(),
+            rate_limits: RateLimitConfig::default(),
+            admin_api_key: None,
+            audit_log_path: None,
+            log_format: LogFormat::Pretty,
+            otlp_endpoint: None,
+            shutdown_drain_timeout_seconds: 30,
+            tls: None,
+            email: None,
+        }
+    }
+}
+
+// Keeps the secrets out of logs and panic messages
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("app_address", &self.app_address)
+            .field("auth_service_url", &self.auth_service_url)
+            .field("allowed_origins", &self.allowed_origins)
+            .field("jwt_secret", &"[REDACTED]")
+            .field("jwt_previous_secrets", &"[REDACTED]")
+            .field("jwt_cookie_name", &self.jwt_cookie_name)
+            .field("jwt_cookie_domain", &self.jwt_cookie_domain)
+            .field("jwt_cookie_secure", &self.jwt_cookie_secure)
+            .field("jwt_cookie_same_site", &self.jwt_cookie_same_site)
+            .field("jwt_cookie_host_prefix", &self.jwt_cookie_host_prefix)
+            .field("token_ttl_seconds", &self.token_ttl_seconds)
+            .field(
+                "account_deletion_grace_period_seconds",
+                &self.account_deletion_grace_period_seconds,
+            )
+            .field("password_policy", &self.password_policy)
+            .field("rate_limits", &self.rate_limits)
+            .field("admin_api_key", &self.admin_api_key.as_ref().map(|_| "[REDACTED]"))
+            .field("audit_log_path", &self.audit_log_path)
+            .field("log_format", &self.log_format)
+            .field("otlp_endpoint", &self.otlp_endpoint)
+            .field(
+                "shutdown_drain_timeout_seconds",
+                &self.shutdown_drain_timeout_seconds,
+            )
+            .field("tls", &self.tls)
+            .field("email", &self.email)
+            .finish()
+    }
+}
+
+impl Settings {
+    // Loads the settings from the config file and the process environment, including `.env`
+    pub fn load() -> Result<Self, SettingsError> {
+        Ok(SettingsHandle::load()?.current().as_ref().clone())
+    }
+
+    // Layers the config file at `path`, if any, and then the environment variables
+    // looked up with `var` over the defaults. Empty environment variables count as unset.
+    pub fn from_sources(
+        path: Option<&Path>,
+        var: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, SettingsError> {
+        let mut settings = match path {
+            Some(path) => Self::from_file(path)?,
+            None => Self::default(),
+        };
+
+        settings.apply_env(|name| var(name).filter(|value| !value.is_empty()))?;
+        settings.auth_service_url = settings.auth_service_url.trim_end_matches('/').to_owned();
+        // Read again on every reload, so an edited blocklist is picked up with a SIGHUP
+        settings.password_policy.load_blocklist().map_err(|e| SettingsError::Read {
+            path: settings.password_policy.blocklist_path.clone().unwrap_or_default(),
+            message: e.to_string(),
+        })?;
+        // Mapped again too, so a new download can be renamed over the old one
+        settings.password_policy.load_breached_passwords().map_err(|e| SettingsError::Read {
+            path: settings.password_policy.breached_hashes_path.clone().unwrap_or_default(),
+            message: e.to_string(),
+        })?;
+
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn from_file(path: &Path) -> Result<Self, SettingsError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| SettingsError::Read {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })?;
+
+        toml::from_str(&contents).map_err(|e| SettingsError::Parse {
+            path: path.to_owned(),
+            message: e.to_string(),
+        })
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), SettingsError> {
+        if let Some(value) = var(env::APP_ADDRESS_ENV_VAR) {
+            self.app_address = value;
+        }
+        if let Some(value) = var(env::AUTH_SERVICE_URL_ENV_VAR) {
+            self.auth_service_url = value;
+        }
+        if let Some(value) = var(env::ALLOWED_ORIGINS_ENV_VAR) {
+            self.allowed_origins = parse_list(env::ALLOWED_ORIGINS_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::JWT_SECRET_ENV_VAR) {
+            self.jwt_secret = value;
+        }
+        if let Some(value) = var(env::JWT_PREVIOUS_SECRETS_ENV_VAR) {
+            self.jwt_previous_secrets = parse_list(env::JWT_PREVIOUS_SECRETS_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::JWT_COOKIE_NAME_ENV_VAR) {
+            self.jwt_cookie_name = value;
+        }
+        if let Some(value) = var(env::JWT_COOKIE_DOMAIN_ENV_VAR) {
+            self.jwt_cookie_domain = Some(value);
+        }
+        if let Some(value) = var(env::JWT_COOKIE_SECURE_ENV_VAR) {
+            self.jwt_cookie_secure = Some(parse_var(env::JWT_COOKIE_SECURE_ENV_VAR, &value)?);
+        }
+        if let Some(value) = var(env::JWT_COOKIE_SAME_SITE_ENV_VAR) {
+            self.jwt_cookie_same_site = parse_var(env::JWT_COOKIE_SAME_SITE_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::JWT_COOKIE_HOST_PREFIX_ENV_VAR) {
+            self.jwt_cookie_host_prefix = parse_var(env::JWT_COOKIE_HOST_PREFIX_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::TOKEN_TTL_ENV_VAR) {
+            self.token_ttl_seconds = parse_var(env::TOKEN_TTL_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR) {
+            self.account_deletion_grace_period_seconds =
+                parse_var(env::ACCOUNT_DELETION_GRACE_PERIOD_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::PASSWORD_MIN_LENGTH_ENV_VAR) {
+            self.password_policy.min_length = parse_var(env::PASSWORD_MIN_LENGTH_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::PASSWORD_MAX_LENGTH_ENV_VAR) {
+            self.password_policy.max_length = parse_var(env::PASSWORD_MAX_LENGTH_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::PASSWORD_BLOCKLIST_PATH_ENV_VAR) {
+            self.password_policy.blocklist_path = Some(PathBuf::from(value));
+        }
+        if let Some(value) = var(env::PASSWORD_BREACHED_HASHES_PATH_ENV_VAR) {
+            self.password_policy.breached_hashes_path = Some(PathBuf::from(value));
+        }
+        if let Some(value) = var(env::PASSWORD_HISTORY_SIZE_ENV_VAR) {
+            self.password_policy.history_size =
+                parse_var(env::PASSWORD_HISTORY_SIZE_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::PASSWORD_MIN_STRENGTH_SCORE_ENV_VAR) {
+            self.password_policy.min_strength_score =
+                parse_var(env::PASSWORD_MIN_STRENGTH_SCORE_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::PASSWORD_MAX_AGE_DAYS_ENV_VAR) {
+            self.password_policy.max_age_days = parse_var(env::PASSWORD_MAX_AGE_DAYS_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::TRUSTED_PROXIES_ENV_VAR) {
+            self.rate_limits.trusted_proxies = parse_list(env::TRUSTED_PROXIES_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::ADMIN_API_KEY_ENV_VAR) {
+            self.admin_api_key = Some(value);
+        }
+        if let Some(value) = var(env::AUDIT_LOG_PATH_ENV_VAR) {
+            self.audit_log_path = Some(value);
+        }
+        if let Some(value) = var(env::LOG_FORMAT_ENV_VAR) {
+            self.log_format = parse_var(env::LOG_FORMAT_ENV_VAR, &value)?;
+        }
+        if let Some(value) = var(env::OTLP_ENDPOINT_ENV_VAR) {
+            self.otlp_endpoint = Some(value);
+        }
+        if let Some(value) = var(env::SHUTDOWN_DRAIN_TIMEOUT_ENV_VAR) {
+            self.shutdown_drain_timeout_seconds =
+                parse_var(env::SHUTDOWN_DRAIN_TIMEOUT_ENV_VAR, &value)?;
+        }
+        // Either path can be overridden, but turning TLS on from the environment takes both
+        let cert_path = var(env::TLS_CERT_PATH_ENV_VAR).map(PathBuf::from);
+        let key_path = var(env::TLS_KEY_PATH_ENV_VAR).map(PathBuf::from);
+        if cert_path.is_some() || key_path.is_some() {
+            let tls = self.tls.take();
+            let cert_path = cert_path.or_else(|| tls.as_ref().map(|tls| tls.cert_path.clone()));
+            let key_path = key_path.or_else(|| tls.as_ref().map(|tls| tls.key_path.clone()));
+            self.tls = match (cert_path, key_path) {
+                (Some(cert_path), Some(key_path)) => Some(TlsSettings { cert_path, key_path }),
+                (None, _) => {
+                    return Err(missing_tls_path(env::TLS_CERT_PATH_ENV_VAR, env::TLS_KEY_PATH_ENV_VAR))
+                }
+                (_, None) => {
+                    return Err(missing_tls_path(env::TLS_KEY_PATH_ENV_VAR, env::TLS_CERT_PATH_ENV_VAR))
+                }
+            };
+        }
+        // Likewise, sending emails from the environment takes both a sender and a token
+        let base_url = var(env::EMAIL_BASE_URL_ENV_VAR);
+        let sender = var(env::EMAIL_SENDER_ENV_VAR);
+        let authorization_token = var(env::EMAIL_AUTHORIZATION_TOKEN_ENV_VAR);
+        if base_url.is_some() || sender.is_some() || authorization_token.is_some() {
+            let email = self.email.take();
+            let timeout_milliseconds = email.as_ref().map(|email| email.timeout_milliseconds);
+            let base_url = base_url
+                .or_else(|| email.as_ref().map(|email| email.base_url.clone()))
+                .unwrap_or_else(default_base_url);
+            let sender = sender.or_else(|| email.as_ref().map(|email| email.sender.clone()));
+            let authorization_token = authorization_token
+                .or_else(|| email.as_ref().map(|email| email.authorization_token.clone()));
+            self.email = match (sender, authorization_token) {
+                (Some(sender), Some(authorization_token)) => Some(EmailSettings {
+                    base_url,
+                    sender,
+                    authorization_token,
+                    timeout_milliseconds: timeout_milliseconds
+                        .unwrap_or_else(default_timeout_milliseconds),
+                }),
+                (None, _) => return Err(missing_email_setting(env::EMAIL_SENDER_ENV_VAR)),
+                (_, None) => return Err(missing_email_setting(env::EMAIL_AUTHORIZATION_TOKEN_ENV_VAR)),
+            };
+        }
+
+        Ok(())
+    }
+
+    // Checks every setting, reporting all the invalid ones at once
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        let mut invalid = Vec::new();
+        let mut check = |ok: bool, setting: &'static str, message: String| {
+            if !ok {
+                invalid.push(InvalidSetting { setting, message });
+            }
+        };
+
+        check(
+            self.app_address.parse::<SocketAddr>().is_ok(),
+            "app_address",
+            "must be an IP address and port, e.g. \"0.0.0.0:3000\"".to_owned(),
+        );
+        check(
+            is_http_url(&self.auth_service_url),
+            "auth_service_url",
+            "must be an http:// or https:// URL".to_owned(),
+        );
+        for origin in self.allowed_origins.iter() {
+            check(
+                is_origin(origin),
+                "allowed_origins",
+                format!("{:?} is not an origin, e.g. \"http://localhost:8000\"", origin),
+            );
+        }
+        check(
+            !self.jwt_secret.is_empty(),
+            "jwt_secret",
+            "must be set, e.g. with the JWT_SECRET environment variable".to_owned(),
+        );
+        check(
+            self.jwt_previous_secrets.iter().all(|secret| !secret.is_empty()),
+            "jwt_previous_secrets",
+            "must not contain empty secrets".to_owned(),
+        );
+        check(
+            is_cookie_name(&self.jwt_cookie_name),
+            "jwt_cookie_name",
+            "must be a valid cookie name".to_owned(),
+        );
+        if let Some(domain) = &self.jwt_cookie_domain {
+            check(
+                is_cookie_domain(domain),
+                "jwt_cookie_domain",
+                format!("{:?} is not a domain, e.g. \"example.com\"", domain),
+            );
+        }
+        // Browsers drop cookies that break these rules rather than reporting anything
+        check(
+            self.jwt_cookie_same_site != CookieSameSite::None || self.auth_cookie_secure(),
+            "jwt_cookie_same_site",
+            "can only be \"none\" if the cookie is secure".to_owned(),
+        );
+        check(
+            !self.jwt_cookie_host_prefix
+                || (self.auth_cookie_secure() && self.jwt_cookie_domain.is_none()),
+            "jwt_cookie_host_prefix",
+            "needs the cookie to be secure and jwt_cookie_domain to be unset".to_owned(),
+        );
+        check(
+            self.token_ttl_seconds > 0,
+            "token_ttl_seconds",
+            "must be greater than 0".to_owned(),
+        );
+        check(
+            self.account_deletion_grace_period_seconds >= 0,
+            "account_deletion_grace_period_seconds",
+            "must not be negative".to_owned(),
+        );
+        check(
+            self.password_policy.min_length >= MIN_PASSWORD_LENGTH,
+            "password_policy.min_length",
+            format!("must be at least {}", MIN_PASSWORD_LENGTH),
+        );
+        check(
+            self.password_policy.max_length >= self.password_policy.min_length,
+            "password_policy.max_length",
+            "must not be less than password_policy.min_length".to_owned(),
+        );
+        check(
+            self.password_policy.min_strength_score <= MAX_STRENGTH_SCORE,
+            "password_policy.min_strength_score",
+            format!("must be at most {}", MAX_STRENGTH_SCORE),
+        );
+        check(
+            self.password_policy.history_size <= MAX_HISTORY_SIZE,
+            "password_policy.history_size",
+            format!("must be at most {}", MAX_HISTORY_SIZE),
+        );
+        if let Some(email) = &self.email {
+            check(
+                is_http_url(&email.base_url),
+                "email.base_url",
+                "must be an http:// or https:// URL".to_owned(),
+            );
+            check(
+                Email::parse(email.sender.clone()).is_ok(),
+                "email.sender",
+                format!("{:?} is not an email address", email.sender),
+            );
+            check(
+                !email.authorization_token.is_empty(),
+                "email.authorization_token",
+                "must not be empty".to_owned(),
+            );
+            check(
+                email.timeout_milliseconds > 0,
+                "email.timeout_milliseconds",
+                "must be greater than 0".to_owned(),
+            );
+        }
+        for (path, limits) in self.rate_limits.routes.iter() {
+            check(
+                path.starts_with('/'),
+                "rate_limits.routes",
+                format!("{:?} is not a path, e.g. \"/login\"", path),
+            );
+            for limit in [limits.per_ip, limits.per_account].iter().flatten() {
+                check(
+                    limit.capacity > 0 && limit.period_seconds > 0,
+                    "rate_limits.routes",
+                    format!("{:?} needs a capacity and period_seconds greater than 0", path),
+                );
+            }
+        }
+
+        if invalid.is_empty() {
+            Ok(())
+        } else {
+            Err(SettingsError::Invalid(invalid))
+        }
+    }
+
+    // The secrets a JWT may be signed with, starting with the one new tokens are signed with
+    pub fn jwt_secrets(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.jwt_secret.as_str())
+            .chain(self.jwt_previous_secrets.iter().map(String::as_str))
+    }
+
+    // The auth cookie's name, including its "__Host-" prefix
+    pub fn auth_cookie_name(&self) -> String {
+        if self.jwt_cookie_host_prefix {
+            format!("__Host-{}", self.jwt_cookie_name)
+        } else {
+            self.jwt_cookie_name.clone()
+        }
+    }
+
+    // The CSRF cookie is issued alongside the auth cookie, with the same prefix
+    pub fn csrf_cookie_name(&self) -> String {
+        if self.jwt_cookie_host_prefix {
+            format!("__Host-{}", CSRF_COOKIE_NAME)
+        } else {
+            CSRF_COOKIE_NAME.to_owned()
+        }
+    }
+
+    pub fn auth_cookie_secure(&self) -> bool {
+        self.jwt_cookie_secure.unwrap_or(self.tls.is_some())
+    }
+
+    // Settings that are only read at startup and differ in `other`
+    pub fn restart_required(&self, other: &Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.app_address != other.app_address {
+            changed.push("app_address");
+        }
+        if self.audit_log_path != other.audit_log_path {
+            changed.push("audit_log_path");
+        }
+        if self.log_format != other.log_format {
+            changed.push("log_format");
+        }
+        if self.otlp_endpoint != other.otlp_endpoint {
+            changed.push("otlp_endpoint");
+        }
+        if self.shutdown_drain_timeout_seconds != other.shutdown_drain_timeout_seconds {
+            changed.push("shutdown_drain_timeout_seconds");
+        }
+        // The certificate paths can change, but the listener can't switch between HTTP and HTTPS
+        if self.tls.is_some() != other.tls.is_some() {
+            changed.push("tls");
+        }
+        if self.email != other.email {
+            changed.push("email");
+        }
+        changed
+    }
+}
+
+// Shares the current settings between everything that reads them. New settings replace
+// the old ones as a whole, so a request never sees a mix of the two.
+#[derive(Clone)]
+pub struct SettingsHandle {
+    current: Arc<RwLock<Arc<Settings>>>,
+    // Where `reload` reads the settings from. Settings built in code can't be reloaded.
+    config_path: Option<PathBuf>,
+}
+
+impl SettingsHandle {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(settings))),
+            config_path: None,
+        }
+    }
+
+    // Loads the settings like `Settings::load`, keeping track of the config file they came from
+    pub fn load() -> Result<Self, SettingsError> {
+        dotenv().ok();
+        let var = |name: &str| std_env::var(name).ok();
+        let config_path = config_path(var);
+        let settings = Settings::from_sources(config_path.as_deref(), var)?;
+        Ok(Self::new(settings).with_config_path(config_path))
+    }
+
+    pub fn with_config_path(mut self, config_path: Option<PathBuf>) -> Self {
+        self.config_path = config_path;
+        self
+    }
+
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    pub fn current(&self) -> Arc<Settings> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    // Reads the config file and environment again and applies the result. The environment
+    // is the one the process started with, so in practice only the file can change.
+    pub fn reload(&self) -> Result<(), SettingsError> {
+        let path = match self.config_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let settings = Settings::from_sources(Some(path), |name| std_env::var(name).ok())?;
+        self.apply(settings)
+    }
+
+    // Replaces the current settings, unless `settings` are invalid or change one that is only
+    // read at startup. The current settings are kept as they are when this fails.
+    pub fn apply(&self, settings: Settings) -> Result<(), SettingsError> {
+        settings.validate()?;
+
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        let restart_required = current.restart_required(&settings);
+        if !restart_required.is_empty() {
+            return Err(SettingsError::RestartRequired(restart_required));
+        }
+
+        *current = Arc::new(settings);
+        Ok(())
+    }
+}
+
+// The config file named by AUTH_SERVICE_CONFIG, or `DEFAULT_CONFIG_PATH` if that exists
+pub fn config_path(var: impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    match var(env::CONFIG_PATH_ENV_VAR).filter(|path| !path.is_empty()) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|path| path.exists()),
+    }
+}
+
+fn parse_var<T>(var: &'static str, value: &str) -> Result<T, SettingsError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value.trim().parse().map_err(|e: T::Err| SettingsError::Env {
+        var,
+        message: format!("{:?}: {}", value, e),
+    })
+}
+
+// Comma-separated, ignoring blank entries
+fn parse_list<T>(var: &'static str, value: &str) -> Result<Vec<T>, SettingsError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse_var(var, item))
+        .collect()
+}
+
+fn missing_tls_path(var: &'static str, set: &'static str) -> SettingsError {
+    SettingsError::Env {
+        var,
+        message: format!("is unset, but is needed with {}", set),
+    }
+}
+
+fn missing_email_setting(var: &'static str) -> SettingsError {
+    SettingsError::Env {
+        var,
+        message: "is unset, but is needed to send emails".to_owned(),
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    ["http://", "https://"]
+        .iter()
+        .any(|scheme| url.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty()))
+}
+
+// A scheme and host with an optional port, but no path
+fn is_origin(origin: &str) -> bool {
+    is_http_url(origin)
+        && !origin.split_once("://").is_some_and(|(_, host)| host.contains('/'))
+        && origin.parse::<HeaderValue>().is_ok()
+}
+
+// Cookie names are HTTP tokens (RFC 6265)
+fn is_cookie_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"()<>@,;:\\\"/[]?={}".contains(c))
+}
+
+// A hostname, optionally with the leading dot older browsers expected
+fn is_cookie_domain(domain: &str) -> bool {
+    domain
+        .strip_prefix('.')
+        .unwrap_or(domain)
+        .split('.')
+        .all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+#[derive(Debug)]
+pub struct InvalidSetting {
+    pub setting: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    // The config file couldn't be read
+    Read { path: PathBuf, message: String },
+    // The config file isn't TOML, or has unknown settings or values of the wrong type
+    Parse { path: PathBuf, message: String },
+    // An environment variable couldn't be parsed
+    Env { var: &'static str, message: String },
+    Invalid(Vec<InvalidSetting>),
+    // Reloaded settings changed settings that are only read at startup
+    RestartRequired(Vec<&'static str>),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Read { path, message } => {
+                write!(f, "failed to read {}: {}", path.display(), message)
+            }
+            SettingsError::Parse { path, message } => {
+                write!(f, "invalid config file {}: {}", path.display(), message)
+            }
+            SettingsError::Env { var, message } => {
+                write!(f, "invalid {} environment variable {}", var, message)
+            }
+            SettingsError::Invalid(invalid) => {
+                let invalid: Vec<String> = invalid
+                    .iter()
+                    .map(|invalid| format!("{} {}", invalid.setting, invalid.message))
+                    .collect();
+                write!(f, "invalid settings: {}", invalid.join("; "))
+            }
+            SettingsError::RestartRequired(settings) => write!(
+                f,
+                "changing {} requires a restart",
+                settings.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, net::IpAddr};
+
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn write_config(contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("auth-service-{}.toml", Uuid::new_v4()));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_environment_overrides_file_overrides_defaults() {
+        let path = write_config(
+            r#"
+            jwt_secret = "file-secret"
+            token_ttl_seconds = 300
+            allowed_origins = ["https://app.example.com"]
+            auth_service_url = "https://auth.example.com/"
+            "#,
+        );
+
+        let settings = Settings::from_sources(
+            Some(&path),
+            vars(&[
+                ("JWT_SECRET", "env-secret"),
+                ("TRUSTED_PROXIES", "10.0.0.1, 10.0.0.2"),
+                ("PASSWORD_MAX_AGE_DAYS", "90"),
+                ("ADMIN_API_KEY", ""),
+            ]),
+        )
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(settings.jwt_secret, "env-secret");
+        assert_eq!(settings.token_ttl_seconds, 300);
+        assert_eq!(settings.allowed_origins, vec!["https://app.example.com"]);
+        assert_eq!(settings.auth_service_url, "https://auth.example.com");
+        assert_eq!(
+            settings.rate_limits.trusted_proxies,
+            vec!["10.0.0.1".parse::<IpAddr>().unwrap(), "10.0.0.2".parse().unwrap()]
+        );
+        assert_eq!(settings.password_policy.max_age_days, 90);
+        // Empty variables count as unset
+        assert_eq!(settings.admin_api_key, None);
+        assert_eq!(settings.jwt_cookie_name, Settings::default().jwt_cookie_name);
+    }
+
+    #[test]
+    fn test_jwt_secret_is_required() {
+        let error = Settings::from_sources(None, vars(&[])).unwrap_err();
+        assert!(error.to_string().contains("jwt_secret must be set"));
+
+        assert!(Settings::from_sources(None, vars(&[("JWT_SECRET", "secret")])).is_ok());
+    }
+
+    #[test]
+    fn test_every_invalid_setting_is_reported() {
+        let error = Settings::from_sources(
+            None,
+            vars(&[
+                ("JWT_SECRET", "secret"),
+                ("ALLOWED_ORIGINS", "http://localhost:8000/path"),
+                ("JWT_COOKIE_NAME", "jwt;"),
+                ("JWT_COOKIE_DOMAIN", "example.com/path"),
+                ("JWT_COOKIE_SAME_SITE", "none"),
+                ("JWT_COOKIE_HOST_PREFIX", "true"),
+                ("TOKEN_TTL_SECONDS", "0"),
+            ]),
+        )
+        .unwrap_err();
+
+        match error {
+            SettingsError::Invalid(invalid) => {
+                let settings: Vec<&str> = invalid.iter().map(|invalid| invalid.setting).collect();
+                assert_eq!(
+                    settings,
+                    vec![
+                        "allowed_origins",
+                        "jwt_cookie_name",
+                        "jwt_cookie_domain",
+                        "jwt_cookie_same_site",
+                        "jwt_cookie_host_prefix",
+                        "token_ttl_seconds"
+                    ]
+                );
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn test_unparseable_sources_are_reported() {
+        let error =
+            Settings::from_sources(None, vars(&[("TOKEN_TTL_SECONDS", "ten minutes")])).unwrap_err();
+        assert!(matches!(error, SettingsError::Env { var: "TOKEN_TTL_SECONDS", .. }));
+
+        let path = write_config("jwt_secret = \"secret\"\nunknown_setting = 1\n");
+        let error = Settings::from_sources(Some(&path), vars(&[])).unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert!(matches!(error, SettingsError::Parse { .. }));
+
+        let path = std::env::temp_dir().join(format!("auth-service-{}.toml", Uuid::new_v4()));
+        let error = Settings::from_sources(Some(&path), vars(&[])).unwrap_err();
+        assert!(matches!(error, SettingsError::Read { .. }));
+    }
+
+    #[test]
+    fn test_tls_paths_are_set_together() {
+        let tls = Settings::from_sources(
+            None,
+            vars(&[
+                ("JWT_SECRET", "secret"),
+                ("TLS_CERT_PATH", "cert.pem"),
+                ("TLS_KEY_PATH", "key.pem"),
+            ]),
+        )
+        .unwrap()
+        .tls
+        .unwrap();
+        assert_eq!(tls.cert_path, PathBuf::from("cert.pem"));
+        assert_eq!(tls.key_path, PathBuf::from("key.pem"));
+
+        let error = Settings::from_sources(
+            None,
+            vars(&[("JWT_SECRET", "secret"), ("TLS_CERT_PATH", "cert.pem")]),
+        )
+        .unwrap_err();
+        assert!(matches!(error, SettingsError::Env { var: "TLS_KEY_PATH", .. }));
+
+        // With the paths in the config file, either can be overridden on its own
+        let path = write_config(
+            "jwt_secret = \"secret\"\n[tls]\ncert_path = \"cert.pem\"\nkey_path = \"key.pem\"\n",
+        );
+        let settings =
+            Settings::from_sources(Some(&path), vars(&[("TLS_CERT_PATH", "renewed.pem")])).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let tls = settings.tls.clone().unwrap();
+        assert_eq!(tls.cert_path, PathBuf::from("renewed.pem"));
+        assert_eq!(tls.key_path, PathBuf::from("key.pem"));
+
+        // Switching between HTTP and HTTPS can't be done by a reload
+        let http = Settings { tls: None, ..settings.clone() };
+        assert_eq!(settings.restart_required(&http), vec!["tls"]);
+    }
+
+    #[test]
+    fn test_email_sender_and_token_are_set_together() {
+        let email = Settings::from_sources(
+            None,
+            vars(&[
+                ("JWT_SECRET", "secret"),
+                ("EMAIL_SENDER", "auth@example.com"),
+                ("EMAIL_AUTHORIZATION_TOKEN", "server-token"),
+            ]),
+        )
+        .unwrap()
+        .email
+        .unwrap();
+        assert_eq!(email.base_url, "https://api.postmarkapp.com");
+        assert_eq!(email.sender, "auth@example.com");
+        assert_eq!(email.authorization_token, "server-token");
+
+        let error = Settings::from_sources(
+            None,
+            vars(&[("JWT_SECRET", "secret"), ("EMAIL_SENDER", "auth@example.com")]),
+        )
+        .unwrap_err();
+        assert!(matches!(error, SettingsError::Env { var: "EMAIL_AUTHORIZATION_TOKEN", .. }));
+
+        // The token is better kept out of the config file, so it can come from the environment
+        let path = write_config(
+            "jwt_secret = \"secret\"\n[email]\nsender = \"not-an-email\"\nauthorization_token = \"\"\n",
+        );
+        let error = Settings::from_sources(
+            Some(&path),
+            vars(&[("EMAIL_AUTHORIZATION_TOKEN", "server-token")]),
+        )
+        .unwrap_err();
+        std::fs::remove_file(path).unwrap();
+        assert!(error.to_string().contains("email.sender"));
+        assert!(!error.to_string().contains("email.authorization_token"));
+
+        // The client is only built at startup
+        let settings = Settings { email: Some(email), ..Settings::default() };
+        assert_eq!(settings.restart_required(&Settings::default()), vec!["email"]);
+    }
+
+    #[test]
+    fn test_reload_applies_config_file_changes() {
+        let path = write_config("jwt_secret = \"secret\"\nallowed_origins = [\"https://a.example.com\"]\n");
+        let settings = Settings::from_sources(Some(&path), vars(&[])).unwrap();
+        let handle = SettingsHandle::new(settings).with_config_path(Some(path.clone()));
+
+        std::fs::write(&path, "jwt_secret = \"secret\"\nallowed_origins = [\"https://b.example.com\"]\n").unwrap();
+        handle.reload().unwrap();
+        assert_eq!(handle.current().allowed_origins, vec!["https://b.example.com"]);
+
+        // Neither settings that need a restart nor a broken file replace the current settings
+        std::fs::write(
+            &path,
+            "jwt_secret = \"secret\"\nallowed_origins = [\"https://c.example.com\"]\napp_address = \"0.0.0.0:4000\"\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            handle.reload(),
+            Err(SettingsError::RestartRequired(settings)) if settings == vec!["app_address"]
+        ));
+
+        std::fs::write(&path, "jwt_secret = ").unwrap();
+        assert!(matches!(handle.reload(), Err(SettingsError::Parse { .. })));
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(handle.current().allowed_origins, vec!["https://b.example.com"]);
+    }
+
+    #[test]
+    fn test_breached_password_hashes_are_mapped() {
+        let hashes = std::env::temp_dir().join(format!("auth-service-{}.txt", Uuid::new_v4()));
+        // The SHA-1 of "password"
+        std::fs::write(&hashes, "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8:52256179\n").unwrap();
+        let hashes_var = hashes.to_str().unwrap();
+        let settings = Settings::from_sources(
+            None,
+            vars(&[("JWT_SECRET", "secret"), ("PASSWORD_BREACHED_HASHES_PATH", hashes_var)]),
+        )
+        .unwrap();
+        assert!(settings.password_policy.breached_passwords.contains("password"));
+
+        // Anything but a HIBP SHA-1 download or index is rejected
+        std::fs::write(&hashes, "password\n").unwrap();
+        let error = Settings::from_sources(
+            None,
+            vars(&[("JWT_SECRET", "secret"), ("PASSWORD_BREACHED_HASHES_PATH", hashes_var)]),
+        )
+        .unwrap_err();
+        assert!(matches!(error, SettingsError::Read { path, .. } if path == hashes));
+        std::fs::remove_file(&hashes).unwrap();
+    }
+
+    #[test]
+    fn test_password_blocklist_is_read_on_reload() {
+        let blocklist = std::env::temp_dir().join(format!("auth-service-{}.txt", Uuid::new_v4()));
+        std::fs::write(&blocklist, "password123\n").unwrap();
+        let path = write_config(&format!(
+            "jwt_secret = \"secret\"\n[password_policy]\nrequire_digit = true\nblocklist_path = {:?}\n",
+            blocklist
+        ));
+        let settings = Settings::from_sources(Some(&path), vars(&[])).unwrap();
+        assert!(settings.password_policy.require_digit);
+        assert!(settings.password_policy.blocklist.contains("password123"));
+
+        let handle = SettingsHandle::new(settings).with_config_path(Some(path.clone()));
+        std::fs::write(&blocklist, "password123\nqwertyuiop\n").unwrap();
+        handle.reload().unwrap();
+        assert_eq!(handle.current().password_policy.blocklist.len(), 2);
+
+        // A blocklist that can't be read keeps the current one
+        std::fs::remove_file(&blocklist).unwrap();
+        assert!(matches!(handle.reload(), Err(SettingsError::Read { .. })));
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(handle.current().password_policy.blocklist.len(), 2);
+
+        let error = Settings::from_sources(
+            None,
+            vars(&[
+                ("JWT_SECRET", "secret"),
+                ("PASSWORD_MIN_LENGTH", "4"),
+                ("PASSWORD_MAX_LENGTH", "2"),
+                ("PASSWORD_MIN_STRENGTH_SCORE", "5"),
+                ("PASSWORD_HISTORY_SIZE", "25"),
+            ]),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("password_policy.min_length must be at least 8"));
+        assert!(error.to_string().contains("password_policy.max_length"));
+        assert!(error.to_string().contains("password_policy.min_strength_score must be at most 4"));
+        assert!(error.to_string().contains("password_policy.history_size must be at most 24"));
+    }
+
+    #[test]
+    fn test_debug_output_is_redacted() {
+        let settings = Settings {
+            jwt_secret: "super-secret".to_owned(),
+            admin_api_key: Some("admin-key".to_owned()),
+            ..Settings::default()
+        };
+        let debug = format!("{:?}", settings);
+        assert!(!debug.contains("super-secret"));
+        assert!(!debug.contains("admin-key"));
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+// Tells the server and its background tasks to stop. Clones share the same signal,
+// so any of them can trigger it and all of them see it.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<bool>>);
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        Self(Arc::new(watch::channel(false).0))
+    }
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    // Resolves once `shutdown` has been called, immediately if it already was
+    pub async fn requested(&self) {
+        let mut receiver = self.0.subscribe();
+        // The sender lives in `self`, so the channel can't close while we wait
+        let _ = receiver.wait_for(|shutting_down| *shutting_down).await;
+    }
+}
+
+// Triggers `handle` on SIGINT (Ctrl+C) or SIGTERM, which is what orchestrators send before killing us
+pub async fn shutdown_on_signal(handle: ShutdownHandle) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("received SIGTERM, shutting down"),
+        _ = handle.requested() => return,
+    }
+
+    handle.shutdown();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_every_clone_sees_shutdown() {
+        let handle = ShutdownHandle::default();
+        let clone = handle.clone();
+        assert!(!clone.is_shutting_down());
+
+        let waiting = tokio::spawn({
+            let clone = clone.clone();
+            async move { clone.requested().await }
+        });
+        handle.shutdown();
+
+        waiting.await.unwrap();
+        assert!(clone.is_shutting_down());
+        // Asking again once shut down resolves straight away
+        clone.requested().await;
+    }
+}
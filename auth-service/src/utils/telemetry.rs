@@ -0,0 +1,207 @@
+use std::{str::FromStr, time::Duration};
+
+use axum::{
+    extract::MatchedPath,
+    http::{Request, Response, Uri},
+};
+use opentelemetry::{global, trace::TracerProvider};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SdkTracerProvider, Resource};
+use serde::Deserialize;
+use tracing::{field::Empty, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+use super::constants::REQUEST_ID_HEADER;
+
+// Used when RUST_LOG isn't set
+const DEFAULT_LOG_FILTER: &str = "auth_service=info,tower_http=warn";
+
+// How spans are labelled in the collector
+const SERVICE_NAME: &str = "auth-service";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    // Human readable, multi-line output
+    Pretty,
+    // One JSON object per line
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+// Installs the global subscriber. Levels are filtered with RUST_LOG, e.g. "auth_service=debug".
+// Spans are exported over OTLP/HTTP when `otlp_endpoint` is set, e.g. "http://localhost:4318".
+// The returned provider should be shut down before exiting so buffered spans are sent.
+pub fn init_tracing(format: LogFormat, otlp_endpoint: Option<&str>) -> SdkTracerProvider {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let tracer_provider = tracer_provider(otlp_endpoint);
+
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let registry = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(SERVICE_NAME)));
+
+    match format {
+        LogFormat::Pretty => registry.with(fmt::layer().pretty()).init(),
+        LogFormat::Json => registry
+            .with(
+                fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_current_span(true)
+                    .with_span_list(false),
+            )
+            .init(),
+    }
+
+    tracer_provider
+}
+
+// Without an exporter spans still get W3C trace context, so it can be passed on to other services
+fn tracer_provider(otlp_endpoint: Option<&str>) -> SdkTracerProvider {
+    let builder = SdkTracerProvider::builder()
+        .with_resource(Resource::builder().with_service_name(SERVICE_NAME).build());
+
+    match otlp_endpoint {
+        Some(endpoint) => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+                .build()
+                .expect("Failed to build the OTLP span exporter");
+            builder.with_batch_exporter(exporter).build()
+        }
+        None => builder.build(),
+    }
+}
+
+// The span every request is handled in. `status` and `latency_ms` are filled in by `on_response`.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // Matched routes keep path parameters such as emails out of the `route` field
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or_else(|| request.uri().path());
+
+    let span = tracing::info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        uri = %redact_query(request.uri()),
+        status = Empty,
+        latency_ms = Empty,
+    );
+
+    // Continue the caller's trace when it sent `traceparent`/`tracestate`
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    span.set_parent(parent);
+
+    span
+}
+
+pub fn on_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("status", status.as_u16());
+    span.record("latency_ms", latency.as_millis() as u64);
+
+    if status.is_server_error() {
+        tracing::error!("request failed");
+    } else {
+        tracing::info!("request completed");
+    }
+}
+
+// Emailed links carry tokens in the query string, so only the parameter names are logged
+fn redact_query(uri: &Uri) -> String {
+    let query = match uri.query() {
+        Some(query) => query,
+        None => return uri.path().to_owned(),
+    };
+
+    let redacted: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) => format!("{}=[REDACTED]", name),
+            None => pair.to_owned(),
+        })
+        .collect();
+
+    format!("{}?{}", uri.path(), redacted.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceContextExt;
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("pretty".parse(), Ok(LogFormat::Pretty));
+        assert_eq!(" JSON ".parse(), Ok(LogFormat::Json));
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_request_span_continues_callers_trace() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let tracer_provider = tracer_provider(None);
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")));
+
+        let request = Request::builder()
+            .uri("/verify-token")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(())
+            .unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = make_request_span(&request);
+            let context = span.context();
+            let span_context = context.span().span_context().clone();
+
+            assert_eq!(
+                span_context.trace_id().to_string(),
+                "4bf92f3577b34da6a3ce929d0e0e4736"
+            );
+            assert_ne!(span_context.span_id().to_string(), "00f067aa0ba902b7");
+        });
+    }
+
+    #[test]
+    fn test_redact_query() {
+        let uri: Uri = "/?unlockAccount=5f0c8b7e&lang=en&flag".parse().unwrap();
+        assert_eq!(
+            redact_query(&uri),
+            "/?unlockAccount=[REDACTED]&lang=[REDACTED]&flag"
+        );
+
+        let uri: Uri = "/login".parse().unwrap();
+        assert_eq!(redact_query(&uri), "/login");
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use axum_server::tls_rustls::RustlsConfig;
+use serde::Deserialize;
+
+use super::{
+    config_reload::modified_at, constants::CONFIG_POLL_INTERVAL_SECONDS, settings::SettingsHandle,
+    shutdown::ShutdownHandle,
+};
+
+// The PEM encoded certificate, or full chain, and private key HTTPS is served with
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSettings {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+}
+
+pub async fn load_tls_config(tls: &TlsSettings) -> io::Result<RustlsConfig> {
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path).await
+}
+
+// Swaps in a new certificate once its files have been modified, e.g. renewed, or the settings
+// point at other files. Open connections keep the certificate they were set up with.
+// A certificate that fails to load is logged and the current one is kept.
+pub async fn run_certificate_reload(
+    config: RustlsConfig,
+    settings: SettingsHandle,
+    shutdown: ShutdownHandle,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CONFIG_POLL_INTERVAL_SECONDS));
+    let mut loaded = match settings.current().tls.clone() {
+        Some(tls) => certificate_files(tls).await,
+        None => return,
+    };
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.requested() => return,
+        }
+
+        // Turning TLS off needs a restart, so this only happens if the settings weren't reloaded
+        let Some(tls) = settings.current().tls.clone() else {
+            continue;
+        };
+        let files = certificate_files(tls).await;
+        if files == loaded {
+            continue;
+        }
+        loaded = files;
+
+        let (tls, _) = &loaded;
+        match config
+            .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+            .await
+        {
+            Ok(()) => tracing::info!(
+                cert_path = %tls.cert_path.display(),
+                "reloaded TLS certificate"
+            ),
+            Err(e) => tracing::error!(
+                cert_path = %tls.cert_path.display(),
+                error = %e,
+                "failed to reload TLS certificate, keeping the current certificate"
+            ),
+        }
+    }
+}
+
+async fn certificate_files(
+    tls: TlsSettings,
+) -> (TlsSettings, (Option<SystemTime>, Option<SystemTime>)) {
+    let modified = (
+        modified_at(&tls.cert_path).await,
+        modified_at(&tls.key_path).await,
+    );
+    (tls, modified)
+}
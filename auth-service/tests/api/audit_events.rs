@@ -0,0 +1,148 @@
+use auth_service::{
+    domain::{AuditOutcome, AuditQuery, RateLimit},
+    routes::AuditEventsResponse,
+    utils::{
+        constants::test::{ADMIN_API_KEY, USER_AGENT},
+        rate_limit::{RateLimitConfig, RouteRateLimits},
+    },
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_admin_api_key_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .get(format!("{}/admin/audit-events", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_admin_api_key() {
+    let app = TestApp::new().await;
+
+    let response = app.get_audit_events(&[], "wrong-key").await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_record_logins_with_request_details() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .get_audit_events(&[("email", email.clone())], ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let events = response
+        .json::<AuditEventsResponse>()
+        .await
+        .expect("Could not deserialize response body to AuditEventsResponse")
+        .events;
+
+    // Most recent first
+    let summary: Vec<(&str, AuditOutcome, u16)> = events
+        .iter()
+        .map(|event| (event.action.as_str(), event.outcome, event.status))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            ("POST /logout", AuditOutcome::Success, 200),
+            ("POST /login", AuditOutcome::Failure, 401),
+            ("POST /login", AuditOutcome::Success, 200),
+            ("POST /signup", AuditOutcome::Success, 201),
+        ]
+    );
+
+    for event in events.iter() {
+        assert_eq!(event.actor.as_deref(), Some(email.as_str()));
+        assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+        assert_eq!(event.user_agent.as_deref(), Some(USER_AGENT));
+    }
+}
+
+#[tokio::test]
+async fn should_filter_events_by_time_range() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let at = app
+        .audit_log
+        .read()
+        .await
+        .query(&AuditQuery::default())
+        .await
+        .unwrap()[0]
+        .at;
+
+    let count_events = |from: i64, to: i64| {
+        let app = &app;
+        async move {
+            let response = app
+                .get_audit_events(
+                    &[("from", from.to_string()), ("to", to.to_string())],
+                    ADMIN_API_KEY,
+                )
+                .await;
+            assert_eq!(response.status().as_u16(), 200);
+            response
+                .json::<AuditEventsResponse>()
+                .await
+                .unwrap()
+                .events
+                .len()
+        }
+    };
+
+    assert_eq!(count_events(at - 60, at + 60).await, 2);
+    assert_eq!(count_events(at + 60, at + 120).await, 0);
+    assert_eq!(count_events(at - 120, at - 60).await, 0);
+}
+
+#[tokio::test]
+async fn should_record_requests_turned_away_by_rate_limiter() {
+    let app = TestApp::with_rate_limits(RateLimitConfig::empty().with_route(
+        "/login",
+        RouteRateLimits {
+            per_ip: Some(RateLimit::new(1, 60)),
+            per_account: None,
+        },
+    ))
+    .await;
+
+    for _ in 0..2 {
+        app.post_login(
+            &serde_json::json!({ "email": get_random_email(), "password": "password123" }),
+        )
+        .await;
+    }
+
+    let events = app
+        .get_audit_events(&[], ADMIN_API_KEY)
+        .await
+        .json::<AuditEventsResponse>()
+        .await
+        .unwrap()
+        .events;
+
+    assert_eq!(events[0].action, "POST /login");
+    assert_eq!(events[0].status, 429);
+    assert_eq!(events[0].outcome, AuditOutcome::Failure);
+}
@@ -0,0 +1,195 @@
+use auth_service::{routes::EmailChangeResponse, ErrorResponse};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app.post_change_email(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "Missing token");
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_new_email() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    for new_email in ["", "invalidemail.com", email.as_str()] {
+        let response = app
+            .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", new_email);
+    }
+}
+
+#[tokio::test]
+async fn should_return_409_if_new_email_already_exists() {
+    let app = TestApp::new().await;
+    let taken_email = get_random_email();
+    app.signup_and_login(&taken_email, "password123").await;
+    app.signup_and_login(&get_random_email(), "password123").await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": taken_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn should_return_202_and_email_both_addresses() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    assert_eq!(
+        response
+            .json::<EmailChangeResponse>()
+            .await
+            .expect("Could not deserialize response body to EmailChangeResponse"),
+        EmailChangeResponse {
+            message: "Confirmation email sent".to_owned()
+        }
+    );
+
+    assert!(app
+        .get_link_param_from_last_email(&new_email, "confirmEmailChange")
+        .is_some());
+    assert!(app
+        .get_link_param_from_last_email(&email, "cancelEmailChange")
+        .is_some());
+}
+
+#[tokio::test]
+async fn should_move_user_and_invalidate_old_tokens_on_confirm() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    let password = "password123";
+    let old_token = app.signup_and_login(&email, password).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let token = app
+        .get_link_param_from_last_email(&new_email, "confirmEmailChange")
+        .expect("No confirmation link sent");
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The old address can no longer log in, the new one can
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = app
+        .post_login(&serde_json::json!({ "email": new_email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Tokens issued for the old address are rejected
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The confirmation link and the cancel link are single use
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+    let cancel_token = app
+        .get_link_param_from_last_email(&email, "cancelEmailChange")
+        .expect("No cancel link sent");
+    let response = app
+        .post_cancel_email_change(&serde_json::json!({ "token": cancel_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_not_change_email_once_cancelled() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    let password = "password123";
+    let old_token = app.signup_and_login(&email, password).await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let cancel_token = app
+        .get_link_param_from_last_email(&email, "cancelEmailChange")
+        .expect("No cancel link sent");
+    let response = app
+        .post_cancel_email_change(&serde_json::json!({ "token": cancel_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = app
+        .get_link_param_from_last_email(&new_email, "confirmEmailChange")
+        .expect("No confirmation link sent");
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_token_used_on_wrong_endpoint() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    app.post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+
+    let cancel_token = app
+        .get_link_param_from_last_email(&email, "cancelEmailChange")
+        .expect("No cancel link sent");
+    let response = app
+        .post_confirm_email_change(&serde_json::json!({ "token": cancel_token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    for token in ["invalid_token", &uuid::Uuid::new_v4().to_string()] {
+        let response = app
+            .post_confirm_email_change(&serde_json::json!({ "token": token }))
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+}
\ No newline at end of file
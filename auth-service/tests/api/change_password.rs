@@ -0,0 +1,305 @@
+use auth_service::{
+    domain::{Email, Password, PasswordHash, PasswordPolicy},
+    routes::PasswordChangeResponse,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_change_password_and_end_every_session() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The auth and CSRF cookies are removed
+    let removed_cookies = response
+        .cookies()
+        .filter(|cookie| cookie.value().is_empty())
+        .count();
+    assert_eq!(removed_cookies, 2);
+    assert_eq!(
+        response
+            .json::<PasswordChangeResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordChangeResponse"),
+        PasswordChangeResponse {
+            message: "Password changed. Please log in again.".to_owned()
+        }
+    );
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "new-password-456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_current_password_is_wrong() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "wrongpassword",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_lock_account_after_too_many_wrong_current_passwords() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let body = serde_json::json!({
+        "currentPassword": "wrongpassword",
+        "newPassword": "new-password-456"
+    });
+    for _ in 0..4 {
+        let response = app.post_change_password(&body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.post_change_password(&body).await;
+    assert_eq!(response.status().as_u16(), 423);
+
+    // Neither the right current password nor a login is accepted while locked
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn should_report_every_password_policy_violation() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        require_uppercase: true,
+        require_digit: true,
+        require_symbol: true,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "aaaaaaaa"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    let errors: Vec<_> = error_response
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            ("newPassword", "missing_uppercase"),
+            ("newPassword", "missing_digit"),
+            ("newPassword", "missing_symbol"),
+        ]
+    );
+
+    // The current password isn't held to the new policy
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "Correct-Horse-9"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_reject_a_new_password_that_is_easy_to_guess() {
+    let app = TestApp::new().await;
+    app.signup_and_login(&get_random_email(), "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        min_strength_score: 3,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "password1234"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    let errors: Vec<_> = error_response
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(errors, [("newPassword", "too_weak")]);
+}
+
+#[tokio::test]
+async fn should_reject_the_current_and_recent_passwords() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        history_size: 3,
+        ..PasswordPolicy::default()
+    });
+
+    let user_email = Email::parse(email.clone()).unwrap();
+    for password in ["oldest-password", "older-password", "old-password"] {
+        let hash = PasswordHash::new(&Password::parse(password.to_owned()).unwrap());
+        app.app_state
+            .user_store
+            .write()
+            .await
+            .add_password_history(&user_email, hash, 5)
+            .await
+            .unwrap();
+    }
+
+    // The current password and the 2 before it can't be used again
+    for password in ["password123", "old-password", "older-password"] {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "currentPassword": "password123",
+                "newPassword": password
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400);
+
+        let error_response: ErrorResponse = response.json().await.unwrap();
+        let errors: Vec<_> = error_response
+            .errors
+            .iter()
+            .map(|error| (error.field.as_str(), error.code.as_str()))
+            .collect();
+        assert_eq!(errors, [("newPassword", "reused")]);
+    }
+
+    // Older ones can
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "oldest-password"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The replaced password joins the history, which keeps the 2 most recent
+    let password_history = app
+        .app_state
+        .user_store
+        .read()
+        .await
+        .get_password_history(&user_email)
+        .await
+        .unwrap();
+    assert_eq!(password_history.len(), 2);
+    assert!(password_history[1].matches(&Password::parse("password123".to_owned()).unwrap()));
+}
+
+#[tokio::test]
+async fn should_allow_any_password_without_a_history() {
+    let app = TestApp::new().await;
+    app.signup_and_login(&get_random_email(), "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        history_size: 0,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "password123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_keep_every_replaced_password_when_changes_race() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let first = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "first-password-1"
+    });
+    let second = serde_json::json!({
+        "currentPassword": "password123",
+        "newPassword": "second-password-2"
+    });
+    let (first, second) = tokio::join!(
+        app.post_change_password(&first),
+        app.post_change_password(&second)
+    );
+    let changed = [first, second]
+        .iter()
+        .filter(|response| response.status().as_u16() == 200)
+        .count();
+    assert!(changed >= 1);
+
+    // Each replaced password is in the history once, including the one replaced in between
+    let password_history = app
+        .app_state
+        .user_store
+        .read()
+        .await
+        .get_password_history(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    let original = Password::parse("password123".to_owned()).unwrap();
+    assert_eq!(password_history.iter().filter(|hash| hash.matches(&original)).count(), 1);
+    assert_eq!(password_history.len(), changed);
+}
@@ -0,0 +1,118 @@
+use auth_service::{
+    domain::RateLimit,
+    utils::{
+        rate_limit::{RateLimitConfig, RouteRateLimits},
+        settings::{Settings, SettingsError},
+    },
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn get_with_origin(app: &TestApp, origin: &str) -> reqwest::Response {
+    app.http_client
+        .get(format!("{}/health/live", &app.address))
+        .header("Origin", origin)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn allowed_origin(response: &reqwest::Response) -> Option<&str> {
+    response
+        .headers()
+        .get("access-control-allow-origin")
+        .and_then(|value| value.to_str().ok())
+}
+
+#[tokio::test]
+async fn should_apply_reloaded_allowed_origins() {
+    let app = TestApp::new().await;
+    let origin = "https://app.example.com";
+
+    let response = get_with_origin(&app, origin).await;
+    assert_eq!(allowed_origin(&response), None);
+
+    let settings = Settings {
+        allowed_origins: vec![origin.to_owned()],
+        ..app.app_state.settings.current().as_ref().clone()
+    };
+    app.app_state.settings.apply(settings).unwrap();
+
+    let response = get_with_origin(&app, origin).await;
+    assert_eq!(allowed_origin(&response), Some(origin));
+}
+
+#[tokio::test]
+async fn should_apply_reloaded_rate_limits() {
+    let app = TestApp::with_rate_limits(RateLimitConfig::empty()).await;
+    let body = serde_json::json!({ "email": get_random_email(), "password": "password123" });
+
+    for _ in 0..2 {
+        assert_eq!(app.post_login(&body).await.status().as_u16(), 401);
+    }
+
+    let settings = Settings {
+        rate_limits: RateLimitConfig::empty().with_route(
+            "/login",
+            RouteRateLimits {
+                per_ip: Some(RateLimit::new(1, 60)),
+                per_account: None,
+            },
+        ),
+        ..app.app_state.settings.current().as_ref().clone()
+    };
+    app.app_state.settings.apply(settings).unwrap();
+
+    assert_eq!(app.post_login(&body).await.status().as_u16(), 401);
+    assert_eq!(app.post_login(&body).await.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_apply_tightened_rate_limits_to_existing_clients() {
+    let login_limit = |capacity| {
+        RateLimitConfig::empty().with_route(
+            "/login",
+            RouteRateLimits {
+                per_ip: Some(RateLimit::new(capacity, 60)),
+                per_account: None,
+            },
+        )
+    };
+    let app = TestApp::with_rate_limits(login_limit(10)).await;
+    let body = serde_json::json!({ "email": get_random_email(), "password": "password123" });
+
+    for _ in 0..2 {
+        assert_eq!(app.post_login(&body).await.status().as_u16(), 401);
+    }
+
+    let settings = Settings {
+        rate_limits: login_limit(3),
+        ..app.app_state.settings.current().as_ref().clone()
+    };
+    app.app_state.settings.apply(settings).unwrap();
+
+    // This client's bucket was created under the old limit, and still only gets the new one
+    for _ in 0..3 {
+        assert_eq!(app.post_login(&body).await.status().as_u16(), 401);
+    }
+    assert_eq!(app.post_login(&body).await.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_keep_current_settings_when_reload_requires_restart() {
+    let app = TestApp::new().await;
+    let origin = "https://app.example.com";
+
+    let settings = Settings {
+        allowed_origins: vec![origin.to_owned()],
+        app_address: "0.0.0.0:4000".to_owned(),
+        ..app.app_state.settings.current().as_ref().clone()
+    };
+    let result = app.app_state.settings.apply(settings);
+    assert!(matches!(result, Err(SettingsError::RestartRequired(_))));
+
+    // Nothing from the rejected settings is applied, and the app keeps serving requests
+    let response = get_with_origin(&app, origin).await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(allowed_origin(&response), None);
+}
@@ -0,0 +1,243 @@
+use auth_service::{
+    domain::{Email, LoginAttemptId, TwoFACode, TwoFAPurpose, UserStoreError},
+    routes::DeleteAccountResponse,
+    utils::{account_purge::purge_deleted_accounts, constants::CSRF_TOKEN_HEADER},
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+    app.signup_and_login(&get_random_email(), "password123").await;
+
+    let response = app.delete_account(&serde_json::json!({})).await;
+    assert_eq!(response.status().as_u16(), 422);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "Missing token");
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .expect("User should still exist");
+    assert_eq!(user.deletion_scheduled_for, None);
+}
+
+#[tokio::test]
+async fn should_return_403_if_csrf_token_missing() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .http_client
+        .delete(format!("{}/account", &app.address))
+        .json(&serde_json::json!({ "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Nor is a token that doesn't match the CSRF cookie accepted
+    let response = app
+        .http_client
+        .delete(format!("{}/account", &app.address))
+        .header(CSRF_TOKEN_HEADER, "0".repeat(64))
+        .json(&serde_json::json!({ "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 403);
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .expect("User should still exist");
+    assert_eq!(user.deletion_scheduled_for, None);
+}
+
+#[tokio::test]
+async fn should_return_202_and_end_all_sessions() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == app.app_state.settings.current().jwt_cookie_name)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.value().is_empty());
+
+    let response_body: DeleteAccountResponse = response
+        .json()
+        .await
+        .expect("Could not deserialize response body to DeleteAccountResponse");
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(email).unwrap())
+        .await
+        .expect("User should exist until the grace period ends");
+    assert_eq!(user.deletion_scheduled_for, Some(response_body.purge_at));
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_restore_account_on_login_during_grace_period() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let user = app
+        .user_store
+        .read()
+        .await
+        .get_user(&Email::parse(email.clone()).unwrap())
+        .await
+        .expect("User should exist");
+    assert_eq!(user.deletion_scheduled_for, None);
+
+    // A restored account is not purged once the original grace period ends
+    let after_grace_period =
+        chrono::Utc::now().timestamp() + app.app_state.settings.current().account_deletion_grace_period_seconds + 1;
+    let purged = purge_deleted_accounts(&app.app_state, after_grace_period)
+        .await
+        .expect("Failed to purge accounts");
+    assert!(purged.is_empty());
+}
+
+#[tokio::test]
+async fn should_only_restore_2fa_account_once_2fa_is_verified() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let parsed_email = Email::parse(email.clone()).unwrap();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let response = app.post_verify_2fa(&app.pending_2fa_login(&email).await).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // The password alone doesn't restore the account
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 206);
+    let user = app.user_store.read().await.get_user(&parsed_email).await.unwrap();
+    assert!(user.deletion_scheduled_for.is_some());
+
+    let response = app.post_verify_2fa(&app.pending_2fa_login(&email).await).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let user = app.user_store.read().await.get_user(&parsed_email).await.unwrap();
+    assert_eq!(user.deletion_scheduled_for, None);
+}
+
+#[tokio::test]
+async fn should_purge_account_after_grace_period() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let parsed_email = Email::parse(email.clone()).unwrap();
+    app.signup_and_login(&email, "password123").await;
+
+    // Leave a pending 2FA code behind to check it is cleaned up too
+    app.two_fa_code_store
+        .write()
+        .await
+        .add_code(parsed_email.clone(), TwoFAPurpose::Login, LoginAttemptId::default(), TwoFACode::default(), None)
+        .await
+        .unwrap();
+
+    let response = app
+        .delete_account(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Nothing is purged before the grace period ends
+    let purged = purge_deleted_accounts(&app.app_state, chrono::Utc::now().timestamp())
+        .await
+        .expect("Failed to purge accounts");
+    assert!(purged.is_empty());
+
+    let after_grace_period =
+        chrono::Utc::now().timestamp() + app.app_state.settings.current().account_deletion_grace_period_seconds + 1;
+    let purged = purge_deleted_accounts(&app.app_state, after_grace_period)
+        .await
+        .expect("Failed to purge accounts");
+    assert_eq!(purged, vec![parsed_email.clone()]);
+
+    assert_eq!(
+        app.user_store.read().await.get_user(&parsed_email).await,
+        Err(UserStoreError::UserNotFound)
+    );
+    assert!(!app.two_fa_code_store.read().await.has_code(&parsed_email).await.unwrap());
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
\ No newline at end of file
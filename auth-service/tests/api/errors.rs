@@ -0,0 +1,82 @@
+use auth_service::{utils::constants::REQUEST_ID_HEADER, ErrorResponse};
+
+use crate::helpers::TestApp;
+
+fn assert_problem_json(response: &reqwest::Response) {
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/problem+json"
+    );
+}
+
+#[tokio::test]
+async fn should_return_problem_details_with_request_id() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/logout", &app.address))
+        .header(REQUEST_ID_HEADER, "problem-request-id")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert_problem_json(&response);
+
+    let problem: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(problem.problem_type, "about:blank");
+    assert_eq!(problem.title, "Bad Request");
+    assert_eq!(problem.status, 400);
+    assert_eq!(problem.code, "missing_token");
+    assert_eq!(problem.detail, "Missing token");
+    assert_eq!(problem.request_id.as_deref(), Some("problem-request-id"));
+}
+
+#[tokio::test]
+async fn should_wrap_extractor_rejections_in_problem_details() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        // Missing fields
+        (serde_json::json!({ "email": "test@example.com" }), 422, "unprocessable_entity"),
+        // Not an object
+        (serde_json::json!("test@example.com"), 422, "unprocessable_entity"),
+    ];
+    for (body, status, code) in test_cases {
+        let response = app.post_signup(&body).await;
+        assert_eq!(response.status().as_u16(), status, "Failed for input: {:?}", body);
+        assert_problem_json(&response);
+
+        let request_id = response.headers().get(REQUEST_ID_HEADER).cloned().unwrap();
+        let problem: ErrorResponse = response.json().await.unwrap();
+        assert_eq!(problem.code, code);
+        assert!(!problem.detail.is_empty());
+        assert_eq!(problem.request_id.as_deref(), request_id.to_str().ok());
+    }
+
+    // A body that isn't labelled as JSON
+    let response = app
+        .http_client
+        .post(format!("{}/signup", &app.address))
+        .body("email=test@example.com")
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 415);
+    assert_problem_json(&response);
+    let problem: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(problem.code, "unsupported_media_type");
+}
+
+#[tokio::test]
+async fn should_leave_successful_and_json_responses_alone() {
+    let app = TestApp::new().await;
+
+    let response = app.get_health_live().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_ne!(
+        response.headers().get("content-type").map(|value| value.as_bytes()),
+        Some("application/problem+json".as_bytes())
+    );
+}
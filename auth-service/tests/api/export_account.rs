@@ -0,0 +1,116 @@
+use auth_service::{routes::AccountExport, ErrorResponse};
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.get_account_export().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "Missing token");
+}
+
+#[tokio::test]
+async fn should_return_200_with_everything_held_about_the_user() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    let password = "password123";
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": password,
+            "requires2FA": false,
+            "consents": ["terms_of_service"]
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = app.get_account_export().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("content-disposition").unwrap(),
+        "attachment; filename=\"account-export.json\""
+    );
+
+    let body = response.text().await.expect("Failed to read response body");
+    // Secrets must never be part of the export
+    assert!(!body.contains(password));
+    let confirm_token = app
+        .get_link_param_from_last_email(&new_email, "confirmEmailChange")
+        .expect("No confirmation link sent");
+    assert!(!body.contains(&confirm_token));
+
+    let export: AccountExport =
+        serde_json::from_str(&body).expect("Could not deserialize response body to AccountExport");
+
+    assert_eq!(export.profile.email, email);
+    assert_eq!(export.profile.deletion_scheduled_for, None);
+    assert!(!export.two_factor_auth.enabled);
+    assert!(!export.two_factor_auth.pending_challenge);
+
+    assert_eq!(export.sessions.len(), 1);
+    assert!(export.sessions[0].current);
+
+    let outcomes: Vec<&str> = export
+        .login_history
+        .iter()
+        .map(|record| record.outcome.as_str())
+        .collect();
+    assert_eq!(outcomes, vec!["incorrect_credentials", "success"]);
+
+    assert_eq!(export.consents.len(), 1);
+    assert_eq!(export.consents[0].purpose, "terms_of_service");
+
+    assert_eq!(export.pending_verifications.len(), 2);
+    assert!(export
+        .pending_verifications
+        .iter()
+        .all(|verification| verification.new_email.as_deref() == Some(new_email.as_str())));
+}
+
+#[tokio::test]
+async fn should_not_list_sessions_that_were_logged_out() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let export: AccountExport = app
+        .get_account_export()
+        .await
+        .json()
+        .await
+        .expect("Could not deserialize response body to AccountExport");
+    assert_eq!(export.sessions.len(), 1);
+    assert!(export.sessions[0].current);
+}
\ No newline at end of file
@@ -0,0 +1,58 @@
+use auth_service::routes::{HealthStatus, LivenessResponse, ReadinessResponse};
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_return_200_when_live() {
+    let app = TestApp::new().await;
+
+    let response = app.get_health_live().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response_body: LivenessResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.status, HealthStatus::Ok);
+}
+
+#[tokio::test]
+async fn should_return_200_when_every_component_is_ready() {
+    let app = TestApp::new().await;
+
+    let response = app.get_health_ready().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response_body: ReadinessResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.status, HealthStatus::Ok);
+    assert_eq!(response_body.components.user_store, HealthStatus::Ok);
+    assert_eq!(response_body.components.banned_token_store, HealthStatus::Ok);
+    assert_eq!(response_body.components.two_fa_code_store, HealthStatus::Ok);
+    assert_eq!(response_body.components.email_client, HealthStatus::Ok);
+}
+
+#[tokio::test]
+async fn should_return_503_when_a_store_does_not_answer() {
+    let app = TestApp::new().await;
+
+    // Holding the lock keeps the user store from answering its health check in time
+    let _user_store = app.user_store.write().await;
+
+    let response = app.get_health_ready().await;
+    assert_eq!(response.status().as_u16(), 503);
+
+    let response_body: ReadinessResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.status, HealthStatus::Unavailable);
+    assert_eq!(response_body.components.user_store, HealthStatus::Unavailable);
+    assert_eq!(response_body.components.banned_token_store, HealthStatus::Ok);
+
+    // Liveness doesn't depend on the stores
+    let response = app.get_health_live().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -1,65 +1,204 @@
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType}, 
+    domain::{Email, EmailClient, PasswordPolicy, TwoFAPurpose},
+    app_state::{AppState, AuditLogType, BannedTokenStoreType, TwoFACodeStoreType, UserStoreType}, 
     services::{
         hashmap_user_store::HashmapUserStore,
         hashset_banned_token_store::HashsetBannedTokenStore,
         hashmap_two_fa_code_store::HashmapTwoFACodeStore,
+        hashmap_verification_token_store::HashmapVerificationTokenStore,
+        hashmap_rate_limit_store::HashmapRateLimitStore,
+        in_memory_audit_log::InMemoryAuditLog,
+        instrumented_store::InstrumentedStore,
     }, 
-    utils::constants::test, Application
+    utils::{
+        constants::{test, ADMIN_API_KEY_HEADER, CSRF_TOKEN_HEADER},
+        rate_limit::{RateLimitConfig, RateLimiter},
+        settings::{Settings, SettingsHandle},
+        shutdown::ShutdownHandle,
+        tls::TlsSettings,
+    },
+    Application
 };
-use reqwest::cookie::Jar;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use reqwest::cookie::{CookieStore, Jar};
+use sha1::{Digest, Sha1};
+use std::{path::PathBuf, sync::{Arc, Mutex}, time::Duration};
+use tokio::{sync::RwLock, task::JoinHandle};
 use uuid::Uuid;
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentEmail {
+    pub recipient: Email,
+    pub subject: String,
+    pub content: String,
+}
+
+// Remembers the emails it's asked to send, so tests can inspect them
+#[derive(Default)]
+pub struct MockEmailClient {
+    sent_emails: Mutex<Vec<SentEmail>>,
+}
+
+impl MockEmailClient {
+    pub fn sent_emails(&self) -> Vec<SentEmail> {
+        self.sent_emails
+            .lock()
+            .map(|sent_emails| sent_emails.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailClient for MockEmailClient {
+    async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<(), String> {
+        self.sent_emails
+            .lock()
+            .map_err(|e| e.to_string())?
+            .push(SentEmail {
+                recipient: recipient.clone(),
+                subject: subject.to_owned(),
+                content: content.to_owned(),
+            });
+
+        Ok(())
+    }
+
+    async fn health_check(&self) -> Result<(), String> {
+        // A panic while recording an email poisons the lock, after which nothing can be sent
+        self.sent_emails
+            .lock()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn test_settings() -> Settings {
+    Settings {
+        jwt_secret: test::JWT_SECRET.to_owned(),
+        admin_api_key: Some(test::ADMIN_API_KEY.to_owned()),
+        ..Settings::default()
+    }
+}
+
 pub struct TestApp {
     pub address: String,
     pub cookie_jar: Arc<Jar>,
+    pub app_state: AppState,
+    pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub audit_log: AuditLogType,
+    pub email_client: Arc<MockEmailClient>,
     pub http_client: reqwest::Client,
+    pub shutdown_handle: ShutdownHandle,
+    // Finishes once the server has drained and stopped after `shutdown_handle` is triggered
+    pub server: JoinHandle<Result<(), std::io::Error>>,
 }
 
 impl TestApp {
     pub async fn new() -> Self {
-        let user_store = Arc::new(RwLock::new(HashmapUserStore::default()));
-        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let two_fa_code_store = Arc::new(RwLock::new(HashmapTwoFACodeStore::default()));
+        Self::with_rate_limits(RateLimitConfig::default()).await
+    }
+
+    pub async fn with_rate_limits(rate_limit_config: RateLimitConfig) -> Self {
+        Self::build(
+            Settings {
+                rate_limits: rate_limit_config,
+                ..test_settings()
+            },
+            None,
+            None,
+        )
+        .await
+    }
+
+    pub async fn with_drain_timeout(drain_timeout: Duration) -> Self {
+        Self::build(test_settings(), Some(drain_timeout), None).await
+    }
+
+    // Serves HTTPS with the certificate in `tls`, which the client trusts as `root_certificate`
+    pub async fn with_tls(tls: TlsSettings, root_certificate: reqwest::Certificate) -> Self {
+        Self::build(
+            Settings {
+                tls: Some(tls),
+                ..test_settings()
+            },
+            None,
+            Some(root_certificate),
+        )
+        .await
+    }
+
+    async fn build(
+        settings: Settings,
+        drain_timeout: Option<Duration>,
+        root_certificate: Option<reqwest::Certificate>,
+    ) -> Self {
+        let user_store = Arc::new(RwLock::new(InstrumentedStore::new(HashmapUserStore::default())));
+        let banned_token_store = Arc::new(RwLock::new(InstrumentedStore::new(HashsetBannedTokenStore::default())));
+        let two_fa_code_store = Arc::new(RwLock::new(InstrumentedStore::new(HashmapTwoFACodeStore::default())));
+        let verification_token_store = Arc::new(RwLock::new(InstrumentedStore::new(HashmapVerificationTokenStore::default())));
+        let email_client = Arc::new(MockEmailClient::default());
+        let rate_limiter = RateLimiter::new(
+            Arc::new(RwLock::new(InstrumentedStore::new(HashmapRateLimitStore::default()))),
+        );
+        let audit_log: AuditLogType = Arc::new(RwLock::new(InMemoryAuditLog::default()));
         let app_state = AppState::new(
             user_store.clone(),
             banned_token_store.clone(),
-            two_fa_code_store.clone()
+            two_fa_code_store.clone(),
+            verification_token_store,
+            email_client.clone(),
+            rate_limiter,
+            audit_log.clone(),
+            SettingsHandle::new(settings),
         );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let mut app = Application::build(app_state.clone(), test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
+        if let Some(drain_timeout) = drain_timeout {
+            app = app.with_drain_timeout(drain_timeout);
+        }
+
+        // The test certificate is issued for localhost rather than an IP address
+        let address = match app.tls {
+            true => format!("https://{}", app.address.replace("127.0.0.1", "localhost")),
+            false => format!("http://{}", app.address.clone()),
+        };
 
-        let address = format!("http://{}", app.address.clone());
+        let shutdown_handle = app.shutdown_handle();
 
         // Run the auth service in a separate async task
         // to avoid blocking the main test thread. 
-        #[allow(clippy::let_underscore_future)]
-        let _ = tokio::spawn(app.run());
+        let server = tokio::spawn(app.run());
 
         let cookie_jar = Arc::new(Jar::default());
-        let http_client = reqwest::Client::builder()
+        let mut http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
-            .build()
-            .unwrap();
+            .user_agent(test::USER_AGENT);
+        if let Some(root_certificate) = root_certificate {
+            http_client = http_client.add_root_certificate(root_certificate);
+        }
+        let http_client = http_client.build().unwrap();
 
         Self {
             address,
+            app_state,
+            user_store,
             banned_token_store,
             cookie_jar,
             two_fa_code_store,
+            audit_log,
+            email_client,
             http_client,
+            shutdown_handle,
+            server,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -70,7 +209,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -82,7 +221,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -90,16 +229,19 @@ impl TestApp {
     }
 
     pub async fn post_logout(&self) -> reqwest::Response {
-        self.http_client
-            .post(&format!("{}/logout", &self.address))
+        self.with_csrf_token(self.http_client.post(format!("{}/logout", &self.address)))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    pub async fn post_verify_2fa(&self) -> reqwest::Response {
+    pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
         self.http_client
-            .post(&format!("{}/verify-2fa", &self.address))
+            .post(format!("{}/verify-2fa", &self.address))
+            .json(body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -117,6 +259,324 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.post(format!("{}/change-email", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_email_change<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/confirm-email-change", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_cancel_email_change<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/cancel-email-change", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.post(format!("{}/change-password", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_forgot_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/forgot-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/reset-password", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_strength<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-strength", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.delete(format!("{}/account", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_account_export(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/account/export", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_enable_2fa(&self) -> reqwest::Response {
+        self.with_csrf_token(self.http_client.post(format!("{}/enable-2fa", &self.address)))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_enable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.post(format!("{}/confirm-enable-2fa", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_disable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.post(format!("{}/disable-2fa", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_confirm_disable_2fa<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.with_csrf_token(self.http_client.post(format!("{}/confirm-disable-2fa", &self.address)))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unlock_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/unlock-account", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_unlock_account<Body>(&self, body: &Body, api_key: &str) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/unlock-account", &self.address))
+            .header(ADMIN_API_KEY_HEADER, api_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_require_password_change<Body>(
+        &self,
+        body: &Body,
+        api_key: &str,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/admin/require-password-change", &self.address))
+            .header(ADMIN_API_KEY_HEADER, api_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_audit_events(&self, query: &[(&str, String)], api_key: &str) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/admin/audit-events", &self.address))
+            .header(ADMIN_API_KEY_HEADER, api_key)
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_health_live(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/live", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_health_ready(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/health/ready", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_metrics(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/metrics", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    // Replaces the password policy, like a config reload would
+    pub fn set_password_policy(&self, password_policy: PasswordPolicy) {
+        let settings = Settings {
+            password_policy,
+            ..self.app_state.settings.current().as_ref().clone()
+        };
+        self.app_state.settings.apply(settings).unwrap();
+    }
+
+    // The CSRF token set at login, if any
+    pub fn csrf_token(&self) -> Option<String> {
+        let url = reqwest::Url::parse(&self.address).unwrap();
+        let cookies = self.cookie_jar.cookies(&url)?;
+        let prefix = format!("{}=", self.app_state.settings.current().csrf_cookie_name());
+        cookies
+            .to_str()
+            .ok()?
+            .split("; ")
+            .find_map(|cookie| cookie.strip_prefix(&prefix))
+            .map(str::to_owned)
+    }
+
+    // Sends the CSRF token like the browser app does for requests authenticated by cookie
+    fn with_csrf_token(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self.csrf_token() {
+            Some(csrf_token) => request.header(CSRF_TOKEN_HEADER, csrf_token),
+            None => request,
+        }
+    }
+
+    // Signs up and logs in a user without 2FA, returning the issued JWT
+    pub async fn signup_and_login(&self, email: &str, password: &str) -> String {
+        let response = self
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": password,
+                "requires2FA": false
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = self
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": password
+            }))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+
+        let token = response
+            .cookies()
+            .find(|c| c.name() == self.app_state.settings.current().jwt_cookie_name)
+            .expect("No auth cookie found")
+            .value()
+            .to_owned();
+        token
+    }
+
+    // Returns the body for /verify-2fa that completes the user's pending 2FA login
+    pub async fn pending_2fa_login(&self, email: &str) -> serde_json::Value {
+        let (login_attempt_id, code) = self
+            .two_fa_code_store
+            .read()
+            .await
+            .get_code(&Email::parse(email.to_owned()).unwrap(), TwoFAPurpose::Login)
+            .await
+            .expect("Failed to get 2FA code");
+
+        serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id.as_ref(),
+            "2FACode": code.as_ref()
+        })
+    }
+
+    // Returns the value of `param` from the link in the last email sent to `recipient`
+    pub fn get_link_param_from_last_email(&self, recipient: &str, param: &str) -> Option<String> {
+        let email = self
+            .email_client
+            .sent_emails()
+            .into_iter()
+            .rev()
+            .find(|email| email.recipient.as_ref() == recipient)?;
+
+        let (_, value) = email.content.split_once(&format!("?{}=", param))?;
+        Some(value.split_whitespace().next()?.to_owned())
+    }
+}
+
+// A Have I Been Pwned SHA-1 download with just `passwords`, for `breached_hashes_path`
+pub fn write_breached_hashes(passwords: &[&str]) -> PathBuf {
+    let mut hashes: Vec<String> = passwords
+        .iter()
+        .map(|password| hex::encode_upper(Sha1::digest(password.as_bytes())))
+        .collect();
+    hashes.sort();
+    let contents: String = hashes.iter().map(|hash| format!("{}:10\n", hash)).collect();
+
+    let path = std::env::temp_dir().join(format!("auth-service-{}-pwned.txt", Uuid::new_v4()));
+    std::fs::write(&path, contents).unwrap();
+    path
 }
 
 pub fn get_random_email() -> String {
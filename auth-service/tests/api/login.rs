@@ -1,8 +1,7 @@
 use crate::helpers::{get_random_email, TestApp};
 use auth_service::{
-    domain::Email,
+    domain::{Email, TwoFAPurpose},
     routes::TwoFactorAuthResponse,
-    utils::constants::JWT_COOKIE_NAME,
     ErrorResponse,
 };
 
@@ -41,17 +40,23 @@ async fn should_return_400_if_invalid_input() {
     let random_email = get_random_email();
 
     let test_cases = [
-        serde_json::json!({
-            "email": "",
-            "password": "password123",
-        }),
-        serde_json::json!({
-            "email": &random_email,
-            "password": "",
-        }),
+        (
+            serde_json::json!({
+                "email": "",
+                "password": "password123",
+            }),
+            ("email", "required"),
+        ),
+        (
+            serde_json::json!({
+                "email": &random_email,
+                "password": "",
+            }),
+            ("password", "required"),
+        ),
     ];
 
-    for test_case in test_cases.iter() {
+    for (test_case, (field, code)) in test_cases.iter() {
         let response = TestApp::post_login(&app, test_case).await;
         assert_eq!(
             response.status().as_u16(),
@@ -60,10 +65,48 @@ async fn should_return_400_if_invalid_input() {
             test_case
         );
         let error_response: ErrorResponse = response.json().await.unwrap();
-        assert_eq!(error_response.error, "Invalid credentials");
+        assert_eq!(error_response.detail, "Invalid credentials");
+        assert_eq!(error_response.code, "invalid_credentials");
+        assert_eq!(error_response.errors.len(), 1);
+        assert_eq!(error_response.errors[0].field, *field);
+        assert_eq!(error_response.errors[0].code, *code);
     }
 }
 
+#[tokio::test]
+async fn should_not_tell_unknown_accounts_from_wrong_passwords() {
+    let app = TestApp::new().await;
+
+    let random_email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": &random_email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let wrong_password = app
+        .post_login(&serde_json::json!({
+            "email": &random_email,
+            "password": "wrongpassword",
+        }))
+        .await;
+    let unknown_account = app
+        .post_login(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "wrongpassword",
+        }))
+        .await;
+
+    assert_eq!(wrong_password.status(), unknown_account.status());
+    let wrong_password: ErrorResponse = wrong_password.json().await.unwrap();
+    let unknown_account: ErrorResponse = unknown_account.json().await.unwrap();
+    assert_eq!(wrong_password.code, unknown_account.code);
+    assert_eq!(wrong_password.detail, unknown_account.detail);
+    assert!(wrong_password.errors.is_empty());
+    assert!(unknown_account.errors.is_empty());
+}
+
 #[tokio::test]
 async fn should_return_401_if_incorrect_credentials() {
     // Call the log-in route with incorrect credentials and assert
@@ -94,7 +137,9 @@ async fn should_return_401_if_incorrect_credentials() {
     assert_eq!(login_response.status().as_u16(), 401, "Expected 401 for incorrect password");
     
     let error_response: ErrorResponse = login_response.json().await.unwrap();
-    assert_eq!(error_response.error, "Invalid credentials");
+    assert_eq!(error_response.detail, "Invalid credentials");
+    // Told apart from malformed credentials by the code rather than just the status
+    assert_eq!(error_response.code, "incorrect_credentials");
 }
 
 #[tokio::test]
@@ -124,10 +169,24 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == app.app_state.settings.current().jwt_cookie_name)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
+
+    // The cookie expires along with the token instead of lasting the browser session
+    let max_age = auth_cookie.max_age().expect("No Max-Age on the auth cookie");
+    let token_ttl_seconds = app.app_state.settings.current().token_ttl_seconds as u64;
+    assert!((token_ttl_seconds - 1..=token_ttl_seconds).contains(&max_age.as_secs()));
+
+    // The CSRF cookie is readable by the page, unlike the auth cookie
+    let csrf_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == app.app_state.settings.current().csrf_cookie_name())
+        .expect("No CSRF cookie found");
+    assert!(!csrf_cookie.value().is_empty());
+    assert!(!csrf_cookie.http_only());
+    assert!(auth_cookie.http_only());
 }
 
 #[tokio::test]
@@ -165,9 +224,108 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
     let two_fa_code_store = app.two_fa_code_store.read().await;
 
     let code_tuple = two_fa_code_store
-        .get_code(&Email::parse(random_email).unwrap())
+        .get_code(&Email::parse(random_email).unwrap(), TwoFAPurpose::Login)
         .await
         .expect("Failed to get 2FA code");
 
     assert_eq!(code_tuple.0.as_ref(), json_body.login_attempt_id);
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn should_return_423_once_too_many_failed_logins() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..4 {
+        let response = app.post_login(&wrong_login).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app.post_login(&wrong_login).await;
+    assert_eq!(response.status().as_u16(), 423);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "60");
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.detail, "Account temporarily locked");
+
+    // Even the correct password is refused while the account is locked
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+    let retry_after: u64 = response.headers()["retry-after"].to_str().unwrap().parse().unwrap();
+    assert!((1..=60).contains(&retry_after));
+
+    // The user is sent a link to unlock their account
+    assert!(app.get_link_param_from_last_email(&email, "unlockAccount").is_some());
+}
+
+#[tokio::test]
+async fn should_double_lockout_for_each_further_failure() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    let parsed_email = Email::parse(email.clone()).unwrap();
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..5 {
+        app.post_login(&wrong_login).await;
+    }
+
+    // Let the first lockout run out
+    let expire_lockout = || async {
+        let mut user_store = app.user_store.write().await;
+        let mut user = user_store.get_user(&parsed_email).await.unwrap();
+        user.lockout.locked_until = Some(0);
+        user_store.update_user(user).await.unwrap();
+    };
+    expire_lockout().await;
+
+    let response = app.post_login(&wrong_login).await;
+    assert_eq!(response.status().as_u16(), 423);
+    assert_eq!(response.headers().get("retry-after").unwrap(), "120");
+
+    // Logging in successfully resets the count
+    expire_lockout().await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_login(&wrong_login).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_only_email_an_unlock_link_for_the_first_lockout() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    let parsed_email = Email::parse(email.clone()).unwrap();
+
+    let unlock_emails = || {
+        app.email_client
+            .sent_emails()
+            .iter()
+            .filter(|sent| sent.content.contains("unlockAccount="))
+            .count()
+    };
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..5 {
+        app.post_login(&wrong_login).await;
+    }
+    assert_eq!(unlock_emails(), 1);
+
+    for _ in 0..3 {
+        {
+            let mut user_store = app.user_store.write().await;
+            let mut user = user_store.get_user(&parsed_email).await.unwrap();
+            user.lockout.locked_until = Some(0);
+            user_store.update_user(user).await.unwrap();
+        }
+        let response = app.post_login(&wrong_login).await;
+        assert_eq!(response.status().as_u16(), 423);
+    }
+    assert_eq!(unlock_emails(), 1);
+}
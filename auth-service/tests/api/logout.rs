@@ -1,4 +1,4 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::{utils::constants::CSRF_TOKEN_HEADER, ErrorResponse};
 use reqwest::{cookie::CookieStore, Url};
 
 use crate::helpers::{get_random_email, TestApp};
@@ -16,7 +16,7 @@ async fn should_return_400_if_jwt_cookie_missing() {
         .await
         .expect("Failed to parse response body as JSON");
 
-    assert_eq!(response_body.error, "Missing token");
+    assert_eq!(response_body.detail, "Missing token");
 }
 
 #[tokio::test]
@@ -27,7 +27,7 @@ async fn should_return_401_if_invalid_token() {
     app.cookie_jar.add_cookie_str(
         &format!(
             "{}=invalid; HttpOnly; SameSite=Lax; Path=/",
-            JWT_COOKIE_NAME
+            app.app_state.settings.current().jwt_cookie_name
         ),
         &Url::parse(&app.address).expect("Failed to parse URL"),
     );
@@ -63,7 +63,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == app.app_state.settings.current().jwt_cookie_name)
         .expect("No auth cookie found");
 
     assert!(!auth_cookie.value().is_empty());
@@ -76,7 +76,7 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     let auth_cookie = response
         .cookies()
-        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .find(|cookie| cookie.name() == app.app_state.settings.current().jwt_cookie_name)
         .expect("No auth cookie found");
 
     assert!(auth_cookie.value().is_empty());
@@ -125,5 +125,41 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
         .json()
         .await
         .expect("Failed to parse response body as JSON");
-    assert_eq!(response_body.error, "Missing token");
-}
\ No newline at end of file
+    assert_eq!(response_body.detail, "Missing token");
+}
+#[tokio::test]
+async fn should_return_403_if_csrf_token_missing_or_wrong() {
+    let app = TestApp::new().await;
+    let token = app.signup_and_login(&get_random_email(), "password123").await;
+
+    // A cross-site request carries the cookies, but can't read the CSRF cookie to send it back
+    let logout_url = format!("{}/logout", &app.address);
+    let forged_requests = [
+        app.http_client.post(&logout_url),
+        app.http_client.post(&logout_url).header(CSRF_TOKEN_HEADER, "forged"),
+    ];
+    for request in forged_requests {
+        let response = request.send().await.expect("Failed to execute request.");
+        assert_eq!(response.status().as_u16(), 403);
+
+        let response_body: ErrorResponse = response
+            .json()
+            .await
+            .expect("Failed to parse response body as JSON");
+        assert_eq!(response_body.detail, "Invalid CSRF token");
+    }
+
+    let is_banned = app
+        .banned_token_store
+        .read()
+        .await
+        .is_token_banned(token)
+        .await
+        .expect("Failed to check if token is banned");
+    assert!(!is_banned);
+
+    // Logging out removes the CSRF cookie along with the auth cookie
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app.csrf_token().is_none());
+}
@@ -1,7 +1,25 @@
+mod audit_events;
+mod change_email;
+mod change_password;
+mod config_reload;
+mod delete_account;
+mod errors;
+mod export_account;
+mod health;
 mod helpers;
 mod login;
 mod logout;
+mod metrics;
+mod password_strength;
+mod rate_limit;
+mod request_id;
+mod required_password_change;
+mod reset_password;
 mod root;
+mod shutdown;
 mod signup;
+mod tls;
+mod two_fa_settings;
+mod unlock_account;
 mod verify_2fa;
 mod verify_token;
@@ -0,0 +1,99 @@
+use crate::helpers::{get_random_email, TestApp};
+
+// Metrics are process-wide and every test app shares them, so
+// these tests only check that the expected series exist.
+async fn get_metrics_text(app: &TestApp) -> String {
+    let response = app.get_metrics().await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+
+    response.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn should_expose_request_and_login_metrics() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    app.signup_and_login(&email, "password123").await;
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let metrics = get_metrics_text(&app).await;
+
+    assert!(metrics.contains(r#"http_requests_total{method="POST",route="/login",status="401"}"#));
+    assert!(metrics.contains(
+        r#"http_request_duration_seconds_bucket{method="POST",route="/signup",status="201",le="#
+    ));
+    assert!(metrics.contains(r#"login_attempts_total{outcome="success"}"#));
+    assert!(metrics.contains(r#"login_attempts_total{outcome="bad_credentials"}"#));
+    assert!(metrics.contains(
+        r#"store_operation_duration_seconds_bucket{store="user",operation="validate_user",le="#
+    ));
+}
+
+#[tokio::test]
+async fn should_expose_2fa_login_and_verification_metrics() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    let metrics = get_metrics_text(&app).await;
+    assert!(metrics.contains(r#"login_attempts_total{outcome="2fa_required"}"#));
+}
+
+#[tokio::test]
+async fn should_count_banned_tokens() {
+    let app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), "password123")
+        .await;
+    let response = app.post_logout().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let metrics = get_metrics_text(&app).await;
+    assert!(metrics.contains(r#"banned_tokens_total{kind="token"}"#));
+}
+
+#[tokio::test]
+async fn should_count_2fa_verifications() {
+    let app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), "password123")
+        .await;
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let response = app
+        .post_confirm_enable_2fa(
+            &serde_json::json!({ "loginAttemptId": "invalid", "2FACode": "123456" }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let metrics = get_metrics_text(&app).await;
+    assert!(
+        metrics.contains(r#"two_fa_verifications_total{flow="enable",outcome="invalid_input"}"#)
+    );
+}
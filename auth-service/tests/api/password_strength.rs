@@ -0,0 +1,92 @@
+use auth_service::{domain::PasswordPolicy, routes::PasswordStrengthResponse, ErrorResponse};
+
+use crate::helpers::TestApp;
+
+#[tokio::test]
+async fn should_score_a_password_with_feedback() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_password_strength(&serde_json::json!({ "password": "password" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let strength: PasswordStrengthResponse = response
+        .json()
+        .await
+        .expect("Could not deserialize response body to PasswordStrengthResponse");
+    assert_eq!(strength.score, 0);
+    assert_eq!(strength.warning.as_deref(), Some("This is a top-10 common password"));
+    assert!(!strength.suggestions.is_empty());
+    // The default policy doesn't check strength
+    assert_eq!(strength.min_score, 0);
+    assert!(strength.acceptable);
+
+    let response = app
+        .post_password_strength(&serde_json::json!({
+            "password": "correct horse battery staple"
+        }))
+        .await;
+    let strength: PasswordStrengthResponse = response.json().await.unwrap();
+    assert_eq!(strength.score, 4);
+    assert_eq!(strength.warning, None);
+}
+
+#[tokio::test]
+async fn should_tell_whether_the_policy_accepts_it() {
+    let app = TestApp::new().await;
+    app.set_password_policy(PasswordPolicy {
+        min_strength_score: 3,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_password_strength(&serde_json::json!({ "password": "password1" }))
+        .await;
+    let strength: PasswordStrengthResponse = response.json().await.unwrap();
+    assert_eq!(strength.min_score, 3);
+    assert!(!strength.acceptable);
+
+    let response = app
+        .post_password_strength(&serde_json::json!({
+            "password": "correct horse battery staple"
+        }))
+        .await;
+    let strength: PasswordStrengthResponse = response.json().await.unwrap();
+    assert!(strength.acceptable);
+}
+
+#[tokio::test]
+async fn should_count_the_email_as_an_easy_guess() {
+    let app = TestApp::new().await;
+
+    let password = serde_json::json!({ "password": "kensington-marlowe" });
+    let response = app.post_password_strength(&password).await;
+    let without_email: PasswordStrengthResponse = response.json().await.unwrap();
+
+    let response = app
+        .post_password_strength(&serde_json::json!({
+            "password": "kensington-marlowe",
+            "email": "kensington.marlowe@example.com"
+        }))
+        .await;
+    let with_email: PasswordStrengthResponse = response.json().await.unwrap();
+    assert!(with_email.guesses_log10 < without_email.guesses_log10);
+    assert!(with_email.score < without_email.score);
+}
+
+#[tokio::test]
+async fn should_return_400_for_an_invalid_email() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_password_strength(&serde_json::json!({
+            "password": "correct horse battery staple",
+            "email": "not-an-email"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.errors[0].field, "email");
+}
@@ -0,0 +1,167 @@
+use std::net::IpAddr;
+
+use auth_service::{
+    domain::RateLimit,
+    utils::rate_limit::{RateLimitConfig, RouteRateLimits},
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn post_login_forwarded_for(
+    app: &TestApp,
+    forwarded_for: &str,
+    email: &str,
+) -> reqwest::Response {
+    app.http_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", forwarded_for)
+        .json(&serde_json::json!({ "email": email, "password": "password123" }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+fn localhost() -> Vec<IpAddr> {
+    vec!["127.0.0.1".parse().unwrap()]
+}
+
+#[tokio::test]
+async fn should_return_429_with_retry_after_once_ip_limit_exceeded() {
+    let app = TestApp::with_rate_limits(RateLimitConfig::empty().with_route(
+        "/login",
+        RouteRateLimits {
+            per_ip: Some(RateLimit::new(2, 60)),
+            per_account: None,
+        },
+    ))
+    .await;
+
+    for _ in 0..2 {
+        let response = app
+            .post_login(
+                &serde_json::json!({ "email": get_random_email(), "password": "password123" }),
+            )
+            .await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": get_random_email(), "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let retry_after: u64 = response
+        .headers()
+        .get("retry-after")
+        .expect("No Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=30).contains(&retry_after));
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "Too many requests");
+
+    // Other routes aren't affected
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+#[tokio::test]
+async fn should_limit_account_across_ips() {
+    let app = TestApp::with_rate_limits(
+        RateLimitConfig::empty()
+            .with_trusted_proxies(localhost())
+            .with_route(
+                "/login",
+                RouteRateLimits {
+                    per_ip: Some(RateLimit::new(1, 60)),
+                    per_account: Some(RateLimit::new(2, 60)),
+                },
+            ),
+    )
+    .await;
+    let email = get_random_email();
+
+    let response = post_login_forwarded_for(&app, "1.1.1.1", &email).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_login_forwarded_for(&app, "2.2.2.2", &email).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Differently cased emails share the same bucket
+    let response = post_login_forwarded_for(&app, "3.3.3.3", &email.to_uppercase()).await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = post_login_forwarded_for(&app, "4.4.4.4", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_ignore_forwarded_for_from_untrusted_peer() {
+    let app = TestApp::with_rate_limits(RateLimitConfig::empty().with_route(
+        "/login",
+        RouteRateLimits {
+            per_ip: Some(RateLimit::new(1, 60)),
+            per_account: None,
+        },
+    ))
+    .await;
+
+    let response = post_login_forwarded_for(&app, "1.1.1.1", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Rotating the header doesn't get a fresh bucket
+    let response = post_login_forwarded_for(&app, "2.2.2.2", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_limit_by_forwarded_for_from_trusted_peer() {
+    let app = TestApp::with_rate_limits(
+        RateLimitConfig::empty()
+            .with_trusted_proxies(localhost())
+            .with_route(
+                "/login",
+                RouteRateLimits {
+                    per_ip: Some(RateLimit::new(1, 60)),
+                    per_account: None,
+                },
+            ),
+    )
+    .await;
+
+    let response = post_login_forwarded_for(&app, "1.1.1.1", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_login_forwarded_for(&app, "2.2.2.2", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let response = post_login_forwarded_for(&app, "1.1.1.1", &get_random_email()).await;
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[tokio::test]
+async fn should_limit_login_attempts_per_account_by_default() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+
+    let mut statuses = Vec::new();
+    for _ in 0..11 {
+        let response = app
+            .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+            .await;
+        statuses.push(response.status().as_u16());
+    }
+
+    assert!(statuses[..10].iter().all(|status| *status == 401));
+    assert_eq!(statuses[10], 429);
+}
@@ -0,0 +1,45 @@
+use auth_service::utils::constants::REQUEST_ID_HEADER;
+
+use crate::helpers::{get_random_email, TestApp};
+
+#[tokio::test]
+async fn should_generate_request_id_when_missing() {
+    let app = TestApp::new().await;
+
+    let first = app
+        .post_login(&serde_json::json!({ "email": get_random_email(), "password": "password123" }))
+        .await;
+    let second = app.get_root().await;
+
+    let first_id = first
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("No request ID header");
+    let second_id = second
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .expect("No request ID header");
+
+    assert!(uuid::Uuid::parse_str(first_id.to_str().unwrap()).is_ok());
+    assert_ne!(first_id, second_id);
+}
+
+#[tokio::test]
+async fn should_propagate_request_id_from_caller() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .http_client
+        .post(format!("{}/verify-token", &app.address))
+        .header(REQUEST_ID_HEADER, "caller-request-id")
+        .json(&serde_json::json!({ "token": "invalid" }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers().get(REQUEST_ID_HEADER).unwrap(),
+        "caller-request-id"
+    );
+}
@@ -0,0 +1,188 @@
+use auth_service::{
+    domain::{Email, PasswordPolicy},
+    routes::{PasswordChangeRequiredResponse, PasswordChangeResponse},
+    utils::constants::test::ADMIN_API_KEY,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Sets the user's flag directly, rather than through the admin endpoint, whose revocation of
+// every token issued up to now would catch a login in the same second too
+async fn require_password_change(app: &TestApp, email: &str) {
+    let email = Email::parse(email.to_owned()).unwrap();
+    let mut user_store = app.app_state.user_store.write().await;
+    let mut user = user_store.get_user(&email).await.unwrap();
+    user.must_change_password = true;
+    user_store.update_user(user).await.unwrap();
+}
+
+async fn login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({ "email": email, "password": password }))
+        .await
+}
+
+#[tokio::test]
+async fn should_require_password_change_with_admin_api_key() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_admin_require_password_change(&serde_json::json!({ "email": email }), ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Their sessions end straight away
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: PasswordChangeRequiredResponse = response.json().await.unwrap();
+    assert_eq!(body.message, "Password change required");
+    assert_eq!(body.reason, "required");
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_admin_api_key() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_admin_require_password_change(&serde_json::json!({ "email": email }), "wrong-key")
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_404_if_admin_requires_change_for_unknown_user() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_admin_require_password_change(
+            &serde_json::json!({ "email": get_random_email() }),
+            ADMIN_API_KEY,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn should_only_let_the_restricted_token_change_the_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    require_password_change(&app, &email).await;
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 202);
+    let token = response
+        .cookies()
+        .find(|c| c.name() == app.app_state.settings.current().jwt_cookie_name)
+        .expect("No auth cookie found");
+    assert!(token.max_age().unwrap().as_secs() <= 300);
+    let token = token.value().to_owned();
+
+    // It doesn't start a session, so other services don't accept it
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_account_export().await;
+    assert_eq!(response.status().as_u16(), 403);
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.code, "password_change_required");
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: PasswordChangeResponse = response.json().await.unwrap();
+    assert_eq!(body.message, "Password changed. Please log in again.");
+
+    // Once changed, logging in starts a session again
+    let response = login(&app, &email, "new-password-456").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_require_password_change_once_expired() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        max_age_days: 90,
+        ..PasswordPolicy::default()
+    });
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    {
+        let email = Email::parse(email.clone()).unwrap();
+        let mut user_store = app.app_state.user_store.write().await;
+        let mut user = user_store.get_user(&email).await.unwrap();
+        user.password_changed_at -= 90 * 86400;
+        user_store.update_user(user).await.unwrap();
+    }
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 202);
+    let body: PasswordChangeRequiredResponse = response.json().await.unwrap();
+    assert_eq!(body.reason, "expired");
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "currentPassword": "password123",
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login(&app, &email, "new-password-456").await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_ask_for_2fa_before_a_password_change() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    require_password_change(&app, &email).await;
+
+    let response = login(&app, &email, "password123").await;
+    assert_eq!(response.status().as_u16(), 206);
+
+    // Completing 2FA doesn't skip the password change
+    let body = app.pending_2fa_login(&email).await;
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 202);
+    let token = response
+        .cookies()
+        .find(|c| c.name() == app.app_state.settings.current().jwt_cookie_name)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let body: PasswordChangeRequiredResponse = response.json().await.unwrap();
+    assert_eq!(body.reason, "required");
+
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_account_export().await;
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,213 @@
+use auth_service::{
+    domain::{password_policy::PasswordBlocklist, PasswordPolicy},
+    routes::PasswordResetResponse,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, write_breached_hashes, TestApp};
+
+#[tokio::test]
+async fn should_reset_password_with_emailed_link() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let token = app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let reset_token = app
+        .get_link_param_from_last_email(&email, "resetPassword")
+        .expect("No reset link sent");
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<PasswordResetResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordResetResponse"),
+        PasswordResetResponse {
+            message: "Password reset. You can log in with the new password.".to_owned()
+        }
+    );
+
+    // Sessions started with the old password end
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "new-password-456" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The link only works once
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "another-password-789"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_not_tell_whether_an_account_exists() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let existing = app
+        .post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    let unknown_email = get_random_email();
+    let unknown = app
+        .post_forgot_password(&serde_json::json!({ "email": unknown_email }))
+        .await;
+
+    assert_eq!(existing.status(), unknown.status());
+    assert_eq!(
+        existing.json::<PasswordResetResponse>().await.unwrap(),
+        unknown.json::<PasswordResetResponse>().await.unwrap()
+    );
+    assert!(app
+        .get_link_param_from_last_email(&unknown_email, "resetPassword")
+        .is_none());
+}
+
+#[tokio::test]
+async fn should_apply_password_policy_to_new_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    app.set_password_policy(PasswordPolicy {
+        blocklist: PasswordBlocklist::parse("letmein123\n"),
+        ..PasswordPolicy::default()
+    });
+
+    app.post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    let reset_token = app
+        .get_link_param_from_last_email(&email, "resetPassword")
+        .expect("No reset link sent");
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "LetMeIn123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.errors.len(), 1);
+    assert_eq!(error_response.errors[0].field, "newPassword");
+    assert_eq!(error_response.errors[0].code, "blocklisted");
+
+    // The link still works with a better password
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_reject_a_breached_new_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    let breached_hashes = write_breached_hashes(&["iloveyou2024"]);
+    let mut password_policy = PasswordPolicy {
+        breached_hashes_path: Some(breached_hashes.clone()),
+        ..PasswordPolicy::default()
+    };
+    password_policy.load_breached_passwords().unwrap();
+    app.set_password_policy(password_policy);
+
+    app.post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    let reset_token = app
+        .get_link_param_from_last_email(&email, "resetPassword")
+        .expect("No reset link sent");
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "iloveyou2024"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.errors.len(), 1);
+    assert_eq!(error_response.errors[0].field, "newPassword");
+    assert_eq!(error_response.errors[0].code, "breached");
+    std::fs::remove_file(breached_hashes).unwrap();
+}
+
+#[tokio::test]
+async fn should_reject_the_current_password() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    app.post_forgot_password(&serde_json::json!({ "email": email }))
+        .await;
+    let reset_token = app
+        .get_link_param_from_last_email(&email, "resetPassword")
+        .expect("No reset link sent");
+
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "password123"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.errors.len(), 1);
+    assert_eq!(error_response.errors[0].code, "reused");
+    assert_eq!(
+        error_response.errors[0].message,
+        "Password must not be any of your last 5 passwords"
+    );
+
+    // The link can be used again with another password
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": reset_token,
+            "newPassword": "new-password-456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_reset_token() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "token": "invalid", "newPassword": "new-password-456" }),
+        serde_json::json!({
+            "token": uuid::Uuid::new_v4().to_string(),
+            "newPassword": "new-password-456"
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_reset_password(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Long enough for a request to reach the server before we act on it
+const IN_FLIGHT_DELAY: Duration = Duration::from_millis(200);
+
+#[tokio::test]
+async fn should_stop_accepting_connections_after_shutdown() {
+    let app = TestApp::new().await;
+    assert_eq!(app.get_root().await.status().as_u16(), 200);
+
+    app.shutdown_handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), app.server)
+        .await
+        .expect("Server did not stop")
+        .expect("Server task panicked")
+        .expect("Server failed");
+
+    // A fresh client, so no pooled connection is reused
+    let response = reqwest::Client::new()
+        .get(format!("{}/", &app.address))
+        .send()
+        .await;
+    assert!(response.is_err());
+}
+
+#[tokio::test]
+async fn should_drain_in_flight_requests() {
+    let app = TestApp::new().await;
+
+    // Holding the lock keeps the signup below in flight until we let go
+    let user_store = app.user_store.write().await;
+
+    let signup = tokio::spawn({
+        let http_client = app.http_client.clone();
+        let address = app.address.clone();
+        async move {
+            http_client
+                .post(format!("{}/signup", address))
+                .json(&serde_json::json!({
+                    "email": get_random_email(),
+                    "password": "password123",
+                    "requires2FA": false
+                }))
+                .send()
+                .await
+        }
+    });
+    tokio::time::sleep(IN_FLIGHT_DELAY).await;
+
+    app.shutdown_handle.shutdown();
+    tokio::time::sleep(IN_FLIGHT_DELAY).await;
+    assert!(!app.server.is_finished());
+
+    drop(user_store);
+
+    let response = signup
+        .await
+        .expect("Signup task panicked")
+        .expect("Failed to execute request.");
+    assert_eq!(response.status().as_u16(), 201);
+
+    tokio::time::timeout(Duration::from_secs(5), app.server)
+        .await
+        .expect("Server did not stop")
+        .expect("Server task panicked")
+        .expect("Server failed");
+}
+
+#[tokio::test]
+async fn should_stop_when_drain_timeout_elapses() {
+    let app = TestApp::with_drain_timeout(Duration::from_millis(100)).await;
+
+    // The signup below never gets the lock, so it can't finish on its own
+    let _user_store = app.user_store.write().await;
+
+    let _signup = tokio::spawn({
+        let http_client = app.http_client.clone();
+        let address = app.address.clone();
+        async move {
+            http_client
+                .post(format!("{}/signup", address))
+                .json(&serde_json::json!({
+                    "email": get_random_email(),
+                    "password": "password123",
+                    "requires2FA": false
+                }))
+                .send()
+                .await
+        }
+    });
+    tokio::time::sleep(IN_FLIGHT_DELAY).await;
+
+    app.shutdown_handle.shutdown();
+    tokio::time::timeout(Duration::from_secs(5), app.server)
+        .await
+        .expect("Server did not stop")
+        .expect("Server task panicked")
+        .expect("Server failed");
+}
@@ -1,5 +1,9 @@
-use crate::helpers::{get_random_email, TestApp};
-use auth_service::{routes::SignupResponse, ErrorResponse};
+use crate::helpers::{get_random_email, write_breached_hashes, TestApp};
+use auth_service::{
+    domain::{FieldError, PasswordPolicy},
+    routes::SignupResponse,
+    ErrorResponse,
+};
 
 #[tokio::test]
 async fn should_return_422_if_malformed_input() {
@@ -94,17 +98,184 @@ async fn should_return_400_if_invalid_input() {
         let response = app.post_signup(test_case).await;
         assert_eq!(response.status().as_u16(), 400, "Failed for input: {:?}", test_case);
 
-        assert_eq!(
-            response
-                .json::<ErrorResponse>()
-                .await
-                .expect("Could not deserialize response body to ErrorResponse")
-                .error,
-            "Invalid credentials".to_owned()
-        );
+        let error_response = response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse");
+        assert_eq!(error_response.detail, "Invalid credentials".to_owned());
+        assert_eq!(error_response.errors.len(), 1, "Failed for input: {:?}", test_case);
     }
 }
 
+#[tokio::test]
+async fn should_return_an_error_for_each_invalid_field() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "testatexample.com",
+            "password": "short",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(
+        error_response.errors,
+        vec![
+            FieldError {
+                field: "email".to_owned(),
+                code: "invalid_format".to_owned(),
+                message: "Email is not a valid email address".to_owned(),
+                suggestions: Vec::new(),
+            },
+            FieldError {
+                field: "password".to_owned(),
+                code: "too_short".to_owned(),
+                message: "Password must be at least 8 characters".to_owned(),
+                suggestions: Vec::new(),
+            },
+        ]
+    );
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "",
+            "password": "",
+            "requires2FA": false
+        }))
+        .await;
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    let codes: Vec<_> = error_response
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(codes, [("email", "required"), ("password", "required")]);
+}
+
+#[tokio::test]
+async fn should_apply_the_password_policy() {
+    let app = TestApp::new().await;
+
+    // "aaaaaaaa" is long enough for the default policy
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "aaaaaaaa",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.set_password_policy(PasswordPolicy {
+        min_length: 12,
+        require_digit: true,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": "jordan@example.com",
+            "password": "jordan-pass",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    let codes: Vec<_> = error_response
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            ("password", "too_short"),
+            ("password", "missing_digit"),
+            ("password", "contains_email"),
+        ]
+    );
+    assert_eq!(
+        error_response.errors[0].message,
+        "Password must be at least 12 characters"
+    );
+}
+
+#[tokio::test]
+async fn should_reject_breached_passwords() {
+    let app = TestApp::new().await;
+    let breached_hashes = write_breached_hashes(&["hunter2hunter2", "trustno1!"]);
+    let mut password_policy = PasswordPolicy {
+        breached_hashes_path: Some(breached_hashes.clone()),
+        ..PasswordPolicy::default()
+    };
+    password_policy.load_breached_passwords().unwrap();
+    app.set_password_policy(password_policy);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "hunter2hunter2",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    let codes: Vec<_> = error_response
+        .errors
+        .iter()
+        .map(|error| (error.field.as_str(), error.code.as_str()))
+        .collect();
+    assert_eq!(codes, [("password", "breached")]);
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "hunter3hunter3",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    std::fs::remove_file(breached_hashes).unwrap();
+}
+
+#[tokio::test]
+async fn should_reject_passwords_that_are_easy_to_guess() {
+    let app = TestApp::new().await;
+    app.set_password_policy(PasswordPolicy {
+        min_strength_score: 3,
+        ..PasswordPolicy::default()
+    });
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "qwertyuiop",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.errors.len(), 1);
+    let error = &error_response.errors[0];
+    assert_eq!((error.field.as_str(), error.code.as_str()), ("password", "too_weak"));
+    assert!(!error.suggestions.is_empty());
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": get_random_email(),
+            "password": "correct horse battery staple",
+            "requires2FA": false
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
 #[tokio::test]
 async fn should_return_409_if_email_already_exists() {
     let app = TestApp::new().await;
@@ -124,7 +295,7 @@ async fn should_return_409_if_email_already_exists() {
             .json::<ErrorResponse>()
             .await
             .expect("Could not deserialize response body to ErrorResponse")
-            .error,
+            .detail,
         "User already exists".to_owned()
     );
 }
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use auth_service::utils::{constants::CONFIG_POLL_INTERVAL_SECONDS, tls::TlsSettings};
+use rcgen::CertifiedKey;
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn generate_certificate() -> CertifiedKey {
+    rcgen::generate_simple_self_signed(vec!["localhost".to_owned()])
+        .expect("Failed to generate certificate")
+}
+
+fn root_certificate(certificate: &CertifiedKey) -> reqwest::Certificate {
+    reqwest::Certificate::from_pem(certificate.cert.pem().as_bytes()).unwrap()
+}
+
+fn write_certificate(certificate: &CertifiedKey) -> TlsSettings {
+    let id = Uuid::new_v4();
+    let tls = TlsSettings {
+        cert_path: std::env::temp_dir().join(format!("auth-service-{}-cert.pem", id)),
+        key_path: std::env::temp_dir().join(format!("auth-service-{}-key.pem", id)),
+    };
+    overwrite_certificate(&tls, certificate);
+    tls
+}
+
+fn overwrite_certificate(tls: &TlsSettings, certificate: &CertifiedKey) {
+    std::fs::write(&tls.cert_path, certificate.cert.pem()).unwrap();
+    std::fs::write(&tls.key_path, certificate.key_pair.serialize_pem()).unwrap();
+}
+
+fn remove_certificate(tls: &TlsSettings) {
+    std::fs::remove_file(&tls.cert_path).unwrap();
+    std::fs::remove_file(&tls.key_path).unwrap();
+}
+
+#[tokio::test]
+async fn should_serve_https_and_set_secure_auth_cookie() {
+    let certificate = generate_certificate();
+    let tls = write_certificate(&certificate);
+    let app = TestApp::with_tls(tls.clone(), root_certificate(&certificate)).await;
+    assert!(app.address.starts_with("https://"));
+
+    let email = get_random_email();
+    let signup_body = serde_json::json!({
+        "email": email,
+        "password": "password123",
+        "requires2FA": false
+    });
+    assert_eq!(app.post_signup(&signup_body).await.status().as_u16(), 201);
+
+    let login_body = serde_json::json!({ "email": email, "password": "password123" });
+    let response = app.post_login(&login_body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == app.app_state.settings.current().jwt_cookie_name)
+        .expect("No auth cookie found");
+    assert!(auth_cookie.secure());
+
+    remove_certificate(&tls);
+}
+
+#[tokio::test]
+async fn should_serve_renewed_certificate_without_restart() {
+    let certificate = generate_certificate();
+    let tls = write_certificate(&certificate);
+    let app = TestApp::with_tls(tls.clone(), root_certificate(&certificate)).await;
+
+    let renewed = generate_certificate();
+    let renewed_client = reqwest::Client::builder()
+        .add_root_certificate(root_certificate(&renewed))
+        .build()
+        .unwrap();
+    let get_live = || renewed_client.get(format!("{}/health/live", &app.address)).send();
+    assert!(get_live().await.is_err());
+
+    overwrite_certificate(&tls, &renewed);
+
+    // The files are checked for changes every CONFIG_POLL_INTERVAL_SECONDS
+    let mut served = false;
+    for _ in 0..(CONFIG_POLL_INTERVAL_SECONDS * 4) {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        if get_live().await.is_ok_and(|response| response.status().is_success()) {
+            served = true;
+            break;
+        }
+    }
+    assert!(served, "the renewed certificate was not served");
+
+    // Clients that only trust the old certificate can't connect anymore
+    let response = app
+        .http_client
+        .get(format!("{}/health/live", &app.address))
+        .send()
+        .await;
+    assert!(response.is_err());
+
+    remove_certificate(&tls);
+}
@@ -0,0 +1,249 @@
+use auth_service::{
+    domain::{Email, TwoFAPurpose},
+    routes::TwoFactorAuthResponse,
+    ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Returns the body expected by the confirm routes for the user's pending 2FA challenge
+async fn pending_challenge(app: &TestApp, email: &str, purpose: TwoFAPurpose) -> serde_json::Value {
+    let (login_attempt_id, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&Email::parse(email.to_owned()).unwrap(), purpose)
+        .await
+        .expect("Failed to get 2FA code");
+
+    serde_json::json!({
+        "loginAttemptId": login_attempt_id.as_ref(),
+        "2FACode": code.as_ref()
+    })
+}
+
+async fn enable_2fa(app: &TestApp, email: &str) {
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let challenge = pending_challenge(app, email, TwoFAPurpose::Enable).await;
+    let response = app.post_confirm_enable_2fa(&challenge).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_jwt_cookie_missing() {
+    let app = TestApp::new().await;
+
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "Missing token");
+}
+
+#[tokio::test]
+async fn should_send_code_and_enable_2fa_once_confirmed() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let json_body = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse");
+    let challenge = pending_challenge(&app, &email, TwoFAPurpose::Enable).await;
+    assert_eq!(challenge["loginAttemptId"], json_body.login_attempt_id);
+
+    let code_email = app
+        .email_client
+        .sent_emails()
+        .pop()
+        .expect("No 2FA code sent");
+    assert_eq!(code_email.recipient.as_ref(), email);
+    assert!(code_email
+        .content
+        .contains(challenge["2FACode"].as_str().unwrap()));
+
+    let response = app.post_confirm_enable_2fa(&challenge).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Logging in now requires 2FA
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_code() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let mut challenge = pending_challenge(&app, &email, TwoFAPurpose::Enable).await;
+    let code = challenge["2FACode"].as_str().unwrap().to_owned();
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    challenge["2FACode"] = serde_json::json!(wrong_code);
+
+    let response = app.post_confirm_enable_2fa(&challenge).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_400_if_malformed_challenge() {
+    let app = TestApp::new().await;
+    app.signup_and_login(&get_random_email(), "password123")
+        .await;
+
+    let test_cases = [
+        serde_json::json!({ "loginAttemptId": "invalid", "2FACode": "123456" }),
+        serde_json::json!({ "loginAttemptId": uuid::Uuid::new_v4().to_string(), "2FACode": "12345" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_confirm_enable_2fa(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_return_409_if_2fa_already_in_requested_state() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    enable_2fa(&app, &email).await;
+
+    let response = app.post_enable_2fa().await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_password_on_disable() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    enable_2fa(&app, &email).await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "wrongpassword" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let has_code = app
+        .two_fa_code_store
+        .read()
+        .await
+        .has_code(&Email::parse(email).unwrap())
+        .await
+        .unwrap();
+    assert!(!has_code);
+}
+
+#[tokio::test]
+async fn should_lock_account_after_too_many_incorrect_passwords_on_disable() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    enable_2fa(&app, &email).await;
+
+    let body = serde_json::json!({ "password": "wrongpassword" });
+    for _ in 0..4 {
+        let response = app.post_disable_2fa(&body).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    let response = app.post_disable_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 423);
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+}
+
+#[tokio::test]
+async fn should_only_accept_a_code_for_the_challenge_it_was_sent_for() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    enable_2fa(&app, &email).await;
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+    let login = app.pending_2fa_login(&email).await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // The login code can't turn 2FA off
+    let response = app.post_confirm_disable_2fa(&login).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Nor did starting the challenge replace it
+    let response = app.post_verify_2fa(&login).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // And the disable code doesn't complete a login
+    let mut disable = pending_challenge(&app, &email, TwoFAPurpose::Disable).await;
+    disable["email"] = serde_json::json!(email);
+    let response = app.post_verify_2fa(&disable).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_confirm_disable_2fa(&disable).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_disable_2fa_after_password_and_challenge() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+    enable_2fa(&app, &email).await;
+
+    let response = app
+        .post_disable_2fa(&serde_json::json!({ "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let challenge = pending_challenge(&app, &email, TwoFAPurpose::Disable).await;
+    let response = app.post_confirm_disable_2fa(&challenge).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The code can't be used twice
+    let response = app.post_confirm_disable_2fa(&challenge).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
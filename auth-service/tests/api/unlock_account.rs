@@ -0,0 +1,150 @@
+use auth_service::{
+    routes::UnlockAccountResponse, utils::constants::test::ADMIN_API_KEY, ErrorResponse,
+};
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up a user and fails enough logins to lock their account
+async fn locked_account(app: &TestApp) -> String {
+    let email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let wrong_login = serde_json::json!({ "email": email, "password": "wrongpassword" });
+    for _ in 0..5 {
+        app.post_login(&wrong_login).await;
+    }
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+
+    email
+}
+
+#[tokio::test]
+async fn should_unlock_account_with_emailed_link() {
+    let app = TestApp::new().await;
+    let email = locked_account(&app).await;
+
+    let token = app
+        .get_link_param_from_last_email(&email, "unlockAccount")
+        .expect("No unlock link sent");
+
+    let response = app
+        .post_unlock_account(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .json::<UnlockAccountResponse>()
+            .await
+            .expect("Could not deserialize response body to UnlockAccountResponse"),
+        UnlockAccountResponse {
+            message: "Account unlocked".to_owned()
+        }
+    );
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The link only works once
+    let response = app
+        .post_unlock_account(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_unlock_token() {
+    let app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({ "token": "invalid" }),
+        serde_json::json!({ "token": uuid::Uuid::new_v4().to_string() }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_unlock_account(test_case).await;
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+}
+
+#[tokio::test]
+async fn should_not_accept_other_emailed_tokens() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    let new_email = get_random_email();
+    app.signup_and_login(&email, "password123").await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({ "newEmail": new_email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let token = app
+        .get_link_param_from_last_email(&new_email, "confirmEmailChange")
+        .expect("No confirmation link sent");
+
+    let response = app
+        .post_unlock_account(&serde_json::json!({ "token": token }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_unlock_account_with_admin_api_key() {
+    let app = TestApp::new().await;
+    let email = locked_account(&app).await;
+
+    let response = app
+        .post_admin_unlock_account(&serde_json::json!({ "email": email }), ADMIN_API_KEY)
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_admin_api_key() {
+    let app = TestApp::new().await;
+    let email = locked_account(&app).await;
+
+    let response = app
+        .post_admin_unlock_account(&serde_json::json!({ "email": email }), "wrong-key")
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 423);
+}
+
+#[tokio::test]
+async fn should_return_404_if_admin_unlocks_unknown_user() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_admin_unlock_account(
+            &serde_json::json!({ "email": get_random_email() }),
+            ADMIN_API_KEY,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response_body: ErrorResponse = response
+        .json()
+        .await
+        .expect("Failed to parse response body as JSON");
+    assert_eq!(response_body.detail, "User not found");
+}
@@ -1,10 +1,121 @@
-use crate::helpers::TestApp;
+use auth_service::ErrorResponse;
+
+use crate::helpers::{get_random_email, TestApp};
+
+// Signs up a user with 2FA and logs in, leaving the 2FA login pending
+async fn start_2fa_login(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app
+        .post_login(&serde_json::json!({ "email": email, "password": "password123" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 206);
+}
 
 #[tokio::test]
-async fn verify_2fa_returns_200() {
+async fn should_return_200_and_start_a_session_with_the_correct_code() {
     let app = TestApp::new().await;
+    let email = get_random_email();
+    start_2fa_login(&app, &email).await;
 
-    let response = app.post_verify_2fa().await;
+    let body = app.pending_2fa_login(&email).await;
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
 
+    let token = response
+        .cookies()
+        .find(|c| c.name() == app.app_state.settings.current().jwt_cookie_name)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+    let response = app.post_verify_token(&serde_json::json!({ "token": token })).await;
     assert_eq!(response.status().as_u16(), 200);
+
+    // The code can't be used again
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_email_the_code_at_login() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    start_2fa_login(&app, &email).await;
+
+    let body = app.pending_2fa_login(&email).await;
+    let code_email = app
+        .email_client
+        .sent_emails()
+        .pop()
+        .expect("No 2FA code sent");
+    assert_eq!(code_email.recipient.as_ref(), email);
+    assert!(code_email.content.contains(body["2FACode"].as_str().unwrap()));
+}
+
+#[tokio::test]
+async fn should_return_401_if_incorrect_code() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    start_2fa_login(&app, &email).await;
+
+    let mut body = app.pending_2fa_login(&email).await;
+    let code = body["2FACode"].as_str().unwrap();
+    let wrong_code = if code == "000000" { "111111" } else { "000000" };
+    body["2FACode"] = serde_json::json!(wrong_code);
+
+    let response = app.post_verify_2fa(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+    let error_response: ErrorResponse = response.json().await.unwrap();
+    assert_eq!(error_response.code, "incorrect_credentials");
+}
+
+#[tokio::test]
+async fn should_return_401_without_a_pending_login() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": get_random_email(),
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+            "2FACode": "123456"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let app = TestApp::new().await;
+    let email = get_random_email();
+    start_2fa_login(&app, &email).await;
+    let body = app.pending_2fa_login(&email).await;
+
+    let test_cases = [
+        ("email", "not-an-email"),
+        ("loginAttemptId", "not-a-uuid"),
+        ("2FACode", "12345"),
+    ];
+    for (field, value) in test_cases {
+        let mut body = body.clone();
+        body[field] = serde_json::json!(value);
+        let response = app.post_verify_2fa(&body).await;
+        assert_eq!(response.status().as_u16(), 400, "Failed for {}", field);
+    }
+}
+
+#[tokio::test]
+async fn should_return_422_if_malformed_input() {
+    let app = TestApp::new().await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+    assert_eq!(response.status().as_u16(), 422);
 }
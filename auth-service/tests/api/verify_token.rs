@@ -1,4 +1,4 @@
-use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
+use auth_service::ErrorResponse;
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -36,7 +36,7 @@ async fn should_return_200_valid_token() {
 
     let cookie = response
         .cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .find(|c| c.name() == app.app_state.settings.current().jwt_cookie_name)
         .expect("No auth cookie found");
 
     let token = cookie.value();
@@ -65,7 +65,7 @@ async fn should_return_401_if_invalid_token() {
             .json::<ErrorResponse>()
             .await
             .expect("Could not deserialize response body to ErrorResponse")
-            .error,
+            .detail,
         "Invalid token".to_owned()
     );
 }
@@ -96,7 +96,7 @@ async fn should_return_401_if_banned_token() {
 
     let cookie = response
         .cookies()
-        .find(|c| c.name() == JWT_COOKIE_NAME)
+        .find(|c| c.name() == app.app_state.settings.current().jwt_cookie_name)
         .expect("No auth cookie found");
 
     let token = cookie.value();